@@ -0,0 +1,3074 @@
+//! High-level operations shared by the CLI and the TUI.
+//!
+//! Each operation loads the config through the [`Store`], talks to Docker
+//! through the [`DockerApi`] and returns human-readable output lines so the
+//! caller decides how to present them.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+
+use crate::config::{Config, ConfigManager, Container, Store};
+use crate::docker::{DockerApi, CONFIG_HASH_LABEL, MANAGED_BY_LABEL, MANAGED_BY_VALUE};
+use crate::manager::{config_hash, BuildOverwrite, ProxyManager};
+
+/// How long `start` waits for the new proxy to come up before failing.
+const START_HEALTH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Poll interval while waiting for connections to drain.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the idle wait reports remaining connections.
+const IDLE_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Exit code for mutations refused in read-only mode, so scripts can tell
+/// "not allowed here" from real failures.
+pub const READ_ONLY_EXIT_CODE: i32 = 4;
+
+/// Marker error for mutations refused in read-only mode; the binary maps
+/// it to [`READ_ONLY_EXIT_CODE`].
+#[derive(Debug)]
+pub struct ReadOnlyError;
+
+impl std::fmt::Display for ReadOnlyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "read-only mode is active; refusing to modify state")
+    }
+}
+
+impl std::error::Error for ReadOnlyError {}
+
+/// Optional knobs for [`App::switch`] beyond the port and target.
+#[derive(Default)]
+pub struct SwitchOptions {
+    /// Backend port; defaults to the container's configured port.
+    pub internal_port: Option<u16>,
+    /// Tags added to the route (existing tags are kept).
+    pub tags: Vec<String>,
+    /// Canary target and traffic percentage.
+    pub canary: Option<(String, u8)>,
+    /// Cap on simultaneous connections per client address.
+    pub max_conn: Option<u32>,
+    /// Note on why the route exists, kept in the config for teammates.
+    pub description: Option<String>,
+    /// Load-balancing method for the route's upstream group.
+    pub lb_method: Option<crate::config::LbMethod>,
+    /// Scheme used to reach the backend; `None` keeps the current value.
+    pub backend_scheme: Option<crate::config::BackendScheme>,
+    /// Whether to verify an https backend's certificate; `None` keeps the
+    /// current value.
+    pub verify_backend_tls: Option<bool>,
+    /// CA bundle for verifying the https backend.
+    pub backend_ca_file: Option<std::path::PathBuf>,
+    /// CA bundle clients must present certificates signed by (mTLS).
+    pub mtls_ca_cert: Option<std::path::PathBuf>,
+    /// How strictly client certificates are demanded on an mTLS route.
+    pub mtls_verify: Option<crate::config::MtlsVerify>,
+    /// Update the container's configured port to the single port it
+    /// currently exposes before routing.
+    pub refresh_port: bool,
+}
+
+/// Ties the config store, the Docker access layer and the proxy manager
+/// together into the operations exposed to users. Cheap to clone; clones
+/// share the same config and Docker handles.
+#[derive(Clone)]
+pub struct App {
+    config: Arc<ConfigManager>,
+    docker: Arc<dyn DockerApi>,
+    manager: ProxyManager,
+    /// Read-only mode forced by flag or environment (the config field is
+    /// consulted live in [`App::is_read_only`]).
+    read_only: bool,
+    /// Skip proxy reloads after config mutations (--no-reload); the user
+    /// applies the whole batch later with one explicit 'reload'.
+    no_reload: bool,
+}
+
+impl App {
+    pub fn new(config: Arc<ConfigManager>, docker: Arc<dyn DockerApi>) -> Self {
+        let manager = ProxyManager::new(docker.clone(), config.clone());
+        Self {
+            config,
+            docker,
+            manager,
+            read_only: false,
+            no_reload: false,
+        }
+    }
+
+    /// Force read-only mode regardless of the config field.
+    pub fn force_read_only(&mut self) {
+        self.read_only = true;
+    }
+
+    /// Defer proxy reloads: mutations persist the config but leave the
+    /// running proxy alone until an explicit 'reload'.
+    pub fn set_no_reload(&mut self) {
+        self.no_reload = true;
+    }
+
+    /// Push a config change through to the running proxy, unless reloads
+    /// are deferred with --no-reload; `advise_start` controls whether a
+    /// stopped proxy earns the usual "start it" hint.
+    async fn reload_after_change(
+        &self,
+        config: &Config,
+        output: &mut Vec<String>,
+        advise_start: bool,
+    ) -> Result<()> {
+        if self.no_reload {
+            output.push("config changed; run 'reload' to apply".to_string());
+            return Ok(());
+        }
+        if self
+            .docker
+            .container_running(&config.interpolated()?.proxy_name)
+            .await?
+        {
+            output.extend(self.manager.reload(config, false).await?);
+        } else if advise_start {
+            output.push("Proxy not running; start it with 'start'".to_string());
+        }
+        Ok(())
+    }
+
+    /// Choose how hand-edited build files are handled on the next build
+    /// (see [`BuildOverwrite`]); the default refuses to overwrite them.
+    pub fn set_build_overwrite(&mut self, policy: BuildOverwrite) {
+        self.manager.set_build_overwrite(policy);
+    }
+
+    /// Whether mutations are currently refused (forced, or set in config).
+    pub fn is_read_only(&self) -> bool {
+        self.read_only || self.config.get().read_only
+    }
+
+    /// Guard called at the top of every mutating operation, so new
+    /// commands inherit the read-only behaviour for free.
+    fn ensure_writable(&self) -> Result<()> {
+        if self.is_read_only() {
+            return Err(anyhow::Error::new(ReadOnlyError));
+        }
+        Ok(())
+    }
+
+    pub fn config_manager(&self) -> &Arc<ConfigManager> {
+        &self.config
+    }
+
+    pub fn store(&self) -> &Store {
+        self.config.store()
+    }
+
+    pub fn docker(&self) -> &Arc<dyn DockerApi> {
+        &self.docker
+    }
+
+    pub fn manager(&self) -> &ProxyManager {
+        &self.manager
+    }
+
+    /// Wait until every route target container is running, polling Docker
+    /// until `timeout_secs` elapses. On timeout the remaining targets are
+    /// reported; with `strict` the wait fails instead of proceeding.
+    pub async fn wait_for_targets(&self, timeout_secs: u64, strict: bool) -> Result<Vec<String>> {
+        let config = self.config.get().clone().interpolated()?;
+        let mut targets: Vec<String> = config
+            .routes
+            .iter()
+            .filter(|r| !r.unbound)
+            .map(|r| r.target.clone())
+            .collect();
+        targets.sort();
+        targets.dedup();
+        if targets.is_empty() {
+            return Ok(vec!["No route targets to wait for".to_string()]);
+        }
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(timeout_secs);
+        loop {
+            let running: Vec<String> = self
+                .docker
+                .list_containers(false)
+                .await?
+                .into_iter()
+                .map(|c| c.name)
+                .collect();
+            let missing: Vec<String> = targets
+                .iter()
+                .filter(|t| !running.contains(t))
+                .cloned()
+                .collect();
+            if missing.is_empty() {
+                return Ok(vec![format!("All {} target(s) running", targets.len())]);
+            }
+            if std::time::Instant::now() >= deadline {
+                let list = missing.join(", ");
+                if strict {
+                    bail!("target container(s) not running after {timeout_secs}s: {list}");
+                }
+                return Ok(vec![format!(
+                    "Warning: target container(s) still not running: {list}; starting anyway"
+                )]);
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        }
+    }
+
+    /// Start (or restart) the proxy from the current config. Unless
+    /// `privileged_ports_ok` is set, a rootless daemon combined with host
+    /// ports below the unprivileged range gets a targeted warning before
+    /// any image is built, instead of an opaque bind error afterwards.
+    ///
+    /// An existing proxy container is classified before anything is torn
+    /// down: one we started from an identical config is left alone, one we
+    /// started from a different config (e.g. a teammate's instance against
+    /// a shared daemon) is only replaced with `take_over`, and a container
+    /// that merely shares the name but was not started by this tool is
+    /// never touched.
+    pub async fn start(&self, privileged_ports_ok: bool, take_over: bool) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let config = self.config.get().clone();
+        if config.routes.is_empty() {
+            bail!("no routes configured; add one with 'switch <port> <container>'");
+        }
+        let mut output = Vec::new();
+        let interpolated_for_hash = config.interpolated()?;
+        let proxy_name = interpolated_for_hash.proxy_name.clone();
+        if let Some(labels) = self.docker.container_labels(&proxy_name).await? {
+            if labels.get(MANAGED_BY_LABEL).map(String::as_str) != Some(MANAGED_BY_VALUE) {
+                bail!(
+                    "container '{proxy_name}' exists but was not started by this tool; \
+                     remove or rename it before starting the proxy"
+                );
+            }
+            let same_config = labels.get(CONFIG_HASH_LABEL).map(String::as_str)
+                == Some(config_hash(&interpolated_for_hash).as_str());
+            if same_config && self.docker.container_running(&proxy_name).await? {
+                return Ok(vec![format!(
+                    "Proxy '{proxy_name}' is already running with this configuration"
+                )]);
+            }
+            if !same_config && !take_over {
+                let since = match self.docker.container_age(&proxy_name).await? {
+                    Some(age) => format!("started {} ago ", format_duration(age)),
+                    None => String::new(),
+                };
+                bail!(
+                    "proxy '{proxy_name}' is already managed ({since}with a different \
+                     config); use --take-over to replace it"
+                );
+            }
+        }
+        if !privileged_ports_ok {
+            let rootless = self.docker.daemon_info().await?.rootless;
+            if let Some(warning) = low_port_warning(
+                &config.interpolated()?.host_ports(),
+                rootless,
+                unprivileged_port_start(),
+            ) {
+                output.push(warning);
+            }
+        }
+        // A target container publishing one of the proxy's host ports will
+        // make the bind fail; point at the culprit before building anything.
+        let interpolated = config.interpolated()?;
+        let host_ports = interpolated.host_ports();
+        let mut checked = Vec::new();
+        for route in interpolated.routes.iter().filter(|r| !r.unbound) {
+            let Some(container) = interpolated.find_container(&route.target) else {
+                continue;
+            };
+            if checked.contains(&container.name) {
+                continue;
+            }
+            checked.push(container.name.clone());
+            let published = self
+                .docker
+                .container_published_ports(&container.name)
+                .await?;
+            if let Some(warning) =
+                publish_conflict_warning(&container.name, &published, &host_ports)
+            {
+                output.push(warning);
+            }
+        }
+        // Replace any existing proxy container.
+        self.docker
+            .stop_and_remove_container(&interpolated.proxy_name)
+            .await?;
+        output.extend(self.manager.start_proxy(&config).await?);
+        self.manager.wait_for_healthy(START_HEALTH_TIMEOUT).await?;
+        let routes = interpolated.routes.iter().filter(|r| !r.unbound).count();
+        let summary = start_summary(host_ports.len(), routes, &output);
+        output.push(summary);
+        Ok(output)
+    }
+
+    /// Stop and remove the proxy container.
+    pub async fn stop(&self) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let config = self.config.get().clone();
+        self.manager.stop_proxy(&config).await
+    }
+
+    /// Remove the backend network connections recorded by
+    /// `connect_backends`; manual attachments are untouched.
+    pub async fn disconnect_backends(&self) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        self.manager.disconnect_recorded_backends().await
+    }
+
+    /// Full teardown: stop and remove the proxy container, remove its
+    /// image, and remove networks this tool created (by label) that have no
+    /// other containers attached. `keep_image` / `keep_networks` skip the
+    /// respective steps.
+    pub async fn down(&self, keep_image: bool, keep_networks: bool) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let config = self.config.get().clone().interpolated()?;
+        let mut output = self.manager.stop_proxy(&config).await?;
+
+        if !keep_image {
+            let image = format!("{}:latest", config.proxy_name);
+            // Tolerates an already-missing image.
+            self.docker.remove_image(&image).await?;
+            output.push(format!("Removed image '{image}'"));
+        }
+
+        if !keep_networks {
+            let networks = self.docker.list_networks().await?;
+            for name in config.all_networks() {
+                let Some(info) = networks.iter().find(|n| n.name == name) else {
+                    continue;
+                };
+                if !info.managed {
+                    output.push(format!(
+                        "Skipped network '{name}' (not created by proxy-manager)"
+                    ));
+                    continue;
+                }
+                if info.container_count.unwrap_or(0) > 0 {
+                    output.push(format!(
+                        "Kept network '{name}' (other containers still attached)"
+                    ));
+                    continue;
+                }
+                self.docker.remove_network(&name).await?;
+                output.push(format!("Removed network '{name}'"));
+            }
+        }
+        Ok(output)
+    }
+
+    /// Stop + start, optionally recovering from the last-good snapshot when
+    /// the start phase fails.
+    pub async fn reload(&self, auto_recover: bool) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let config = self.config.get().clone();
+        let mut output = self.manager.reload(&config, auto_recover).await?;
+        output.extend(self.port_drift_warnings().await?);
+        Ok(output)
+    }
+
+    /// Drift warnings for every bound route whose target no longer
+    /// exposes the configured internal port.
+    pub async fn port_drift_warnings(&self) -> Result<Vec<String>> {
+        let config = self.config.get().clone().interpolated()?;
+        let running = self.docker.list_containers(true).await?;
+        Ok(config
+            .routes
+            .iter()
+            .filter(|r| !r.unbound)
+            .filter_map(|route| {
+                let exposed = running
+                    .iter()
+                    .find(|c| c.name == route.target)
+                    .map(|c| c.ports.as_slice())
+                    .unwrap_or(&[]);
+                port_drift_warning(route, exposed).map(|w| format!("Warning: {w}"))
+            })
+            .collect())
+    }
+
+    /// Restore the proxy from the last successfully deployed snapshot.
+    pub async fn recover(&self) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        self.manager.recover().await
+    }
+
+    /// Register a whole batch of containers in one config save, filling
+    /// missing ports and networks from one Docker listing. Returns the
+    /// per-entry summary plus the number of failed entries.
+    pub async fn add_containers_batch(
+        &self,
+        entries: Vec<crate::batch::BatchEntry>,
+    ) -> Result<(Vec<String>, usize)> {
+        self.ensure_writable()?;
+        let (entries, mut output) = crate::batch::dedupe_entries(entries);
+        let running = self.docker.list_containers(true).await?;
+        let mut config = self.config.get().clone();
+        let (lines, failed) = crate::batch::apply_batch(&mut config, &entries, &running);
+        output.extend(lines);
+        config.validate()?;
+        self.config.replace(config)?;
+        Ok((output, failed))
+    }
+
+    /// Register (or update) a container in the config, auto-detecting the
+    /// network and exposed port from Docker when not given.
+    pub async fn add_container(
+        &self,
+        name: &str,
+        label: Option<String>,
+        port: Option<u16>,
+        network: Option<String>,
+        static_root: Option<String>,
+        description: Option<String>,
+    ) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let mut config = self.config.get().clone();
+        let mut detected_port = port;
+        let mut detected_network = network;
+
+        if detected_port.is_none() || detected_network.is_none() {
+            let running = self.docker.list_containers(true).await?;
+            if let Some(info) = running.iter().find(|c| c.name == name) {
+                if detected_port.is_none() {
+                    detected_port = info.ports.first().copied();
+                }
+                if detected_network.is_none() {
+                    detected_network = info.networks.first().cloned();
+                }
+            }
+        }
+
+        let port = match detected_port {
+            Some(p) => p,
+            None => bail!("could not detect a port for '{name}'; pass one with --port"),
+        };
+
+        let container = Container {
+            name: name.to_string(),
+            label,
+            port,
+            network: detected_network.clone(),
+            static_root,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        };
+        config.upsert_container(container);
+        self.config.replace(config.clone())?;
+
+        let mut output = vec![format!("Added container '{name}' (port {port})")];
+        if let Some(net) = detected_network {
+            output.push(format!("Network: {net}"));
+        }
+        Ok(output)
+    }
+
+    /// Remove a container from the config. Routes still targeting it make
+    /// the removal fail unless `force` (drops them too) or `retarget`
+    /// (repoints them at another configured container, then reloads once)
+    /// is given.
+    pub async fn remove_container(
+        &self,
+        identifier: &str,
+        force: bool,
+        retarget: Option<&str>,
+    ) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let mut config = self.config.get().clone();
+        let Some(container) = config.find_container(identifier) else {
+            bail!("no configured container matches '{identifier}'");
+        };
+        let name = container.name.clone();
+        let ports = config.routes_targeting(&name);
+
+        let mut output = Vec::new();
+        let mut retargeted = false;
+        if !ports.is_empty() {
+            if let Some(other) = retarget {
+                let Some(replacement) = config.find_container(other) else {
+                    bail!("no configured container matches retarget '{other}'");
+                };
+                let (new_target, new_port) = (replacement.name.clone(), replacement.port);
+                if new_target == name {
+                    bail!("cannot retarget routes to the container being removed");
+                }
+                for route in config.routes.iter_mut().filter(|r| r.target == name) {
+                    route.target = new_target.clone();
+                    route.internal_port = new_port;
+                }
+                output.push(format!(
+                    "Retargeted route(s) {} to '{new_target}'",
+                    format_port_list(&ports)
+                ));
+                retargeted = true;
+            } else if !force {
+                bail!(
+                    "routes {} point at '{name}'; re-run with --force to drop them \
+                     or --retarget <container> to repoint them",
+                    format_port_list(&ports)
+                );
+            }
+        }
+
+        config.containers.retain(|c| c.name != name);
+        let routes_before = config.routes.len();
+        config.routes.retain(|r| r.target != name);
+        let removed_routes = routes_before - config.routes.len();
+        self.config.replace(config.clone())?;
+
+        output.insert(0, format!("Removed container '{name}'"));
+        if removed_routes > 0 {
+            output.push(format!("Removed {removed_routes} route(s) targeting it"));
+        }
+        if retargeted {
+            self.reload_after_change(&config, &mut output, false)
+                .await?;
+        }
+        Ok(output)
+    }
+
+    /// Point a host port at a container and reload the proxy if it is
+    /// running.
+    pub async fn switch(
+        &self,
+        host_port: u16,
+        target: &str,
+        options: SwitchOptions,
+    ) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let mut config = self.config.get().clone();
+        let Some(container) = config.find_container(target) else {
+            bail!("no configured container matches '{target}'; add it first with 'add'");
+        };
+        let target_name = container.name.clone();
+        // The exposed ports as Docker reports them right now; recorded on
+        // the route so later drift is attributable to a specific switch.
+        let observed_ports = self
+            .docker
+            .list_containers(true)
+            .await?
+            .into_iter()
+            .find(|c| c.name == target_name)
+            .map(|c| c.ports)
+            .unwrap_or_default();
+        let mut output = Vec::new();
+        if options.refresh_port {
+            match observed_ports.as_slice() {
+                [port] => {
+                    let container = config
+                        .find_container_mut(&target_name)
+                        .expect("container was just found");
+                    if container.port != *port {
+                        output.push(format!(
+                            "Updated '{}' port {} -> {port}",
+                            target_name, container.port
+                        ));
+                        container.port = *port;
+                    }
+                }
+                [] => bail!(
+                    "--refresh-port: '{target_name}' exposes no ports; pass --container-port"
+                ),
+                many => bail!(
+                    "--refresh-port: '{target_name}' exposes several ports {many:?}; pass --container-port"
+                ),
+            }
+        }
+        let container = config
+            .find_container(&target_name)
+            .expect("container was just found");
+        let internal_port = options.internal_port.unwrap_or(container.port);
+        // Remember the route being replaced so `switch --undo` can flip
+        // back; same-target updates are not worth an undo step.
+        if let Some(previous) = config
+            .find_route(host_port)
+            .filter(|r| !r.is_static() && r.target != target_name)
+            .map(|r| (r.target.clone(), r.internal_port))
+        {
+            let path = self.store().route_history_file();
+            let mut history = load_route_history(&path);
+            let stack = history.entry(host_port).or_default();
+            stack.push(previous);
+            if stack.len() > ROUTE_HISTORY_DEPTH {
+                stack.remove(0);
+            }
+            save_route_history(&path, &history)?;
+        }
+        config.set_route(host_port, &target_name, internal_port);
+        {
+            let route = config
+                .routes
+                .iter_mut()
+                .find(|r| r.listens_on(host_port))
+                .expect("route was just set");
+            if options.max_conn.is_some() {
+                route.max_conn = options.max_conn;
+            }
+            if options.description.is_some() {
+                route.description = options.description.clone();
+            }
+            if options.lb_method.is_some() {
+                route.lb_method = options.lb_method;
+            }
+            if let Some(scheme) = options.backend_scheme {
+                route.backend_scheme = scheme;
+            }
+            if let Some(verify) = options.verify_backend_tls {
+                route.verify_backend_tls = verify;
+            }
+            if options.backend_ca_file.is_some() {
+                route.backend_ca_file = options.backend_ca_file.clone();
+            }
+            if options.mtls_ca_cert.is_some() {
+                route.mtls_ca_cert = options.mtls_ca_cert.clone();
+            }
+            if options.mtls_verify.is_some() {
+                route.mtls_verify = options.mtls_verify;
+            }
+            route.observed_ports = observed_ports.clone();
+        }
+        if let Some((canary_ident, percent)) = &options.canary {
+            let Some(canary_container) = config.find_container(canary_ident) else {
+                bail!("no configured container matches canary '{canary_ident}'");
+            };
+            let canary_value = crate::config::Canary {
+                target: canary_container.name.clone(),
+                internal_port: canary_container.port,
+                percent: *percent,
+            };
+            config
+                .routes
+                .iter_mut()
+                .find(|r| r.listens_on(host_port))
+                .expect("route was just set")
+                .canary = Some(canary_value);
+        }
+        config.validate()?;
+        if !options.tags.is_empty() {
+            let route = config
+                .routes
+                .iter_mut()
+                .find(|r| r.listens_on(host_port))
+                .expect("route was just set");
+            for tag in &options.tags {
+                if !route.has_tag(tag) {
+                    route.tags.push(tag.clone());
+                }
+            }
+        }
+        self.config.replace(config.clone())?;
+
+        output.push(format!(
+            "Route {host_port} -> {target_name}:{internal_port}"
+        ));
+        if let Some(drift) = port_drift_warning(
+            config
+                .routes
+                .iter()
+                .find(|r| r.listens_on(host_port))
+                .expect("route was just set"),
+            &observed_ports,
+        ) {
+            output.push(format!("Warning: {drift}"));
+        }
+        if let Some(collision) = config.containers.iter().find(|c| c.port == host_port) {
+            output.push(format!(
+                "Note: host port {host_port} is also container '{}'s internal port; \
+                 'switch' takes the host port",
+                collision.name
+            ));
+        }
+        if let Some((canary_ident, percent)) = &options.canary {
+            output.push(format!("Canary: {percent}% -> {canary_ident}"));
+        }
+        self.reload_after_change(&config, &mut output, true).await?;
+        Ok(output)
+    }
+
+    /// Restore the previous target of `host_port` from the switch
+    /// history and reload, for quick blue/green flips.
+    pub async fn undo_switch(&self, host_port: u16) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let path = self.store().route_history_file();
+        let mut history = load_route_history(&path);
+        let Some((target, internal_port)) =
+            history.get_mut(&host_port).and_then(|stack| stack.pop())
+        else {
+            bail!("no switch history for port {host_port}; nothing to undo");
+        };
+        save_route_history(&path, &history)?;
+        let mut config = self.config.get().clone();
+        if config.find_container(&target).is_none() {
+            bail!("previous target '{target}' is no longer configured; undo aborted");
+        }
+        config.set_route(host_port, &target, internal_port);
+        config.validate()?;
+        self.config.replace(config.clone())?;
+        let mut output = vec![format!(
+            "Restored route {host_port} -> {target}:{internal_port}"
+        )];
+        self.reload_after_change(&config, &mut output, true).await?;
+        Ok(output)
+    }
+
+    /// Point a host port at a local directory of static files instead of a
+    /// container, and reload the proxy if it is running. The directory is
+    /// checked and copied into the build context at build time.
+    pub async fn switch_static(
+        &self,
+        host_port: u16,
+        dir: &std::path::Path,
+    ) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let mut config = self.config.get().clone();
+        config.set_static_route(host_port, dir.to_path_buf());
+        config.validate()?;
+        self.config.replace(config.clone())?;
+
+        let mut output = vec![format!("Route {host_port} -> static {}", dir.display())];
+        self.reload_after_change(&config, &mut output, true).await?;
+        Ok(output)
+    }
+
+    /// Flip the route on `host_port` in or out of maintenance mode and
+    /// reload the proxy if it is running.
+    pub async fn set_maintenance(&self, host_port: u16, on: bool) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let mut config = self.config.get().clone();
+        let Some(route) = config.routes.iter_mut().find(|r| r.listens_on(host_port)) else {
+            bail!("no route on port {host_port}");
+        };
+        if route.maintenance == on {
+            let state = if on { "already in" } else { "not in" };
+            return Ok(vec![format!("Route {host_port} is {state} maintenance")]);
+        }
+        route.maintenance = on;
+        self.config.replace(config.clone())?;
+
+        let state = if on { "entering" } else { "leaving" };
+        let mut output = vec![format!("Route {host_port} {state} maintenance")];
+        self.reload_after_change(&config, &mut output, false)
+            .await?;
+        Ok(output)
+    }
+
+    /// Protect the route on `host_port` with basic auth. Only the apr1
+    /// hash of `password` is stored; the proxy is reloaded when running so
+    /// the htpasswd file reaches the container.
+    pub async fn set_auth(
+        &self,
+        host_port: u16,
+        username: &str,
+        password: &str,
+    ) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        if username.is_empty() || username.contains(':') {
+            bail!("username must be non-empty and must not contain ':'");
+        }
+        if password.is_empty() {
+            bail!("password must not be empty");
+        }
+        let mut config = self.config.get().clone();
+        let Some(route) = config.routes.iter_mut().find(|r| r.listens_on(host_port)) else {
+            bail!("no route on port {host_port}");
+        };
+        route.basic_auth = Some(crate::config::BasicAuth {
+            username: username.to_string(),
+            password_hash: crate::auth::hash_password(password),
+        });
+        self.config.replace(config.clone())?;
+
+        let mut output = vec![format!(
+            "Route {host_port} now requires basic auth (user '{username}')"
+        )];
+        self.reload_after_change(&config, &mut output, false)
+            .await?;
+        Ok(output)
+    }
+
+    /// Remove basic-auth protection from the route on `host_port`.
+    pub async fn remove_auth(&self, host_port: u16) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let mut config = self.config.get().clone();
+        let Some(route) = config.routes.iter_mut().find(|r| r.listens_on(host_port)) else {
+            bail!("no route on port {host_port}");
+        };
+        if route.basic_auth.take().is_none() {
+            return Ok(vec![format!("Route {host_port} has no basic auth")]);
+        }
+        self.config.replace(config.clone())?;
+
+        let mut output = vec![format!("Removed basic auth from route {host_port}")];
+        self.reload_after_change(&config, &mut output, false)
+            .await?;
+        Ok(output)
+    }
+
+    /// Remove the route on `host_port`, or with `keep` leave it in the
+    /// config but unbind it (the port is no longer published). Stops the
+    /// proxy when no bound route remains, otherwise reloads once with the
+    /// remaining set.
+    pub async fn stop_port(&self, host_port: u16, keep: bool) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let mut config = self.config.get().clone();
+        let mut output = Vec::new();
+        if keep {
+            let Some(route) = config.routes.iter_mut().find(|r| r.listens_on(host_port)) else {
+                bail!("no route on port {host_port}");
+            };
+            route.unbound = true;
+            output.push(format!(
+                "Unbound route on port {host_port} (kept in config; re-bind with 'resume {host_port}')"
+            ));
+        } else {
+            if !config.remove_route(host_port) {
+                bail!("no route on port {host_port}");
+            }
+            output.push(format!("Removed route on port {host_port}"));
+        }
+        self.config.replace(config.clone())?;
+
+        if self.no_reload {
+            output.push("config changed; run 'reload' to apply".to_string());
+        } else if self
+            .docker
+            .container_running(&config.interpolated()?.proxy_name)
+            .await?
+        {
+            if config.host_ports().is_empty() {
+                output.extend(self.manager.stop_proxy(&config).await?);
+            } else {
+                output.extend(self.manager.reload(&config, false).await?);
+            }
+        }
+        Ok(output)
+    }
+
+    /// Re-bind a route previously unbound with `stop <port> --keep`.
+    pub async fn resume_port(&self, host_port: u16) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let mut config = self.config.get().clone();
+        let Some(route) = config.routes.iter_mut().find(|r| r.listens_on(host_port)) else {
+            bail!("no route on port {host_port}");
+        };
+        if !route.unbound {
+            bail!("route on port {host_port} is already bound");
+        }
+        route.unbound = false;
+        self.config.replace(config.clone())?;
+
+        let mut output = vec![format!("Re-bound route on port {host_port}")];
+        self.reload_after_change(&config, &mut output, true).await?;
+        Ok(output)
+    }
+
+    /// Add or remove a tag on the route bound to `host_port`.
+    pub fn tag_route(&self, host_port: u16, tag: &str, add: bool) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let mut config = self.config.get().clone();
+        let Some(route) = config.routes.iter_mut().find(|r| r.listens_on(host_port)) else {
+            bail!("no route on port {host_port}");
+        };
+        let output = if add {
+            if route.has_tag(tag) {
+                vec![format!("Route {host_port} already tagged '{tag}'")]
+            } else {
+                route.tags.push(tag.to_string());
+                vec![format!("Tagged route {host_port} with '{tag}'")]
+            }
+        } else {
+            if !route.has_tag(tag) {
+                bail!("route {host_port} does not carry tag '{tag}'");
+            }
+            route.tags.retain(|t| t != tag);
+            vec![format!("Removed tag '{tag}' from route {host_port}")]
+        };
+        self.config.replace(config.clone())?;
+        Ok(output)
+    }
+
+    /// Remove every route carrying `tag` with a single reload at the end.
+    pub async fn stop_tag(&self, tag: &str) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let mut config = self.config.get().clone();
+        let ports: Vec<u16> = config
+            .routes_with_tag(tag)
+            .iter()
+            .flat_map(|r| r.host_ports.clone())
+            .collect();
+        if ports.is_empty() {
+            bail!("no routes carry tag '{tag}'");
+        }
+        config.routes.retain(|r| !r.has_tag(tag));
+        self.config.replace(config.clone())?;
+
+        let mut output = vec![format!(
+            "Removed {} route(s) tagged '{tag}': {}",
+            ports.len(),
+            ports
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )];
+        if self.no_reload {
+            output.push("config changed; run 'reload' to apply".to_string());
+        } else if self
+            .docker
+            .container_running(&config.interpolated()?.proxy_name)
+            .await?
+        {
+            if config.routes.is_empty() {
+                output.extend(self.manager.stop_proxy(&config).await?);
+            } else {
+                output.extend(self.manager.reload(&config, false).await?);
+            }
+        }
+        Ok(output)
+    }
+
+    /// Probe every configured container from inside the proxy container
+    /// via `ping -c 1`, reporting reachability and latency. The proxy must
+    /// be running; it is the vantage point that matters, since that is
+    /// where nginx will resolve and connect.
+    pub async fn network_connectivity_check(&self) -> Result<Vec<NetworkCheckResult>> {
+        let config = self.config.get().clone().interpolated()?;
+        if !self.docker.container_running(&config.proxy_name).await? {
+            bail!(
+                "proxy '{}' is not running; start it first so connectivity can be \
+                 checked from its network",
+                config.proxy_name
+            );
+        }
+        let mut results = Vec::new();
+        for container in &config.containers {
+            let cmd = vec![
+                "ping".to_string(),
+                "-c".to_string(),
+                "1".to_string(),
+                "-W".to_string(),
+                "1".to_string(),
+                container.name.clone(),
+            ];
+            let (exit_code, output) = self
+                .docker
+                .exec_in_container(&config.proxy_name, &cmd)
+                .await?;
+            let reachable = exit_code == 0;
+            results.push(NetworkCheckResult {
+                container_name: container.name.clone(),
+                address: format!("{}:{}", container.name, container.port),
+                reachable,
+                latency_ms: reachable.then(|| parse_ping_latency(&output)).flatten(),
+            });
+        }
+        Ok(results)
+    }
+
+    /// Restart a configured container (by name or label) in place, giving
+    /// it `timeout_secs` to stop gracefully.
+    pub async fn restart_container(
+        &self,
+        identifier: &str,
+        timeout_secs: u32,
+    ) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let config = self.config.get().clone().interpolated()?;
+        let Some(container) = config.find_container(identifier) else {
+            bail!("no configured container matches '{identifier}'");
+        };
+        self.docker
+            .restart_container(&container.name, timeout_secs)
+            .await?;
+        Ok(vec![format!("Restarted container '{}'", container.name)])
+    }
+
+    /// Remove dangling images left behind by repeated proxy builds,
+    /// skipping the image the proxy container currently runs on.
+    pub async fn prune_images(&self) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let config = self.config.get().clone().interpolated()?;
+        let in_use = self.docker.container_image_id(&config.proxy_name).await?;
+        let dangling = self.docker.list_dangling_images().await?;
+
+        let mut output = Vec::new();
+        let mut reclaimed: i64 = 0;
+        for image in dangling {
+            if in_use.as_deref() == Some(image.id.as_str()) {
+                output.push(format!(
+                    "Skipped {} (in use by '{}')",
+                    short_id(&image.id),
+                    config.proxy_name
+                ));
+                continue;
+            }
+            self.docker.remove_image(&image.id).await?;
+            reclaimed += image.size;
+            output.push(format!("Removed {}", short_id(&image.id)));
+        }
+        if reclaimed > 0 {
+            output.push(format!(
+                "Reclaimed {:.1} MB",
+                reclaimed as f64 / (1024.0 * 1024.0)
+            ));
+        } else if output.is_empty() {
+            output.push("No dangling images to remove".to_string());
+        }
+        Ok(output)
+    }
+
+    /// List managed proxy containers left behind by old proxy names —
+    /// anything carrying this tool's managed-by label whose name is not
+    /// the current `proxy_name` — with their published ports and age.
+    /// `remove` tears each one down through the usual stop/remove path;
+    /// `adopt` instead points the config's `proxy_name` at the chosen
+    /// container so it becomes the current proxy.
+    pub async fn orphans(&self, remove: bool, adopt: Option<&str>) -> Result<Vec<String>> {
+        if remove || adopt.is_some() {
+            self.ensure_writable()?;
+        }
+        let config = self.config.get().clone().interpolated()?;
+        let mut labelled = Vec::new();
+        for info in self.docker.list_containers(true).await? {
+            if let Some(labels) = self.docker.container_labels(&info.name).await? {
+                labelled.push((info.name, labels));
+            }
+        }
+        let orphans = orphaned_proxies(&labelled, &config.proxy_name);
+        if orphans.is_empty() {
+            return Ok(vec!["No orphaned proxy containers found".to_string()]);
+        }
+
+        if let Some(name) = adopt {
+            if !orphans.iter().any(|o| o == name) {
+                bail!(
+                    "'{name}' is not an orphaned proxy container; candidates: {}",
+                    orphans.join(", ")
+                );
+            }
+            let mut raw = self.config.get().clone();
+            raw.proxy_name = name.to_string();
+            raw.validate()?;
+            self.config.replace(raw)?;
+            return Ok(vec![format!(
+                "Adopted '{name}': proxy_name now points at it"
+            )]);
+        }
+
+        let host_ports = config.host_ports();
+        let mut output = Vec::new();
+        for name in &orphans {
+            if remove {
+                self.docker.stop_and_remove_container(name).await?;
+                output.push(format!("Removed orphaned proxy container '{name}'"));
+                continue;
+            }
+            let published = self.docker.container_published_ports(name).await?;
+            let age = match self.docker.container_age(name).await? {
+                Some(age) => format!(", up {}", format_duration(age)),
+                None => String::new(),
+            };
+            let ports = if published.is_empty() {
+                "no published ports".to_string()
+            } else {
+                format!("port(s) {}", format_port_list(&published))
+            };
+            let mut line = format!("{name} ({ports}{age})");
+            let conflicts: Vec<u16> = published
+                .iter()
+                .copied()
+                .filter(|p| host_ports.contains(p))
+                .collect();
+            if !conflicts.is_empty() {
+                line.push_str(&format!(
+                    " -- holds configured port(s) {}",
+                    format_port_list(&conflicts)
+                ));
+            }
+            output.push(line);
+        }
+        Ok(output)
+    }
+
+    /// Wait until nginx reports no active connections beyond the probe
+    /// itself, or `max_wait` elapses; true means idle was reached.
+    /// Progress is printed every few seconds so long drains stay visible.
+    pub async fn wait_until_idle(&self, max_wait: Duration) -> Result<bool> {
+        let config = self.config.get().clone().interpolated()?;
+        let deadline = std::time::Instant::now() + max_wait;
+        let mut last_report = std::time::Instant::now();
+        loop {
+            let active = self.probe_active_connections(&config).await?;
+            // The probing request itself holds one connection.
+            let draining = active.saturating_sub(1);
+            if draining == 0 {
+                return Ok(true);
+            }
+            if std::time::Instant::now() >= deadline {
+                return Ok(false);
+            }
+            if last_report.elapsed() >= IDLE_REPORT_INTERVAL {
+                println!("Waiting for idle: {draining} active connection(s)");
+                last_report = std::time::Instant::now();
+            }
+            tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Active connection count from the internal stub_status server,
+    /// fetched through `docker exec` so the port never has to be
+    /// published.
+    async fn probe_active_connections(&self, config: &crate::config::Config) -> Result<u32> {
+        let port = config
+            .management_port
+            .unwrap_or(crate::nginx::INTERNAL_STATUS_PORT);
+        let url = format!("http://127.0.0.1:{port}/nginx_status");
+        let cmd = ["wget", "-qO-", &url].map(String::from);
+        let (code, out) = self
+            .docker
+            .exec_in_container(&config.proxy_name, &cmd)
+            .await?;
+        if code != 0 {
+            bail!(
+                "status probe inside '{}' failed (exit {code})",
+                config.proxy_name
+            );
+        }
+        parse_active_connections(&out)
+            .ok_or_else(|| anyhow::anyhow!("unexpected stub_status output: {out:?}"))
+    }
+
+    /// Clean up config inconsistencies via [`crate::config::Config::normalize`];
+    /// `dry_run` reports what would change without saving.
+    pub fn normalize(&self, dry_run: bool) -> Result<Vec<String>> {
+        if !dry_run {
+            self.ensure_writable()?;
+        }
+        let mut config = self.config.get().clone();
+        let mut lines = config.normalize();
+        if lines.is_empty() {
+            return Ok(vec!["Config is already normalized".to_string()]);
+        }
+        if dry_run {
+            lines.push("Dry run: nothing saved".to_string());
+        } else {
+            self.config.replace(config)?;
+            lines.push("Saved normalized config".to_string());
+        }
+        Ok(lines)
+    }
+
+    /// Assigned proxy addresses on networks with a static IP pin, so
+    /// drift from `proxy_static_ips` is visible in `status`.
+    pub async fn proxy_static_ip_report(&self) -> Result<Vec<String>> {
+        let config = self.config.get().clone().interpolated()?;
+        let mut pins: Vec<_> = config.proxy_static_ips.iter().collect();
+        pins.sort();
+        let mut lines = Vec::new();
+        for (network, pinned) in pins {
+            let assigned = self
+                .docker
+                .inspect_network(network)
+                .await?
+                .into_iter()
+                .find(|c| c.name == config.proxy_name)
+                .map(|c| c.ipv4_address);
+            lines.push(match assigned {
+                Some(ip) if ip.split('/').next() == Some(pinned.to_string().as_str()) => {
+                    format!("Proxy IP on '{network}': {ip} (pinned)")
+                }
+                Some(ip) => format!("Proxy IP on '{network}': {ip} (pinned {pinned}, drifted)"),
+                None => format!("Proxy IP on '{network}': not attached (pinned {pinned})"),
+            });
+        }
+        Ok(lines)
+    }
+
+    /// Docker-ps-style table of the proxy and every route target,
+    /// joining config routes with live container state.
+    pub async fn ps(&self) -> Result<Vec<String>> {
+        let config = self.config.get().clone().interpolated()?;
+        let live = self.docker.list_containers(true).await?;
+        let status_of = |name: &str| {
+            live.iter()
+                .find(|c| c.name == name)
+                .map(|c| c.status.clone())
+                .unwrap_or_else(|| "not found".to_string())
+        };
+        let mut rows = vec![[
+            config.proxy_name.clone(),
+            status_of(&config.proxy_name),
+            join_ports(&config.host_ports()),
+            "-".to_string(),
+            config.network.clone(),
+        ]];
+        for route in &config.routes {
+            if let Some(dir) = &route.static_dir {
+                rows.push([
+                    format!("static:{}", dir.display()),
+                    "-".to_string(),
+                    join_ports(&route.host_ports),
+                    "-".to_string(),
+                    "-".to_string(),
+                ]);
+                continue;
+            }
+            let network = config
+                .find_container(&route.target)
+                .and_then(|c| c.network.clone())
+                .unwrap_or_else(|| config.network.clone());
+            rows.push([
+                route.target.clone(),
+                status_of(&route.target),
+                join_ports(&route.host_ports),
+                route.internal_port.to_string(),
+                network,
+            ]);
+        }
+        Ok(render_table(
+            ["NAME", "STATUS", "HOST_PORT", "INTERNAL_PORT", "NETWORK"],
+            &rows,
+        ))
+    }
+
+    /// Read one top-level config field by its JSON name (`config get`).
+    pub fn config_get(&self, key: &str) -> Result<String> {
+        self.config.get().get_value(key)
+    }
+
+    /// Set one top-level config field from its string form (`config
+    /// set`), validating the result before saving.
+    pub fn config_set(&self, key: &str, value: &str) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let mut config = self.config.get().clone();
+        config.set_value(key, value)?;
+        config.validate()?;
+        self.config.replace(config)?;
+        Ok(vec![if value.is_empty() {
+            format!("Unset {key}")
+        } else {
+            format!("Set {key} to '{value}'")
+        }])
+    }
+
+    /// Replace the whole config from a JSON stream (`config --stdin`);
+    /// parse and validation errors leave the existing file untouched.
+    pub fn config_import(&self, reader: impl std::io::Read) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let config = Config::from_reader(reader)?;
+        self.config.replace(config)?;
+        Ok(vec!["Config replaced".to_string()])
+    }
+
+    /// Pause a backend, probe its routes for the 503 fallback page and
+    /// resume it; see [`ProxyManager::simulate_failover`].
+    pub async fn simulate_failover(&self, identifier: &str) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let report = self.manager.simulate_failover(identifier).await?;
+        let mut lines = report.messages;
+        lines.push(if report.all_returned_503 {
+            format!(
+                "All {} route(s) fell back to the 503 page",
+                report.routes_tested
+            )
+        } else {
+            "Warning: not every route returned the 503 fallback; check the proxy config".to_string()
+        });
+        Ok(lines)
+    }
+
+    /// Rewrite the config file in canonical form (sorted, two-space
+    /// indented, trailing newline) without other changes; `check` only
+    /// reports, exiting non-zero when the file is not canonical.
+    pub fn config_fmt(&self, check: bool) -> Result<Vec<String>> {
+        let path = self.store().config_file();
+        let config = self.config.get().clone();
+        let canonical = config.to_canonical_json()?;
+        let current = std::fs::read_to_string(&path).unwrap_or_default();
+        if current == canonical {
+            return Ok(vec![format!("{} is already canonical", path.display())]);
+        }
+        if check {
+            bail!(
+                "{} is not in canonical form; run 'config-fmt' to rewrite it",
+                path.display()
+            );
+        }
+        self.ensure_writable()?;
+        self.config.store().save(&config)?;
+        Ok(vec![format!(
+            "Rewrote {} in canonical form",
+            path.display()
+        )])
+    }
+
+    /// Structured snapshot behind `status`, shared by the text, markdown
+    /// and HTML renderers.
+    pub async fn status_info(&self) -> Result<StatusInfo> {
+        let config = self.config.get().clone().interpolated()?;
+        let proxy_status = self
+            .docker
+            .get_container_status(&config.proxy_name)
+            .await?
+            .unwrap_or_else(|| "not running".to_string());
+        let live = self.docker.list_containers(true).await?;
+        let proxy = live.iter().find(|c| c.name == config.proxy_name);
+        let mut routes = Vec::new();
+        for route in &config.routes {
+            if route.is_static() {
+                routes.push(RouteStatus {
+                    ports: route.ports_display(),
+                    target: "[static]".to_string(),
+                    internal_port: None,
+                    backend_status: "-".to_string(),
+                    unbound: route.unbound,
+                    description: route.description.clone(),
+                });
+                continue;
+            }
+            let backend_status = self
+                .docker
+                .get_container_status(&route.target)
+                .await?
+                .unwrap_or_else(|| "not found".to_string());
+            routes.push(RouteStatus {
+                ports: route.ports_display(),
+                target: route.target.clone(),
+                internal_port: Some(route.internal_port),
+                backend_status,
+                unbound: route.unbound,
+                description: route.description.clone(),
+            });
+        }
+        Ok(StatusInfo {
+            proxy_name: config.proxy_name.clone(),
+            proxy_status,
+            proxy_image: proxy.map(|c| c.image.clone()),
+            proxy_networks: proxy.map(|c| c.networks.clone()).unwrap_or_default(),
+            summary: config.count_stats().summary(),
+            routes,
+        })
+    }
+
+    /// Prometheus exposition-format snapshot of the config and proxy
+    /// state, served by `serve` for scrapes.
+    pub async fn export_metrics_prometheus(&self) -> Result<String> {
+        let config = self.config.get().clone().interpolated()?;
+        let proxy_running = matches!(
+            self.docker.get_container_status(&config.proxy_name).await?,
+            Some(status) if status == "running"
+        );
+        let mut out = String::new();
+        out.push_str(
+            "# HELP proxy_manager_containers_total Containers registered in the config.\n",
+        );
+        out.push_str("# TYPE proxy_manager_containers_total gauge\n");
+        out.push_str(&format!(
+            "proxy_manager_containers_total {}\n",
+            config.containers.len()
+        ));
+        out.push_str("# HELP proxy_manager_routes_total Routes in the config.\n");
+        out.push_str("# TYPE proxy_manager_routes_total gauge\n");
+        out.push_str(&format!(
+            "proxy_manager_routes_total {}\n",
+            config.routes.len()
+        ));
+        out.push_str(
+            "# HELP proxy_manager_proxy_running Whether the proxy container is running.\n",
+        );
+        out.push_str("# TYPE proxy_manager_proxy_running gauge\n");
+        out.push_str(&format!(
+            "proxy_manager_proxy_running {}\n",
+            proxy_running as u8
+        ));
+        out.push_str(
+            "# HELP proxy_manager_container_routed Whether at least one route targets the container.\n",
+        );
+        out.push_str("# TYPE proxy_manager_container_routed gauge\n");
+        for container in &config.containers {
+            let routed = config
+                .routes
+                .iter()
+                .any(|r| !r.is_static() && container.matches(&r.target));
+            out.push_str(&format!(
+                "proxy_manager_container_routed{{container=\"{}\"}} {}\n",
+                container.name, routed as u8
+            ));
+        }
+        Ok(out)
+    }
+
+    /// Names of running containers not yet present in the config.
+    pub async fn detect(&self) -> Result<Vec<String>> {
+        Ok(self
+            .detect_full()
+            .await?
+            .into_iter()
+            .map(|c| c.name)
+            .collect())
+    }
+
+    /// Like [`App::detect`], but with the full container summaries for
+    /// metadata output.
+    pub async fn detect_full(&self) -> Result<Vec<crate::docker::ContainerInfo>> {
+        let config = self.config.get().clone().interpolated()?;
+        let running = self.docker.list_containers(false).await?;
+        Ok(running
+            .into_iter()
+            .filter(|c| c.name != config.proxy_name)
+            .filter(|c| config.find_container(&c.name).is_none())
+            .collect())
+    }
+
+    /// Bulk-register running containers that are not yet in the config,
+    /// optionally restricted to names starting with `prefix`. Ports and
+    /// networks are auto-detected the same way `add` does it; `dry_run`
+    /// only reports what would be added.
+    pub async fn auto_discover_and_add(
+        &self,
+        prefix: Option<&str>,
+        dry_run: bool,
+    ) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let config = self.config.get().clone().interpolated()?;
+        let running = self.docker.list_containers(true).await?;
+
+        let mut output = Vec::new();
+        for info in running {
+            if info.name == config.proxy_name {
+                continue;
+            }
+            if let Some(prefix) = prefix {
+                if !info.name.starts_with(prefix) {
+                    continue;
+                }
+            }
+            if config.find_container(&info.name).is_some() {
+                output.push(format!("Skipped {} (already configured)", info.name));
+                continue;
+            }
+            if dry_run {
+                output.push(format!("Would add {}", info.name));
+                continue;
+            }
+            match self
+                .add_container(&info.name, None, None, None, None, None)
+                .await
+            {
+                Ok(_) => output.push(format!("Added {}", info.name)),
+                Err(err) => output.push(format!("Skipped {} ({err})", info.name)),
+            }
+        }
+        if output.is_empty() {
+            output.push("Nothing to add".to_string());
+        }
+        Ok(output)
+    }
+}
+
+/// Warning when `target` publishes host ports the proxy also wants to
+/// bind, or `None` when the sets are disjoint.
+fn publish_conflict_warning(target: &str, published: &[u16], host_ports: &[u16]) -> Option<String> {
+    let conflicts: Vec<u16> = published
+        .iter()
+        .copied()
+        .filter(|p| host_ports.contains(p))
+        .collect();
+    if conflicts.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "Warning: container '{target}' already publishes host port(s) {} that the proxy \
+         binds too; the bind will fail. Run the container without publishing (drop its \
+         -p flag) and let the proxy route to it instead.",
+        format_port_list(&conflicts)
+    ))
+}
+
+/// Names of containers carrying this tool's managed-by label that are not
+/// the current proxy — leftovers from an earlier `proxy_name`. Sorted so
+/// the listing (and any removal order) is stable.
+fn orphaned_proxies(
+    labelled: &[(String, std::collections::HashMap<String, String>)],
+    current_proxy: &str,
+) -> Vec<String> {
+    let mut orphans: Vec<String> = labelled
+        .iter()
+        .filter(|(name, labels)| {
+            name != current_proxy
+                && labels.get(MANAGED_BY_LABEL).map(String::as_str) == Some(MANAGED_BY_VALUE)
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+    orphans.sort();
+    orphans
+}
+
+/// Outcome of one reachability probe from the proxy to a container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkCheckResult {
+    pub container_name: String,
+    /// Address probed, as resolved inside the proxy's network.
+    pub address: String,
+    pub reachable: bool,
+    /// Round-trip time when the probe succeeded and could be parsed.
+    pub latency_ms: Option<u64>,
+}
+
+/// One-line outcome appended to `start` output so the result is visible
+/// at a glance even when the lines above scroll or get piped: ports
+/// bound, routes served and how many of the preceding lines were
+/// warnings.
+fn start_summary(ports: usize, routes: usize, lines: &[String]) -> String {
+    let warnings = lines
+        .iter()
+        .filter(|line| line.starts_with("Warning"))
+        .count();
+    format!("Proxy running on {ports} port(s); {routes} route(s); {warnings} warning(s)")
+}
+
+/// Clamp a requested log tail to the configured `log_tail_limit`; the
+/// notice explains the reduction when one happened.
+pub fn clamp_log_tail(requested: u32, limit: u32) -> (u32, Option<String>) {
+    if requested <= limit {
+        return (requested, None);
+    }
+    (
+        limit,
+        Some(format!(
+            "Requested --tail {requested} exceeds log_tail_limit ({limit}); \
+             showing the last {limit} lines"
+        )),
+    )
+}
+
+/// Round-trip time in whole milliseconds from `ping -c 1` output
+/// ("time=0.123 ms"), rounded up so a sub-millisecond reply is not
+/// reported as zero.
+fn parse_ping_latency(output: &str) -> Option<u64> {
+    let rest = output.split("time=").nth(1)?;
+    let value: f64 = rest.split_whitespace().next()?.parse().ok()?;
+    Some(value.ceil() as u64)
+}
+
+/// Rough human rendering of a duration ("45s", "12m", "3h", "2d").
+fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86_399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+/// Why binding the given host ports is expected to fail on this daemon, or
+/// `None` when it should work. A rootful daemon binds anything; a rootless
+/// one is subject to the host's `net.ipv4.ip_unprivileged_port_start`.
+fn low_port_warning(ports: &[u16], rootless: bool, unprivileged_start: u16) -> Option<String> {
+    if !rootless {
+        return None;
+    }
+    let low: Vec<u16> = ports
+        .iter()
+        .copied()
+        .filter(|p| *p < unprivileged_start)
+        .collect();
+    if low.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "Warning: host port(s) {} are below net.ipv4.ip_unprivileged_port_start \
+         ({unprivileged_start}) and the Docker daemon is rootless; the bind will likely fail. \
+         Raise the sysctl on the daemon host or pass --privileged-ports-ok to silence this.",
+        format_port_list(&low)
+    ))
+}
+
+/// The host's first unprivileged port, read from /proc; 1024 when the sysctl
+/// is unreadable (non-Linux or remote daemon).
+fn unprivileged_port_start() -> u16 {
+    std::fs::read_to_string("/proc/sys/net/ipv4/ip_unprivileged_port_start")
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(1024)
+}
+
+/// Join host ports for display ("8000, 8443").
+fn format_port_list(ports: &[u16]) -> String {
+    ports
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Shorten a Docker image id (with or without the `sha256:` prefix) for
+/// display.
+fn short_id(id: &str) -> &str {
+    let id = id.strip_prefix("sha256:").unwrap_or(id);
+    &id[..id.len().min(12)]
+}
+
+/// Warning when a route's internal port is no longer among the ports its
+/// target exposes (typically after an image upgrade changed the listen
+/// port). No port metadata means no verdict, so no warning.
+pub(crate) fn port_drift_warning(route: &crate::config::Route, exposed: &[u16]) -> Option<String> {
+    if route.is_static() || exposed.is_empty() || exposed.contains(&route.internal_port) {
+        return None;
+    }
+    let exposed = exposed
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(format!(
+        "route {} -> {}:{} but {} now exposes [{exposed}]",
+        route.primary_port(),
+        route.target,
+        route.internal_port,
+        route.target,
+    ))
+}
+
+/// Pull the count out of nginx stub_status output
+/// ("Active connections: N ...").
+pub(crate) fn parse_active_connections(body: &str) -> Option<u32> {
+    body.split("Active connections:")
+        .nth(1)?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Snapshot of proxy and route state collected by [`App::status_info`].
+#[derive(Debug, Clone)]
+pub struct StatusInfo {
+    pub proxy_name: String,
+    /// Docker status, or "not running" when the container is absent.
+    pub proxy_status: String,
+    pub proxy_image: Option<String>,
+    pub proxy_networks: Vec<String>,
+    /// The [`crate::config::ConfigStats`] one-liner.
+    pub summary: String,
+    pub routes: Vec<RouteStatus>,
+}
+
+/// Per-route line in a [`StatusInfo`].
+#[derive(Debug, Clone)]
+pub struct RouteStatus {
+    /// Host ports, comma-joined.
+    pub ports: String,
+    /// Target container, or "[static]" for file-serving routes.
+    pub target: String,
+    /// None for static routes.
+    pub internal_port: Option<u16>,
+    pub backend_status: String,
+    pub unbound: bool,
+    pub description: Option<String>,
+}
+
+/// Escape user-controlled strings for the HTML report.
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Escape a markdown table cell: pipes would otherwise split the column.
+fn escape_md_cell(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+/// Render a [`StatusInfo`] as a markdown report with one table row per
+/// route, for `status --format markdown`.
+pub fn render_status_markdown(status: &StatusInfo) -> String {
+    let mut out = format!("# Proxy status: {}\n\n", escape_md_cell(&status.proxy_name));
+    out.push_str(&format!("- Status: {}\n", status.proxy_status));
+    out.push_str(&format!(
+        "- Image: {}\n",
+        status.proxy_image.as_deref().unwrap_or("-")
+    ));
+    out.push_str(&format!(
+        "- Networks: {}\n",
+        if status.proxy_networks.is_empty() {
+            "-".to_string()
+        } else {
+            status.proxy_networks.join(", ")
+        }
+    ));
+    out.push_str(&format!("- {}\n\n", status.summary));
+    out.push_str("| Host port | Target | Internal port | Backend | Note |\n");
+    out.push_str("| --- | --- | --- | --- | --- |\n");
+    for route in &status.routes {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} |\n",
+            escape_md_cell(&route.ports),
+            escape_md_cell(&route.target),
+            route
+                .internal_port
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            escape_md_cell(&route.backend_status),
+            escape_md_cell(&route_note(route)),
+        ));
+    }
+    out
+}
+
+/// Render a [`StatusInfo`] as a standalone HTML page, for
+/// `status --format html`.
+pub fn render_status_html(status: &StatusInfo) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>proxy-manager status</title>\n<style>\n\
+         body { font-family: sans-serif; margin: 2em; }\n\
+         table { border-collapse: collapse; }\n\
+         th, td { border: 1px solid #ccc; padding: 4px 10px; text-align: left; }\n\
+         th { background: #f0f0f0; }\n</style>\n</head>\n<body>\n",
+    );
+    out.push_str(&format!(
+        "<h1>Proxy status: {}</h1>\n",
+        escape_html(&status.proxy_name)
+    ));
+    out.push_str(&format!(
+        "<p>Status: {}<br>Image: {}<br>Networks: {}<br>{}</p>\n",
+        escape_html(&status.proxy_status),
+        escape_html(status.proxy_image.as_deref().unwrap_or("-")),
+        escape_html(&if status.proxy_networks.is_empty() {
+            "-".to_string()
+        } else {
+            status.proxy_networks.join(", ")
+        }),
+        escape_html(&status.summary),
+    ));
+    out.push_str(
+        "<table>\n<tr><th>Host port</th><th>Target</th><th>Internal port</th>\
+         <th>Backend</th><th>Note</th></tr>\n",
+    );
+    for route in &status.routes {
+        out.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape_html(&route.ports),
+            escape_html(&route.target),
+            route
+                .internal_port
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            escape_html(&route.backend_status),
+            escape_html(&route_note(route)),
+        ));
+    }
+    out.push_str("</table>\n</body>\n</html>\n");
+    out
+}
+
+/// The free-text column of a route row: the description plus an unbound
+/// marker.
+fn route_note(route: &RouteStatus) -> String {
+    let mut note = route.description.clone().unwrap_or_default();
+    if route.unbound {
+        if !note.is_empty() {
+            note.push(' ');
+        }
+        note.push_str("(unbound)");
+    }
+    note
+}
+
+/// Render the networks listing: one line per network, `*` marking the
+/// ones the configured proxy would join, optionally with the attached
+/// container names indented underneath (configured ones marked too).
+pub fn network_listing(
+    networks: &[crate::docker::NetworkInfo],
+    config: &crate::config::Config,
+    show_containers: bool,
+) -> Vec<String> {
+    let joined = config.all_networks();
+    let mut lines = Vec::new();
+    for network in networks {
+        let mark =
+            if joined.contains(&network.name) || config.external_networks.contains(&network.name) {
+                "*"
+            } else {
+                " "
+            };
+        let count = network
+            .container_count
+            .map(|c| c.to_string())
+            .unwrap_or_else(|| "?".to_string());
+        lines.push(format!(
+            "{mark} {}  driver={}  containers={count}",
+            network.name, network.driver
+        ));
+        if !show_containers {
+            continue;
+        }
+        match &network.containers {
+            None => lines.push("    (members unknown)".to_string()),
+            Some(members) if members.is_empty() => {
+                lines.push("    (no containers attached)".to_string())
+            }
+            Some(members) => {
+                for member in members {
+                    let known = if config.find_container(member).is_some() {
+                        " *"
+                    } else {
+                        ""
+                    };
+                    lines.push(format!("    {member}{known}"));
+                }
+            }
+        }
+    }
+    lines
+}
+
+/// Per-port stacks of previous targets, newest last; kept in a sidecar
+/// file so undo survives restarts without touching the config itself.
+type RouteHistory = std::collections::HashMap<u16, Vec<(String, u16)>>;
+
+/// Entries kept per port; flipping between two targets needs very few.
+const ROUTE_HISTORY_DEPTH: usize = 10;
+
+fn load_route_history(path: &std::path::Path) -> RouteHistory {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save_route_history(path: &std::path::Path, history: &RouteHistory) -> Result<()> {
+    let data = serde_json::to_string_pretty(history)?;
+    std::fs::write(path, data).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(())
+}
+
+fn join_ports(ports: &[u16]) -> String {
+    ports
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Left-aligned table with two-space gaps; each column fits its longest
+/// cell. Trailing padding is trimmed so lines stay clean in pipes.
+fn render_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) -> Vec<String> {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let format_row = |cells: [&str; N]| {
+        let line = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        line.trim_end().to_string()
+    };
+    let mut lines = vec![format_row(header)];
+    lines.extend(
+        rows.iter()
+            .map(|row| format_row(row.each_ref().map(String::as_str))),
+    );
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_PROXY_NAME;
+    use crate::docker::ContainerInfo;
+    use crate::manager::tests::{test_config, FakeDocker};
+
+    /// SwitchOptions carrying only tags.
+    fn tags(tags: &[&str]) -> SwitchOptions {
+        SwitchOptions {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..SwitchOptions::default()
+        }
+    }
+
+    fn app_with(docker: Arc<FakeDocker>) -> (App, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Arc::new(ConfigManager::new(dir.path().to_path_buf()).unwrap());
+        (App::new(config, docker), dir)
+    }
+
+    #[tokio::test]
+    async fn status_info_collects_proxy_and_route_state() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker.clone());
+        app.config_manager().replace(test_config()).unwrap();
+        docker.containers.lock().unwrap().push(ContainerInfo {
+            name: "app1".into(),
+            status: "running".into(),
+            image: "app:1".into(),
+            ports: vec![8080],
+            networks: vec!["proxy-net".into()],
+        });
+        let status = app.status_info().await.unwrap();
+        assert_eq!(status.proxy_status, "not running");
+        assert_eq!(status.proxy_image, None);
+        assert_eq!(status.routes.len(), 1);
+        assert_eq!(status.routes[0].target, "app1");
+        assert_eq!(status.routes[0].backend_status, "running");
+        assert_eq!(status.routes[0].internal_port, Some(8080));
+    }
+
+    #[test]
+    fn status_reports_escape_hostile_input() {
+        let status = StatusInfo {
+            proxy_name: "<script>alert(1)</script>".into(),
+            proxy_status: "running".into(),
+            proxy_image: Some("evil\"image".into()),
+            proxy_networks: vec!["proxy-net".into()],
+            summary: "1 container (1 routed), 1 network, 1 route".into(),
+            routes: vec![RouteStatus {
+                ports: "8000".into(),
+                target: "app|one & <b>".into(),
+                internal_port: Some(8080),
+                backend_status: "running".into(),
+                unbound: true,
+                description: Some("pipe | in note".into()),
+            }],
+        };
+        let html = render_status_html(&status);
+        assert!(!html.contains("<script>alert"));
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(html.contains("evil&quot;image"));
+        assert!(html.contains("app|one &amp; &lt;b&gt;"));
+
+        let md = render_status_markdown(&status);
+        assert!(md.contains("| app\\|one & <b> |"));
+        assert!(md.contains("pipe \\| in note (unbound) |"));
+        assert!(md.contains("| Host port | Target | Internal port | Backend | Note |"));
+    }
+
+    #[tokio::test]
+    async fn metrics_come_out_in_exposition_format() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker.clone());
+        app.config_manager().replace(test_config()).unwrap();
+        let metrics = app.export_metrics_prometheus().await.unwrap();
+        assert!(metrics.contains("# TYPE proxy_manager_containers_total gauge"));
+        assert!(metrics.contains("proxy_manager_containers_total 1"));
+        assert!(metrics.contains("proxy_manager_routes_total 1"));
+        assert!(metrics.contains("proxy_manager_proxy_running 0"));
+        assert!(metrics.contains("proxy_manager_container_routed{container=\"app1\"} 1"));
+
+        // An unrouted container reports 0 and a running proxy flips the gauge.
+        app.config_manager()
+            .mutate(|c| {
+                let mut spare = c.containers[0].clone();
+                spare.name = "app2".into();
+                c.upsert_container(spare);
+            })
+            .unwrap();
+        docker.containers.lock().unwrap().push(ContainerInfo {
+            name: DEFAULT_PROXY_NAME.to_string(),
+            status: "running".into(),
+            image: String::new(),
+            ports: Vec::new(),
+            networks: Vec::new(),
+        });
+        let metrics = app.export_metrics_prometheus().await.unwrap();
+        assert!(metrics.contains("proxy_manager_proxy_running 1"));
+        assert!(metrics.contains("proxy_manager_container_routed{container=\"app2\"} 0"));
+    }
+
+    #[tokio::test]
+    async fn read_only_refuses_mutations_but_not_reads() {
+        let docker = Arc::new(FakeDocker::default());
+        let (mut app, _dir) = app_with(docker);
+        app.force_read_only();
+        let err = app.start(false, false).await.unwrap_err();
+        assert!(err.is::<crate::app::ReadOnlyError>());
+        assert!(app
+            .stop()
+            .await
+            .unwrap_err()
+            .is::<crate::app::ReadOnlyError>());
+        assert!(app
+            .switch(8000, "app1", SwitchOptions::default())
+            .await
+            .unwrap_err()
+            .is::<crate::app::ReadOnlyError>());
+        assert!(app
+            .add_container("app1", None, Some(8080), None, None, None)
+            .await
+            .unwrap_err()
+            .is::<crate::app::ReadOnlyError>());
+        // Reads keep working.
+        app.detect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_only_comes_from_config_with_flag_precedence() {
+        let docker = Arc::new(FakeDocker::default());
+        let (mut app, _dir) = app_with(docker);
+        assert!(!app.is_read_only());
+        app.config_manager().mutate(|c| c.read_only = true).unwrap();
+        assert!(app.is_read_only());
+        // The flag forces read-only even when the config says otherwise.
+        app.config_manager()
+            .mutate(|c| c.read_only = false)
+            .unwrap();
+        assert!(!app.is_read_only());
+        app.force_read_only();
+        assert!(app.is_read_only());
+    }
+
+    #[test]
+    fn port_drift_warning_fires_only_when_the_port_vanished() {
+        let mut config = crate::config::Config::default();
+        config.set_route(8000, "app1", 8080);
+        let route = config.routes.first().unwrap();
+        // Port still exposed, or no metadata at all: no warning.
+        assert!(port_drift_warning(route, &[8080, 9090]).is_none());
+        assert!(port_drift_warning(route, &[]).is_none());
+        // Port gone: name both sides.
+        let warning = port_drift_warning(route, &[3000]).unwrap();
+        assert_eq!(
+            warning,
+            "route 8000 -> app1:8080 but app1 now exposes [3000]"
+        );
+    }
+
+    #[tokio::test]
+    async fn switch_records_observed_ports_and_warns_on_drift() {
+        let docker = Arc::new(FakeDocker::default());
+        docker
+            .containers
+            .lock()
+            .unwrap()
+            .push(crate::docker::ContainerInfo {
+                name: "app1".to_string(),
+                image: "app:2".to_string(),
+                status: "running".to_string(),
+                networks: vec![],
+                ports: vec![3000],
+            });
+        let (app, _dir) = app_with(docker);
+        app.add_container("app1", None, Some(8080), None, None, None)
+            .await
+            .unwrap();
+        let output = app
+            .switch(8000, "app1", SwitchOptions::default())
+            .await
+            .unwrap();
+        assert!(output
+            .iter()
+            .any(|l| l.contains("but app1 now exposes [3000]")));
+        let config = app.config_manager().get().clone();
+        assert_eq!(config.routes.first().unwrap().observed_ports, vec![3000]);
+        assert!(!app.port_drift_warnings().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn refresh_port_adopts_the_single_exposed_port() {
+        let docker = Arc::new(FakeDocker::default());
+        docker
+            .containers
+            .lock()
+            .unwrap()
+            .push(crate::docker::ContainerInfo {
+                name: "app1".to_string(),
+                image: "app:2".to_string(),
+                status: "running".to_string(),
+                networks: vec![],
+                ports: vec![3000],
+            });
+        let (app, _dir) = app_with(docker);
+        app.add_container("app1", None, Some(8080), None, None, None)
+            .await
+            .unwrap();
+        let options = SwitchOptions {
+            refresh_port: true,
+            ..SwitchOptions::default()
+        };
+        let output = app.switch(8000, "app1", options).await.unwrap();
+        assert!(output.iter().any(|l| l.contains("8080 -> 3000")));
+        let config = app.config_manager().get().clone();
+        assert_eq!(config.find_container("app1").unwrap().port, 3000);
+        assert_eq!(config.routes.first().unwrap().internal_port, 3000);
+        assert!(app.port_drift_warnings().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn stub_status_parsing_survives_real_output() {
+        let body = "Active connections: 3 \nserver accepts handled requests\n 16 16 31 \nReading: 0 Writing: 1 Waiting: 2 \n";
+        assert_eq!(parse_active_connections(body), Some(3));
+        assert_eq!(parse_active_connections("garbage"), None);
+    }
+
+    #[tokio::test]
+    async fn idle_wait_discounts_the_probe_connection() {
+        let docker = Arc::new(FakeDocker::default());
+        let url = format!(
+            "http://127.0.0.1:{}/nginx_status",
+            crate::nginx::INTERNAL_STATUS_PORT
+        );
+        docker.exec_results.lock().unwrap().push((
+            url.clone(),
+            0,
+            "Active connections: 1 \n".to_string(),
+        ));
+        let (app, _dir) = app_with(docker.clone());
+        assert!(app.wait_until_idle(Duration::from_secs(1)).await.unwrap());
+
+        // Real traffic on top of the probe: the wait times out instead.
+        docker.exec_results.lock().unwrap().clear();
+        docker
+            .exec_results
+            .lock()
+            .unwrap()
+            .push((url, 0, "Active connections: 4 \n".to_string()));
+        assert!(!app.wait_until_idle(Duration::ZERO).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn ps_joins_routes_with_live_state_into_an_aligned_table() {
+        let docker = Arc::new(FakeDocker::default());
+        docker.containers.lock().unwrap().push(ContainerInfo {
+            name: "app1".into(),
+            image: "app1:latest".into(),
+            status: "running".into(),
+            networks: vec!["proxy-network".into()],
+            ports: vec![8080],
+        });
+        let (app, _dir) = app_with(docker);
+        app.config_manager().replace(test_config()).unwrap();
+        let lines = app.ps().await.unwrap();
+        assert!(lines[0].starts_with("NAME"));
+        assert!(lines[0].contains("HOST_PORT"));
+        // Proxy row first: not running, bound to the route's host port.
+        assert!(lines[1].starts_with("proxy-manager"));
+        assert!(lines[1].contains("not found"));
+        let app1 = lines.iter().find(|l| l.starts_with("app1")).unwrap();
+        assert!(app1.contains("running"));
+        assert!(app1.contains("8000"));
+        assert!(app1.contains("8080"));
+        // Columns line up: every STATUS cell starts at the same offset.
+        let offset = lines[0].find("STATUS").unwrap();
+        assert_eq!(app1.find("running").unwrap(), offset);
+    }
+
+    #[test]
+    fn network_listing_marks_joined_networks_and_known_members() {
+        use crate::docker::NetworkInfo;
+        let config = test_config();
+        let networks = vec![
+            NetworkInfo {
+                name: "proxy-net".into(),
+                driver: "bridge".into(),
+                container_count: Some(2),
+                containers: Some(vec!["app1".into(), "stranger".into()]),
+                managed: false,
+            },
+            NetworkInfo {
+                name: "other".into(),
+                driver: "bridge".into(),
+                container_count: None,
+                containers: None,
+                managed: false,
+            },
+            NetworkInfo {
+                name: "empty".into(),
+                driver: "bridge".into(),
+                container_count: Some(0),
+                containers: Some(Vec::new()),
+                managed: false,
+            },
+        ];
+        let lines = network_listing(&networks, &config, true);
+        assert!(lines[0].starts_with("* proxy-net"));
+        assert!(lines.contains(&"    app1 *".to_string()));
+        assert!(lines.contains(&"    stranger".to_string()));
+        // None members are reported as unknown, not as an empty network.
+        assert!(lines[3].starts_with("  other"));
+        assert!(lines.contains(&"    (members unknown)".to_string()));
+        assert!(lines.contains(&"    (no containers attached)".to_string()));
+        // Without --containers only the summary lines remain.
+        assert_eq!(network_listing(&networks, &config, false).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn undo_switch_flips_back_through_the_history() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker);
+        let mut config = test_config();
+        config.upsert_container(crate::config::Container {
+            name: "app2".into(),
+            label: None,
+            port: 9090,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        app.config_manager().replace(config).unwrap();
+        // No history yet: undo has nothing to restore.
+        let err = app.undo_switch(8000).await.unwrap_err();
+        assert!(err.to_string().contains("no switch history"));
+
+        app.switch(8000, "app2", SwitchOptions::default())
+            .await
+            .unwrap();
+        let output = app.undo_switch(8000).await.unwrap();
+        assert!(output[0].contains("8000 -> app1:8080"));
+        assert_eq!(
+            app.config_manager().get().find_route(8000).unwrap().target,
+            "app1"
+        );
+        // The stack is consumed: a second undo fails again.
+        let err = app.undo_switch(8000).await.unwrap_err();
+        assert!(err.to_string().contains("no switch history"));
+    }
+
+    #[tokio::test]
+    async fn batch_add_saves_once_and_reports_failures() {
+        let docker = Arc::new(FakeDocker::default());
+        docker
+            .containers
+            .lock()
+            .unwrap()
+            .push(crate::docker::ContainerInfo {
+                name: "app1".to_string(),
+                image: "app:1".to_string(),
+                status: "running".to_string(),
+                networks: vec!["backend-net".to_string()],
+                ports: vec![3000],
+            });
+        let (app, _dir) = app_with(docker.clone());
+        let entries = crate::batch::parse_batch(
+            "app1
+app2:8080
+ghost
+",
+        )
+        .unwrap();
+        let (output, failed) = app.add_containers_batch(entries).await.unwrap();
+        assert_eq!(failed, 1);
+        assert!(output.contains(&"Added app1:3000".to_string()));
+        assert!(output.contains(&"Added app2:8080".to_string()));
+        let config = app.config_manager().get().clone();
+        assert_eq!(config.containers.len(), 2);
+        // The whole batch needs exactly one Docker listing.
+        let calls = docker.calls.lock().unwrap();
+        assert_eq!(calls.iter().filter(|c| *c == "list_containers").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn switch_requires_known_container() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker);
+        let err = app
+            .switch(8000, "ghost", SwitchOptions::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("ghost"));
+    }
+
+    #[tokio::test]
+    async fn switch_defaults_to_container_port() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker);
+        app.config_manager().replace(test_config()).unwrap();
+        app.switch(9000, "app1", SwitchOptions::default())
+            .await
+            .unwrap();
+        let config = app.config_manager().get().clone();
+        let route = config.find_route(9000).unwrap();
+        assert_eq!(route.internal_port, 8080);
+    }
+
+    #[tokio::test]
+    async fn wait_for_targets_succeeds_when_all_running() {
+        let docker = Arc::new(FakeDocker::default());
+        docker
+            .containers
+            .lock()
+            .unwrap()
+            .push(crate::docker::ContainerInfo {
+                name: "app1".into(),
+                image: String::new(),
+                status: "running".into(),
+                networks: vec![],
+                ports: vec![],
+            });
+        let (app, _dir) = app_with(docker);
+        app.config_manager().replace(test_config()).unwrap();
+        let output = app.wait_for_targets(0, true).await.unwrap();
+        assert!(output[0].contains("1 target(s) running"));
+    }
+
+    #[tokio::test]
+    async fn wait_for_targets_warns_or_fails_on_timeout() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker);
+        app.config_manager().replace(test_config()).unwrap();
+
+        let output = app.wait_for_targets(0, false).await.unwrap();
+        assert!(output[0].contains("app1"));
+        assert!(output[0].contains("starting anyway"));
+
+        let err = app.wait_for_targets(0, true).await.unwrap_err();
+        assert!(err.to_string().contains("app1"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn switch_notes_host_port_matching_an_internal_port() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker);
+        app.config_manager().replace(test_config()).unwrap();
+        // app1's internal port is 8080; using it as the host port is likely
+        // a host/container port mix-up worth calling out.
+        let output = app
+            .switch(8080, "app1", SwitchOptions::default())
+            .await
+            .unwrap();
+        assert!(output[0].contains("8080 -> app1:8080"));
+        assert!(output.iter().any(|l| l.contains("Note: host port 8080")));
+    }
+
+    #[tokio::test]
+    async fn stop_port_on_unknown_route_errors() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker);
+        app.config_manager().replace(test_config()).unwrap();
+        assert!(app.stop_port(1234, false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn switch_applies_and_preserves_tags() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker);
+        app.config_manager().replace(test_config()).unwrap();
+        app.switch(8000, "app1", tags(&["project-x"]))
+            .await
+            .unwrap();
+        // Re-switching the same port without tags keeps the existing tag.
+        app.switch(
+            8000,
+            "app1",
+            SwitchOptions {
+                internal_port: Some(9090),
+                ..SwitchOptions::default()
+            },
+        )
+        .await
+        .unwrap();
+        let config = app.config_manager().get().clone();
+        assert!(config.find_route(8000).unwrap().has_tag("project-x"));
+    }
+
+    #[tokio::test]
+    async fn stop_tag_requires_a_match_and_reloads_once() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker.clone());
+        app.config_manager().replace(test_config()).unwrap();
+        app.switch(8000, "app1", tags(&["project-x"]))
+            .await
+            .unwrap();
+        app.switch(8001, "app1", tags(&["project-x"]))
+            .await
+            .unwrap();
+
+        assert!(app.stop_tag("other").await.is_err());
+
+        // Pretend the proxy is running so stop_tag goes through a reload.
+        fake_running_proxy(&docker);
+        docker.calls.lock().unwrap().clear();
+        app.stop_tag("project-x").await.unwrap();
+        let stops = docker
+            .calls()
+            .iter()
+            .filter(|c| c.starts_with("stop_and_remove"))
+            .count();
+        assert_eq!(stops, 1, "bulk stop must reload exactly once");
+        assert!(app.config_manager().get().routes.is_empty());
+    }
+
+    fn fake_running_proxy(docker: &FakeDocker) {
+        docker
+            .containers
+            .lock()
+            .unwrap()
+            .push(crate::docker::ContainerInfo {
+                name: "proxy-manager".into(),
+                image: String::new(),
+                status: "running".into(),
+                networks: vec![],
+                ports: vec![],
+            });
+    }
+
+    #[tokio::test]
+    async fn no_reload_defers_the_restart_until_an_explicit_reload() {
+        let docker = Arc::new(FakeDocker::default());
+        let (mut app, _dir) = app_with(docker.clone());
+        app.config_manager().replace(test_config()).unwrap();
+        fake_running_proxy(&docker);
+        app.set_no_reload();
+
+        docker.calls.lock().unwrap().clear();
+        let output = app
+            .switch(8000, "app1", SwitchOptions::default())
+            .await
+            .unwrap();
+        app.stop_port(8000, true).await.unwrap();
+        let calls = docker.calls();
+        assert!(
+            !calls
+                .iter()
+                .any(|c| c.starts_with("stop_and_remove") || c.starts_with("run ")),
+            "no reload cycle expected, got {calls:?}"
+        );
+        assert!(output
+            .iter()
+            .any(|l| l == "config changed; run 'reload' to apply"));
+    }
+
+    #[tokio::test]
+    async fn stop_port_keep_round_trips_through_resume() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker);
+        app.config_manager().replace(test_config()).unwrap();
+
+        app.stop_port(8000, true).await.unwrap();
+        let config = app.config_manager().get().clone();
+        assert!(config.find_route(8000).unwrap().unbound);
+        assert!(config.host_ports().is_empty());
+
+        app.resume_port(8000).await.unwrap();
+        let config = app.config_manager().get().clone();
+        assert!(!config.find_route(8000).unwrap().unbound);
+        assert_eq!(config.host_ports(), vec![8000]);
+        // Resuming an already-bound route errors.
+        assert!(app.resume_port(8000).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn stop_port_does_exactly_one_stop_start_cycle() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker.clone());
+        let mut config = test_config();
+        config.set_route(8001, "app1", 8080);
+        app.config_manager().replace(config).unwrap();
+        fake_running_proxy(&docker);
+
+        docker.calls.lock().unwrap().clear();
+        app.stop_port(8000, false).await.unwrap();
+        let calls = docker.calls();
+        let stops = calls
+            .iter()
+            .filter(|c| c.starts_with("stop_and_remove"))
+            .count();
+        let starts = calls.iter().filter(|c| c.starts_with("run ")).count();
+        assert_eq!(stops, 1, "expected exactly one stop, got {calls:?}");
+        assert_eq!(starts, 1, "expected exactly one start, got {calls:?}");
+    }
+
+    #[tokio::test]
+    async fn prune_images_skips_image_in_use() {
+        use crate::docker::ImageInfo;
+        let docker = Arc::new(FakeDocker::default());
+        docker.images.lock().unwrap().extend([
+            ImageInfo {
+                id: "sha256:aaa".into(),
+                size: 10 * 1024 * 1024,
+                tags: vec![],
+            },
+            ImageInfo {
+                id: "sha256:bbb".into(),
+                size: 5 * 1024 * 1024,
+                tags: vec![],
+            },
+        ]);
+        *docker.in_use_image.lock().unwrap() = Some("sha256:aaa".into());
+        let (app, _dir) = app_with(docker.clone());
+        let output = app.prune_images().await.unwrap();
+        assert!(output.iter().any(|l| l.starts_with("Skipped aaa")));
+        assert!(output.iter().any(|l| l.starts_with("Removed bbb")));
+        assert!(output.iter().any(|l| l.contains("5.0 MB")));
+        let remaining = docker.images.lock().unwrap().clone();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, "sha256:aaa");
+    }
+
+    #[tokio::test]
+    async fn remove_container_without_routes_needs_no_force() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker);
+        let mut config = test_config();
+        config.routes.clear();
+        app.config_manager().replace(config).unwrap();
+        app.remove_container("app1", false, None).await.unwrap();
+        assert!(app.config_manager().get().containers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn remove_container_with_routes_requires_force() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker);
+        app.config_manager().replace(test_config()).unwrap();
+        let err = app.remove_container("app1", false, None).await.unwrap_err();
+        assert!(err.to_string().contains("8000"), "{err}");
+        assert!(!app.config_manager().get().containers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn remove_container_force_drops_its_routes() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker);
+        app.config_manager().replace(test_config()).unwrap();
+        let output = app.remove_container("app1", true, None).await.unwrap();
+        assert!(output.iter().any(|l| l.contains("1 route(s)")));
+        let config = app.config_manager().get().clone();
+        assert!(config.containers.is_empty());
+        assert!(config.routes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn remove_container_retarget_repoints_routes_and_reloads_once() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker.clone());
+        let mut config = test_config();
+        config.upsert_container(crate::config::Container {
+            name: "app2".into(),
+            label: None,
+            port: 3000,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        app.config_manager().replace(config).unwrap();
+        fake_running_proxy(&docker);
+
+        let output = app
+            .remove_container("app1", false, Some("app2"))
+            .await
+            .unwrap();
+        assert!(output.iter().any(|l| l.contains("Retargeted")));
+        let config = app.config_manager().get().clone();
+        let route = config.find_route(8000).unwrap();
+        assert_eq!(route.target, "app2");
+        assert_eq!(route.internal_port, 3000);
+
+        let calls = docker.calls();
+        let starts = calls.iter().filter(|c| c.starts_with("run ")).count();
+        assert_eq!(starts, 1, "expected exactly one reload, got {calls:?}");
+    }
+
+    #[tokio::test]
+    async fn auto_discover_adds_unknown_and_skips_configured() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker.clone());
+        app.config_manager().replace(test_config()).unwrap();
+        fake_running_proxy(&docker);
+        for (name, port) in [("app1", 8080), ("worker", 9000)] {
+            docker
+                .containers
+                .lock()
+                .unwrap()
+                .push(crate::docker::ContainerInfo {
+                    name: name.into(),
+                    image: String::new(),
+                    status: "running".into(),
+                    networks: vec!["proxy-net".into()],
+                    ports: vec![port],
+                });
+        }
+
+        let output = app.auto_discover_and_add(None, false).await.unwrap();
+        assert!(output.contains(&"Skipped app1 (already configured)".to_string()));
+        assert!(output.contains(&"Added worker".to_string()));
+        // The proxy itself is never offered for registration.
+        assert!(!output.iter().any(|l| l.contains("proxy-manager")));
+        let config = app.config_manager().get().clone();
+        assert_eq!(config.find_container("worker").unwrap().port, 9000);
+    }
+
+    #[tokio::test]
+    async fn auto_discover_honours_prefix_and_dry_run() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker.clone());
+        for name in ["svc-a", "other"] {
+            docker
+                .containers
+                .lock()
+                .unwrap()
+                .push(crate::docker::ContainerInfo {
+                    name: name.into(),
+                    image: String::new(),
+                    status: "running".into(),
+                    networks: vec![],
+                    ports: vec![80],
+                });
+        }
+
+        let output = app.auto_discover_and_add(Some("svc-"), true).await.unwrap();
+        assert_eq!(output, vec!["Would add svc-a".to_string()]);
+        assert!(app.config_manager().get().containers.is_empty());
+    }
+
+    fn seed_network(docker: &FakeDocker, name: &str, managed: bool, attached: usize) {
+        docker
+            .networks
+            .lock()
+            .unwrap()
+            .push(crate::docker::NetworkInfo {
+                name: name.into(),
+                driver: "bridge".into(),
+                container_count: Some(attached),
+                containers: None,
+                managed,
+            });
+    }
+
+    #[tokio::test]
+    async fn down_removes_container_then_image_and_managed_networks() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker.clone());
+        app.config_manager().replace(test_config()).unwrap();
+        seed_network(&docker, "proxy-net", true, 0);
+
+        let output = app.down(false, false).await.unwrap();
+        assert!(output.iter().any(|l| l.contains("Removed image")));
+        assert!(output
+            .iter()
+            .any(|l| l.contains("Removed network 'proxy-net'")));
+        let calls = docker.calls();
+        let stop = calls
+            .iter()
+            .position(|c| c.starts_with("stop_and_remove"))
+            .unwrap();
+        let rmi = calls
+            .iter()
+            .position(|c| c.starts_with("remove_image"))
+            .unwrap();
+        assert!(stop < rmi, "container must be removed before the image");
+    }
+
+    #[tokio::test]
+    async fn down_preserves_busy_and_unlabeled_networks() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker.clone());
+        let mut config = test_config();
+        config.find_container_mut("app1").unwrap().network = Some("shared".into());
+        app.config_manager().replace(config).unwrap();
+        seed_network(&docker, "proxy-net", false, 0);
+        seed_network(&docker, "shared", true, 2);
+
+        let output = app.down(false, false).await.unwrap();
+        assert!(output
+            .iter()
+            .any(|l| l.contains("Skipped network 'proxy-net'")));
+        assert!(output.iter().any(|l| l.contains("Kept network 'shared'")));
+        assert!(!docker
+            .calls()
+            .iter()
+            .any(|c| c.starts_with("remove_network")));
+    }
+
+    #[tokio::test]
+    async fn down_succeeds_when_the_image_is_already_gone() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker.clone());
+        app.config_manager().replace(test_config()).unwrap();
+        // FakeDocker's remove_image tolerates unknown ids, mirroring the
+        // real client's 404 handling.
+        let output = app.down(false, true).await.unwrap();
+        assert!(output.iter().any(|l| l.contains("Removed image")));
+    }
+
+    #[test]
+    fn publish_conflict_warning_names_the_container_and_ports() {
+        assert!(publish_conflict_warning("app1", &[3000], &[8000]).is_none());
+        assert!(publish_conflict_warning("app1", &[], &[8000]).is_none());
+        let warning = publish_conflict_warning("app1", &[8000, 3000], &[8000, 8001]).unwrap();
+        assert!(warning.contains("'app1'"));
+        assert!(warning.contains("8000"));
+        assert!(!warning.contains("3000"));
+        // Spans the joins of the multi-line source string, so a missing
+        // line continuation (a run of spaces) would fail here.
+        assert!(warning.contains("that the proxy binds too"));
+        assert!(warning.contains("drop its -p flag"));
+    }
+
+    #[test]
+    fn low_port_warning_only_fires_for_rootless_low_ports() {
+        // Rootful daemons bind anything, rootless ones only from the
+        // unprivileged start upwards.
+        assert!(low_port_warning(&[80], false, 1024).is_none());
+        assert!(low_port_warning(&[8080], true, 1024).is_none());
+        assert!(low_port_warning(&[80], true, 80).is_none());
+        let warning = low_port_warning(&[80, 443, 8080], true, 1024).unwrap();
+        assert!(warning.contains("80, 443"));
+        assert!(!warning.contains("8080"));
+        assert!(warning.contains("--privileged-ports-ok"));
+    }
+
+    #[tokio::test]
+    async fn start_warns_about_low_ports_before_building() {
+        let docker = Arc::new(FakeDocker::default());
+        *docker.rootless.lock().unwrap() = true;
+        let (app, _dir) = app_with(docker);
+        let mut config = test_config();
+        config.set_route(80, "app1", 8080);
+        app.config_manager().replace(config).unwrap();
+
+        let output = app.start(false, true).await.unwrap();
+        assert!(output[0].starts_with("Warning: host port(s) 80"));
+        // --privileged-ports-ok silences it.
+        let output = app.start(true, true).await.unwrap();
+        assert!(!output.iter().any(|l| l.starts_with("Warning: host port")));
+    }
+
+    fn seed_proxy_labels(docker: &FakeDocker, proxy: &str, hash: Option<&str>) {
+        let mut labels = docker.labels.lock().unwrap();
+        labels.push((
+            proxy.to_string(),
+            crate::docker::MANAGED_BY_LABEL.to_string(),
+            crate::docker::MANAGED_BY_VALUE.to_string(),
+        ));
+        if let Some(hash) = hash {
+            labels.push((
+                proxy.to_string(),
+                crate::docker::CONFIG_HASH_LABEL.to_string(),
+                hash.to_string(),
+            ));
+        }
+    }
+
+    #[test]
+    fn orphan_classification_is_by_label_and_name() {
+        let managed: std::collections::HashMap<String, String> = [(
+            crate::docker::MANAGED_BY_LABEL.to_string(),
+            crate::docker::MANAGED_BY_VALUE.to_string(),
+        )]
+        .into();
+        let labelled = vec![
+            ("proxy-manager".to_string(), managed.clone()),
+            ("old-proxy".to_string(), managed.clone()),
+            ("ancient-proxy".to_string(), managed),
+            ("app1".to_string(), std::collections::HashMap::new()),
+        ];
+        assert_eq!(
+            orphaned_proxies(&labelled, "proxy-manager"),
+            vec!["ancient-proxy".to_string(), "old-proxy".to_string()]
+        );
+        // The current proxy is never an orphan of itself.
+        assert_eq!(
+            orphaned_proxies(&labelled, "old-proxy"),
+            vec!["ancient-proxy".to_string(), "proxy-manager".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn orphans_lists_flags_conflicts_and_removes() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker.clone());
+        app.config_manager().replace(test_config()).unwrap();
+        docker
+            .containers
+            .lock()
+            .unwrap()
+            .push(crate::docker::ContainerInfo {
+                name: "old-proxy".to_string(),
+                image: "old-proxy:latest".to_string(),
+                status: "running".to_string(),
+                networks: Vec::new(),
+                ports: Vec::new(),
+            });
+        seed_proxy_labels(&docker, "old-proxy", None);
+        // Publishes the port the current config also routes on.
+        docker
+            .published_ports
+            .lock()
+            .unwrap()
+            .push(("old-proxy".to_string(), 8000));
+
+        let output = app.orphans(false, None).await.unwrap();
+        assert_eq!(output.len(), 1);
+        assert!(output[0].starts_with("old-proxy (port(s) 8000"));
+        assert!(output[0].contains("holds configured port(s) 8000"));
+
+        let output = app.orphans(true, None).await.unwrap();
+        assert_eq!(
+            output,
+            vec!["Removed orphaned proxy container 'old-proxy'".to_string()]
+        );
+        assert!(docker
+            .calls()
+            .contains(&"stop_and_remove old-proxy".to_string()));
+    }
+
+    #[tokio::test]
+    async fn adopting_an_orphan_renames_the_proxy() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker.clone());
+        app.config_manager().replace(test_config()).unwrap();
+        docker
+            .containers
+            .lock()
+            .unwrap()
+            .push(crate::docker::ContainerInfo {
+                name: "old-proxy".to_string(),
+                image: "old-proxy:latest".to_string(),
+                status: "running".to_string(),
+                networks: Vec::new(),
+                ports: Vec::new(),
+            });
+        seed_proxy_labels(&docker, "old-proxy", None);
+
+        assert!(app.orphans(false, Some("app1")).await.is_err());
+        let output = app.orphans(false, Some("old-proxy")).await.unwrap();
+        assert!(output[0].starts_with("Adopted 'old-proxy'"));
+        assert_eq!(app.config_manager().get().proxy_name, "old-proxy");
+        // Once adopted there is nothing left to report.
+        let output = app.orphans(false, None).await.unwrap();
+        assert_eq!(
+            output,
+            vec!["No orphaned proxy containers found".to_string()]
+        );
+    }
+
+    #[test]
+    fn config_fmt_reports_and_rewrites_non_canonical_files() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker);
+        app.config_manager().replace(test_config()).unwrap();
+        assert!(app.config_fmt(true).is_ok());
+
+        // A hand-edited, non-canonical file fails --check...
+        let path = app.store().config_file();
+        let compact = serde_json::to_string(&*app.config_manager().get()).unwrap();
+        std::fs::write(&path, compact).unwrap();
+        let err = app.config_fmt(true).unwrap_err();
+        assert!(err.to_string().contains("not in canonical form"));
+
+        // ...and a plain fmt rewrites it back to canonical.
+        let output = app.config_fmt(false).unwrap();
+        assert!(output[0].starts_with("Rewrote"));
+        assert!(app.config_fmt(true).is_ok());
+        assert!(std::fs::read_to_string(&path).unwrap().ends_with("}\n"));
+    }
+
+    #[test]
+    fn start_summary_counts_ports_routes_and_warnings() {
+        let lines = vec![
+            "Warning: host port(s) 80 are below...".to_string(),
+            "Built image 'proxy-manager:latest'".to_string(),
+            "Warning: backend TLS verification is DISABLED for route 8000".to_string(),
+        ];
+        assert_eq!(
+            start_summary(2, 3, &lines),
+            "Proxy running on 2 port(s); 3 route(s); 2 warning(s)"
+        );
+        assert_eq!(
+            start_summary(1, 1, &[]),
+            "Proxy running on 1 port(s); 1 route(s); 0 warning(s)"
+        );
+    }
+
+    #[test]
+    fn log_tails_clamp_to_the_configured_limit() {
+        assert_eq!(clamp_log_tail(100, 10_000), (100, None));
+        let (tail, notice) = clamp_log_tail(100_000, 10_000);
+        assert_eq!(tail, 10_000);
+        // Spans the join of the multi-line source string, so a missing
+        // line continuation (a run of spaces) would fail here.
+        assert!(notice
+            .unwrap()
+            .contains("log_tail_limit (10000); showing the last 10000 lines"));
+    }
+
+    #[test]
+    fn ping_latency_parses_and_rounds_up() {
+        let output = "64 bytes from 172.18.0.2: seq=0 ttl=64 time=0.123 ms";
+        assert_eq!(parse_ping_latency(output), Some(1));
+        assert_eq!(parse_ping_latency("time=12.7 ms"), Some(13));
+        assert_eq!(parse_ping_latency("no reply"), None);
+    }
+
+    #[tokio::test]
+    async fn connectivity_check_reports_per_container_reachability() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker.clone());
+        let mut config = test_config();
+        config.upsert_container(crate::config::Container {
+            name: "app2".into(),
+            label: None,
+            port: 3000,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        app.config_manager().replace(config.clone()).unwrap();
+        docker
+            .containers
+            .lock()
+            .unwrap()
+            .push(crate::docker::ContainerInfo {
+                name: config.proxy_name.clone(),
+                image: format!("{}:latest", config.proxy_name),
+                status: "running".to_string(),
+                networks: Vec::new(),
+                ports: Vec::new(),
+            });
+        docker.exec_results.lock().unwrap().push((
+            "app1".to_string(),
+            0,
+            "64 bytes from 172.18.0.2: time=0.4 ms".to_string(),
+        ));
+        docker
+            .exec_results
+            .lock()
+            .unwrap()
+            .push(("app2".to_string(), 1, String::new()));
+
+        let results = app.network_connectivity_check().await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].reachable);
+        assert_eq!(results[0].latency_ms, Some(1));
+        assert_eq!(results[0].address, "app1:8080");
+        assert!(!results[1].reachable);
+        assert_eq!(results[1].latency_ms, None);
+    }
+
+    #[tokio::test]
+    async fn connectivity_check_requires_a_running_proxy() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker);
+        app.config_manager().replace(test_config()).unwrap();
+        let err = app.network_connectivity_check().await.unwrap_err();
+        assert!(err.to_string().contains("not running"));
+    }
+
+    #[tokio::test]
+    async fn start_is_a_noop_when_our_proxy_runs_the_same_config() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker.clone());
+        app.config_manager().replace(test_config()).unwrap();
+        let config = app.config_manager().get().clone().interpolated().unwrap();
+        seed_proxy_labels(&docker, &config.proxy_name, Some(&config_hash(&config)));
+        docker
+            .containers
+            .lock()
+            .unwrap()
+            .push(crate::docker::ContainerInfo {
+                name: config.proxy_name.clone(),
+                image: format!("{}:latest", config.proxy_name),
+                status: "running".to_string(),
+                networks: Vec::new(),
+                ports: Vec::new(),
+            });
+
+        let output = app.start(true, false).await.unwrap();
+        assert!(output[0].contains("already running with this configuration"));
+        let calls = docker.calls.lock().unwrap().clone();
+        assert!(!calls.iter().any(|c| c.starts_with("run ")));
+    }
+
+    #[tokio::test]
+    async fn start_requires_take_over_for_a_different_config() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker.clone());
+        app.config_manager().replace(test_config()).unwrap();
+        let proxy = app.config_manager().get().proxy_name.clone();
+        seed_proxy_labels(&docker, &proxy, Some("someone-elses-hash"));
+
+        let err = app.start(true, false).await.unwrap_err();
+        assert!(err.to_string().contains("--take-over"));
+        // With the flag, the existing container is replaced.
+        let output = app.start(true, true).await.unwrap();
+        assert!(output.iter().any(|l| l.starts_with("Started proxy")));
+    }
+
+    #[tokio::test]
+    async fn start_refuses_to_touch_a_foreign_container() {
+        let docker = Arc::new(FakeDocker::default());
+        let (app, _dir) = app_with(docker.clone());
+        app.config_manager().replace(test_config()).unwrap();
+        let proxy = app.config_manager().get().proxy_name.clone();
+        // The name is taken but carries no managed-by label.
+        docker
+            .containers
+            .lock()
+            .unwrap()
+            .push(crate::docker::ContainerInfo {
+                name: proxy.clone(),
+                image: "somebody/elses:image".to_string(),
+                status: "running".to_string(),
+                networks: Vec::new(),
+                ports: Vec::new(),
+            });
+
+        let err = app.start(true, true).await.unwrap_err();
+        assert!(err.to_string().contains("not started by this tool"));
+        let calls = docker.calls.lock().unwrap().clone();
+        assert!(!calls.iter().any(|c| c.starts_with("stop_and_remove")));
+    }
+
+    #[tokio::test]
+    async fn start_warns_when_a_target_publishes_a_proxy_port() {
+        let docker = Arc::new(FakeDocker::default());
+        docker
+            .published_ports
+            .lock()
+            .unwrap()
+            .push(("app1".to_string(), 8000));
+        let (app, _dir) = app_with(docker.clone());
+        app.config_manager().replace(test_config()).unwrap();
+
+        let output = app.start(true, true).await.unwrap();
+        assert!(output
+            .iter()
+            .any(|l| l.contains("'app1' already publishes host port(s) 8000")));
+
+        // No overlap, no warning.
+        docker.published_ports.lock().unwrap().clear();
+        let output = app.start(true, true).await.unwrap();
+        assert!(!output.iter().any(|l| l.contains("already publishes")));
+    }
+}
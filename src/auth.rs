@@ -0,0 +1,172 @@
+//! htpasswd-style basic-auth hashing.
+//!
+//! nginx's `auth_basic_user_file` understands the Apache `$apr1$` MD5
+//! scheme on every platform it ships on, unlike bcrypt which depends on
+//! the system crypt(3). The algorithm is implemented here directly (it is
+//! tiny and frozen) on top of the `md-5` crate, so no plaintext password
+//! ever needs to leave the process.
+
+use md5::{Digest, Md5};
+
+/// Alphabet used by crypt-style base64 (note: not RFC 4648).
+const CRYPT_ALPHABET: &[u8] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Length of the generated salts; the maximum apr1 supports.
+const SALT_LEN: usize = 8;
+
+/// Hash `password` with a fresh random salt, returning the full
+/// `$apr1$<salt>$<hash>` string as stored in htpasswd files.
+pub fn hash_password(password: &str) -> String {
+    apr1(password, &random_salt())
+}
+
+/// Whether `password` matches an `$apr1$` hash produced by [`apr1`].
+pub fn verify(password: &str, hash: &str) -> bool {
+    let mut parts = hash.splitn(4, '$');
+    let (Some(""), Some("apr1"), Some(salt), Some(_)) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return false;
+    };
+    apr1(password, salt) == hash
+}
+
+/// One htpasswd file line (without trailing newline).
+pub fn htpasswd_line(username: &str, hash: &str) -> String {
+    format!("{username}:{hash}")
+}
+
+/// Apache's apr1 MD5 crypt: `$apr1$<salt>$<22 chars>`.
+pub fn apr1(password: &str, salt: &str) -> String {
+    let pw = password.as_bytes();
+    let salt = &salt.as_bytes()[..salt.len().min(SALT_LEN)];
+
+    let mut ctx = Md5::new();
+    ctx.update(pw);
+    ctx.update(b"$apr1$");
+    ctx.update(salt);
+
+    let mut ctx1 = Md5::new();
+    ctx1.update(pw);
+    ctx1.update(salt);
+    ctx1.update(pw);
+    let digest = ctx1.finalize();
+
+    let mut remaining = pw.len();
+    while remaining > 0 {
+        ctx.update(&digest[..remaining.min(16)]);
+        remaining = remaining.saturating_sub(16);
+    }
+    let mut bits = pw.len();
+    while bits != 0 {
+        if bits & 1 == 1 {
+            ctx.update([0u8]);
+        } else {
+            ctx.update(&pw[..1]);
+        }
+        bits >>= 1;
+    }
+    let mut digest = ctx.finalize();
+
+    // 1000 stretching rounds, mixing password and salt per the original.
+    for round in 0..1000 {
+        let mut ctx1 = Md5::new();
+        if round & 1 == 1 {
+            ctx1.update(pw);
+        } else {
+            ctx1.update(digest);
+        }
+        if round % 3 != 0 {
+            ctx1.update(salt);
+        }
+        if round % 7 != 0 {
+            ctx1.update(pw);
+        }
+        if round & 1 == 1 {
+            ctx1.update(digest);
+        } else {
+            ctx1.update(pw);
+        }
+        digest = ctx1.finalize();
+    }
+
+    let mut encoded = String::with_capacity(22);
+    for &(a, b, c) in &[(0, 6, 12), (1, 7, 13), (2, 8, 14), (3, 9, 15), (4, 10, 5)] {
+        let value = ((digest[a] as u32) << 16) | ((digest[b] as u32) << 8) | digest[c] as u32;
+        crypt_to64(&mut encoded, value, 4);
+    }
+    crypt_to64(&mut encoded, digest[11] as u32, 2);
+
+    format!(
+        "$apr1${}${encoded}",
+        std::str::from_utf8(salt).unwrap_or_default()
+    )
+}
+
+/// Append `count` crypt-base64 characters of `value`, least significant
+/// six bits first.
+fn crypt_to64(out: &mut String, mut value: u32, count: usize) {
+    for _ in 0..count {
+        out.push(CRYPT_ALPHABET[(value & 0x3f) as usize] as char);
+        value >>= 6;
+    }
+}
+
+/// A fresh salt from the kernel RNG, falling back to a clock-derived one
+/// when /dev/urandom is unavailable (salts need uniqueness, not secrecy).
+fn random_salt() -> String {
+    let mut bytes = [0u8; SALT_LEN];
+    let read = std::fs::File::open("/dev/urandom")
+        .and_then(|mut f| std::io::Read::read_exact(&mut f, &mut bytes));
+    let bytes = match read {
+        Ok(()) => bytes.to_vec(),
+        Err(_) => {
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or(0)
+                ^ u128::from(std::process::id());
+            nanos.to_le_bytes()[..SALT_LEN].to_vec()
+        }
+    };
+    bytes
+        .iter()
+        .map(|b| CRYPT_ALPHABET[(b & 0x3f) as usize] as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apr1_matches_a_known_htpasswd_hash() {
+        // Reference vector from `openssl passwd -apr1 -salt 'r31.....'`.
+        assert_eq!(
+            apr1("myPassword", "r31....."),
+            "$apr1$r31.....$HqJZimcKQFAMYayBlzkrA/"
+        );
+    }
+
+    #[test]
+    fn hash_and_verify_round_trip() {
+        let hash = hash_password("s3cret!");
+        assert!(hash.starts_with("$apr1$"));
+        assert!(verify("s3cret!", &hash));
+        assert!(!verify("wrong", &hash));
+        // Two hashes of the same password use different salts.
+        assert_ne!(hash, hash_password("s3cret!"));
+    }
+
+    #[test]
+    fn verify_rejects_malformed_hashes() {
+        assert!(!verify("pw", ""));
+        assert!(!verify("pw", "plaintext"));
+        assert!(!verify("pw", "$2y$10$notapr1"));
+    }
+
+    #[test]
+    fn htpasswd_line_joins_user_and_hash() {
+        assert_eq!(htpasswd_line("alice", "$apr1$x$y"), "alice:$apr1$x$y");
+    }
+}
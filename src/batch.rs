@@ -0,0 +1,265 @@
+//! Batch container registration: `add --from-file` / `--from-stdin`.
+//!
+//! Input is either a JSON array of `{name, label?, port?, network?}`
+//! objects or line-based `name[:port][@network]` entries. Parsing and
+//! batch application are pure so the IO-free parts stay testable; the
+//! whole batch lands in one config save.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::config::{Config, Container};
+use crate::docker::ContainerInfo;
+
+/// One container to register, before auto-detection fills the gaps.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchEntry {
+    pub name: String,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub network: Option<String>,
+}
+
+/// Parse batch input, accepting the JSON array format when the trimmed
+/// input starts with `[` and the line format otherwise.
+pub fn parse_batch(input: &str) -> Result<Vec<BatchEntry>> {
+    if input.trim_start().starts_with('[') {
+        parse_json_entries(input)
+    } else {
+        parse_line_entries(input)
+    }
+}
+
+/// Parse a JSON array of `{name, label?, port?, network?}` objects.
+pub fn parse_json_entries(input: &str) -> Result<Vec<BatchEntry>> {
+    let entries: Vec<BatchEntry> =
+        serde_json::from_str(input).context("invalid batch JSON; expected an array of objects")?;
+    for entry in &entries {
+        if entry.name.is_empty() {
+            bail!("batch entry with an empty name");
+        }
+    }
+    Ok(entries)
+}
+
+/// Parse line-based `name[:port][@network]` entries; blank lines and
+/// `#` comments are skipped.
+pub fn parse_line_entries(input: &str) -> Result<Vec<BatchEntry>> {
+    let mut entries = Vec::new();
+    for (number, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (spec, network) = match line.split_once('@') {
+            Some((spec, network)) if !network.is_empty() => (spec, Some(network.to_string())),
+            Some(_) => bail!("line {}: empty network in '{line}'", number + 1),
+            None => (line, None),
+        };
+        let (name, port) = match spec.split_once(':') {
+            Some((name, port)) => {
+                let port = port
+                    .parse::<u16>()
+                    .with_context(|| format!("line {}: invalid port in '{line}'", number + 1))?;
+                (name, Some(port))
+            }
+            None => (spec, None),
+        };
+        if name.is_empty() {
+            bail!("line {}: missing container name in '{line}'", number + 1);
+        }
+        entries.push(BatchEntry {
+            name: name.to_string(),
+            label: None,
+            port,
+            network,
+        });
+    }
+    Ok(entries)
+}
+
+/// Drop duplicate names keeping the last occurrence, returning a warning
+/// per dropped entry.
+pub fn dedupe_entries(entries: Vec<BatchEntry>) -> (Vec<BatchEntry>, Vec<String>) {
+    let mut warnings = Vec::new();
+    let mut kept: Vec<BatchEntry> = Vec::new();
+    for entry in entries {
+        if let Some(previous) = kept.iter().position(|e| e.name == entry.name) {
+            warnings.push(format!(
+                "Warning: duplicate entry for '{}'; the later one wins",
+                entry.name
+            ));
+            kept.remove(previous);
+        }
+        kept.push(entry);
+    }
+    (kept, warnings)
+}
+
+/// Apply a deduplicated batch to `config`, filling missing ports and
+/// networks from `running`. Returns per-entry summary lines and the
+/// number of failed entries; the caller saves the config once.
+pub fn apply_batch(
+    config: &mut Config,
+    entries: &[BatchEntry],
+    running: &[ContainerInfo],
+) -> (Vec<String>, usize) {
+    let mut output = Vec::new();
+    let mut failed = 0;
+    for entry in entries {
+        let info = running.iter().find(|c| c.name == entry.name);
+        let port = entry
+            .port
+            .or_else(|| info.and_then(|i| i.ports.first().copied()));
+        let Some(port) = port else {
+            output.push(format!(
+                "Failed {}: could not detect a port; pass one as 'name:port'",
+                entry.name
+            ));
+            failed += 1;
+            continue;
+        };
+        let network = entry
+            .network
+            .clone()
+            .or_else(|| info.and_then(|i| i.networks.first().cloned()));
+        let updated = config.find_container(&entry.name).is_some();
+        config.upsert_container(Container {
+            name: entry.name.clone(),
+            label: entry.label.clone(),
+            port,
+            network,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        output.push(format!(
+            "{} {}:{port}",
+            if updated { "Updated" } else { "Added" },
+            entry.name
+        ));
+    }
+    (output, failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, port: Option<u16>, network: Option<&str>) -> BatchEntry {
+        BatchEntry {
+            name: name.to_string(),
+            label: None,
+            port,
+            network: network.map(String::from),
+        }
+    }
+
+    #[test]
+    fn line_format_parses_ports_and_networks() {
+        let input = "app1\napp2:3000\n\n# comment\napp3:8080@backend-net\n";
+        let entries = parse_line_entries(input).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                entry("app1", None, None),
+                entry("app2", Some(3000), None),
+                entry("app3", Some(8080), Some("backend-net")),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_lines_name_the_line() {
+        let err = parse_line_entries("app1\napp2:http\n").unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+        assert!(parse_line_entries(":8080").is_err());
+        assert!(parse_line_entries("app1@").is_err());
+    }
+
+    #[test]
+    fn json_format_parses_and_rejects_bad_objects() {
+        let entries = parse_json_entries(
+            r#"[{"name": "app1"}, {"name": "app2", "port": 3000, "network": "backend-net"}]"#,
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].port, Some(3000));
+        // Unknown fields, wrong shapes and empty names are rejected.
+        assert!(parse_json_entries(r#"[{"name": "x", "prot": 1}]"#).is_err());
+        assert!(parse_json_entries(r#"{"name": "x"}"#).is_err());
+        assert!(parse_json_entries(r#"[{"name": ""}]"#).is_err());
+    }
+
+    #[test]
+    fn parse_batch_picks_the_format_by_first_character() {
+        assert_eq!(parse_batch(r#"  [{"name": "app1"}]"#).unwrap().len(), 1);
+        assert_eq!(parse_batch("app1:8080").unwrap()[0].port, Some(8080));
+    }
+
+    #[test]
+    fn duplicates_within_a_batch_keep_the_last_with_a_warning() {
+        let (entries, warnings) = dedupe_entries(vec![
+            entry("app1", Some(1000), None),
+            entry("app2", None, None),
+            entry("app1", Some(2000), None),
+        ]);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries.iter().find(|e| e.name == "app1").unwrap().port,
+            Some(2000)
+        );
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("'app1'"));
+    }
+
+    #[test]
+    fn apply_batch_detects_counts_failures_and_updates() {
+        let mut config = Config::default();
+        config.upsert_container(Container {
+            name: "app2".to_string(),
+            label: None,
+            port: 9999,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        let running = vec![ContainerInfo {
+            name: "app1".to_string(),
+            image: "app:1".to_string(),
+            status: "running".to_string(),
+            networks: vec!["backend-net".to_string()],
+            ports: vec![3000],
+        }];
+        let entries = vec![
+            entry("app1", None, None),
+            entry("app2", Some(8080), None),
+            entry("ghost", None, None),
+        ];
+        let (output, failed) = apply_batch(&mut config, &entries, &running);
+        assert_eq!(failed, 1);
+        assert_eq!(output[0], "Added app1:3000");
+        assert_eq!(output[1], "Updated app2:8080");
+        assert!(output[2].starts_with("Failed ghost"));
+        let app1 = config.find_container("app1").unwrap();
+        assert_eq!(app1.network.as_deref(), Some("backend-net"));
+        assert_eq!(config.find_container("app2").unwrap().port, 8080);
+    }
+}
@@ -0,0 +1,198 @@
+//! Caddyfile generation, for configs with `proxy_backend: caddy`.
+//!
+//! Mirrors the nginx generator for the common route shapes — proxying,
+//! static directories, maintenance pages and host matching — emitting
+//! `reverse_proxy` sites instead of nginx server blocks. nginx-only
+//! features (canaries, basic auth files, connection caps) are not
+//! translated; `Config::validate` still applies unchanged.
+
+use crate::config::Config;
+use crate::nginx::FALLBACK_MESSAGE;
+
+/// Base image used for Caddy builds, regardless of `base_image` (which
+/// stays nginx-flavoured for the default backend).
+pub const CADDY_BASE_IMAGE: &str = "caddy:latest";
+
+pub struct CaddyfileGenerator;
+
+impl CaddyfileGenerator {
+    /// Render the full Caddyfile for `config`.
+    pub fn generate(config: &Config) -> String {
+        let mut out = String::from("# Generated by proxy-manager; do not edit.\n");
+        out.push_str("{\n    admin off\n    auto_https off\n}\n");
+
+        for route in config.routes.iter().filter(|r| !r.unbound) {
+            let addresses: Vec<String> = route
+                .host_ports
+                .iter()
+                .map(|port| match &route.host {
+                    Some(host) => format!("http://{host}:{port}"),
+                    None => format!(":{port}"),
+                })
+                .collect();
+            out.push('\n');
+            out.push_str(&format!("{} {{\n", addresses.join(", ")));
+
+            if route.maintenance {
+                let message = config
+                    .maintenance_message
+                    .as_deref()
+                    .unwrap_or(FALLBACK_MESSAGE);
+                out.push_str(&format!("    respond \"{message}\" 503\n"));
+                out.push_str("}\n");
+                continue;
+            }
+            if route.is_static() {
+                let port = route.primary_port();
+                out.push_str(&format!("    root * /srv/static_{port}\n"));
+                out.push_str("    file_server\n");
+                out.push_str("}\n");
+                continue;
+            }
+
+            let container = config.find_container(&route.target);
+            let target = container
+                .map(|c| c.name.as_str())
+                .unwrap_or(route.target.as_str());
+            let tls_backend = container.map(|c| c.tls_backend).unwrap_or(false);
+            let tls_insecure = container.map(|c| c.tls_backend_insecure).unwrap_or(false);
+            let scheme = if tls_backend { "https" } else { "http" };
+            out.push_str(&format!(
+                "    reverse_proxy {scheme}://{}:{} {{\n",
+                target, route.internal_port
+            ));
+            if tls_backend {
+                if tls_insecure {
+                    out.push_str("        transport http {\n");
+                    out.push_str("            tls_insecure_skip_verify\n");
+                    out.push_str("        }\n");
+                } else {
+                    out.push_str("        transport http {\n");
+                    out.push_str("            tls\n");
+                    out.push_str("        }\n");
+                }
+            }
+            out.push_str(&format!(
+                "        header_down +X-Proxied-By \"{}\"\n",
+                config.proxy_name
+            ));
+            out.push_str("    }\n");
+            out.push_str("}\n");
+        }
+        out
+    }
+
+    /// Render the Dockerfile for the Caddy proxy image.
+    pub fn generate_dockerfile(config: &Config) -> String {
+        let mut out = format!("FROM {CADDY_BASE_IMAGE}\nCOPY Caddyfile /etc/caddy/Caddyfile\n");
+        for route in config.routes.iter().filter(|r| r.is_static() && !r.unbound) {
+            let port = route.primary_port();
+            out.push_str(&format!("COPY static_{port}/ /srv/static_{port}/\n"));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Container, ProxyBackend};
+
+    fn config_with_route() -> Config {
+        let mut config = Config {
+            proxy_backend: ProxyBackend::Caddy,
+            ..Config::default()
+        };
+        config.upsert_container(Container {
+            name: "app1".into(),
+            label: None,
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.set_route(8000, "app1", 8080);
+        config
+    }
+
+    #[test]
+    fn route_emits_a_reverse_proxy_site() {
+        let conf = CaddyfileGenerator::generate(&config_with_route());
+        assert!(conf.contains(":8000 {"));
+        assert!(conf.contains("reverse_proxy http://app1:8080"));
+        assert!(conf.contains("auto_https off"));
+    }
+
+    #[test]
+    fn multi_port_route_lists_every_address() {
+        let mut config = config_with_route();
+        config.routes[0].host_ports = vec![80, 443];
+        let conf = CaddyfileGenerator::generate(&config);
+        assert!(conf.contains(":80, :443 {"));
+    }
+
+    #[test]
+    fn host_routes_bind_the_name() {
+        let mut config = config_with_route();
+        config.routes[0].host = Some("app.example.test".into());
+        let conf = CaddyfileGenerator::generate(&config);
+        assert!(conf.contains("http://app.example.test:8000 {"));
+    }
+
+    #[test]
+    fn maintenance_routes_respond_503() {
+        let mut config = config_with_route();
+        config.routes[0].maintenance = true;
+        let conf = CaddyfileGenerator::generate(&config);
+        assert!(conf.contains("respond"));
+        assert!(conf.contains("503"));
+        assert!(!conf.contains("reverse_proxy"));
+    }
+
+    #[test]
+    fn static_routes_get_a_file_server() {
+        let mut config = config_with_route();
+        config.set_static_route(8008, std::path::PathBuf::from("/srv/site"));
+        let conf = CaddyfileGenerator::generate(&config);
+        assert!(conf.contains("root * /srv/static_8008"));
+        assert!(conf.contains("file_server"));
+    }
+
+    #[test]
+    fn tls_backends_flip_the_scheme() {
+        let mut config = config_with_route();
+        config.find_container_mut("app1").unwrap().tls_backend = true;
+        config
+            .find_container_mut("app1")
+            .unwrap()
+            .tls_backend_insecure = true;
+        let conf = CaddyfileGenerator::generate(&config);
+        assert!(conf.contains("reverse_proxy https://app1:8080"));
+        assert!(conf.contains("tls_insecure_skip_verify"));
+    }
+
+    #[test]
+    fn unbound_routes_are_skipped() {
+        let mut config = config_with_route();
+        config.routes[0].unbound = true;
+        let conf = CaddyfileGenerator::generate(&config);
+        assert!(!conf.contains(":8000 {"));
+    }
+
+    #[test]
+    fn dockerfile_builds_from_caddy_and_copies_static_dirs() {
+        let mut config = config_with_route();
+        config.set_static_route(8008, std::path::PathBuf::from("/srv/site"));
+        let dockerfile = CaddyfileGenerator::generate_dockerfile(&config);
+        assert!(dockerfile.starts_with("FROM caddy:latest\n"));
+        assert!(dockerfile.contains("COPY Caddyfile /etc/caddy/Caddyfile"));
+        assert!(dockerfile.contains("COPY static_8008/ /srv/static_8008/"));
+    }
+}
@@ -0,0 +1,2697 @@
+//! Configuration model and on-disk store.
+//!
+//! The config is a single JSON file describing the managed containers and the
+//! host-port routes the proxy serves. The [`Store`] owns the directory layout
+//! (config file, build directory) and handles load/save.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Default name for the proxy container and its image tag.
+pub const DEFAULT_PROXY_NAME: &str = "proxy-manager";
+/// Default Docker network joined by the proxy and its targets.
+pub const DEFAULT_NETWORK: &str = "proxy-net";
+/// Default base image for the generated proxy Dockerfile.
+pub const DEFAULT_BASE_IMAGE: &str = "nginx:alpine";
+
+fn default_proxy_name() -> String {
+    DEFAULT_PROXY_NAME.to_string()
+}
+
+/// Trim a string field in place; true when anything was removed.
+fn trim_in_place(value: &mut String) -> bool {
+    let trimmed = value.trim();
+    if trimmed.len() != value.len() {
+        *value = trimmed.to_string();
+        return true;
+    }
+    false
+}
+
+fn default_network() -> String {
+    DEFAULT_NETWORK.to_string()
+}
+
+fn default_base_image() -> String {
+    DEFAULT_BASE_IMAGE.to_string()
+}
+
+fn default_proxy_labels() -> std::collections::HashMap<String, String> {
+    let mut labels = std::collections::HashMap::new();
+    labels.insert(
+        crate::docker::MANAGED_BY_LABEL.to_string(),
+        crate::docker::MANAGED_BY_VALUE.to_string(),
+    );
+    labels
+}
+
+fn default_static_limit_mb() -> u64 {
+    50
+}
+
+fn default_log_tail_limit() -> u32 {
+    10_000
+}
+
+/// A backend container the proxy can route to.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Container {
+    /// Docker container name.
+    pub name: String,
+    /// Optional short label usable instead of the name in commands.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Further identifiers usable instead of the name, on top of `label`
+    /// (e.g. both "web" and "frontend" for one container). Older
+    /// single-label configs load unchanged: a missing field is just an
+    /// empty list.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// Port the application listens on inside the container.
+    pub port: u16,
+    /// Docker network the container is attached to; falls back to the
+    /// config-level default network when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    /// Directory inside the proxy container holding this container's static
+    /// assets; when set, nginx serves matching files directly via
+    /// `try_files` and only falls through to the backend for the rest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub static_root: Option<String>,
+    /// (from, to) pairs rewritten in response bodies via nginx `sub_filter`,
+    /// for legacy apps that embed absolute URLs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub response_rewrites: Vec<(String, String)>,
+    /// HTTP methods this container accepts; anything else is denied via
+    /// nginx `limit_except`. `None` allows everything.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_methods: Option<Vec<String>>,
+    /// The backend itself speaks HTTPS; proxied with an `https://` scheme
+    /// and SNI enabled.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub tls_backend: bool,
+    /// Skip certificate verification towards a TLS backend (self-signed
+    /// internal certs). Only valid together with `tls_backend`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub tls_backend_insecure: bool,
+    /// Delegate authentication for this container's routes to an external
+    /// auth service via nginx `auth_request`; must be a full `http://...`
+    /// address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_request_url: Option<String>,
+    /// Free-form note on why this container is here; never read by the
+    /// generator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Extra DNS names served for this container on its network; the
+    /// container is reattached with these aliases when the proxy starts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dns_aliases: Vec<String>,
+}
+
+impl Container {
+    /// True when `identifier` matches this container's name, label or
+    /// any of its aliases.
+    pub fn matches(&self, identifier: &str) -> bool {
+        self.name == identifier
+            || self.label.as_deref() == Some(identifier)
+            || self.aliases.iter().any(|alias| alias == identifier)
+    }
+}
+
+/// A host-port binding served by the proxy, forwarding to a target container.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Route {
+    /// Host ports the proxy listens on for this route (one `listen` line
+    /// each). Pre-multi-port configs with a scalar `host_port` still load;
+    /// saving rewrites them to this shape.
+    #[serde(alias = "host_port", deserialize_with = "one_or_many_ports")]
+    pub host_ports: Vec<u16>,
+    /// Name of the target container (must exist in `containers`). Empty
+    /// for static routes, which serve files instead of proxying.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub target: String,
+    /// Port the target listens on inside its container.
+    pub internal_port: u16,
+    /// Free-form tags for bulk operations; matching is exact and
+    /// case-sensitive.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// When true the route is kept in the config but its host port is not
+    /// published and no server block is generated (`stop <port> --keep`).
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub unbound: bool,
+    /// Optional canary target receiving a percentage of this route's
+    /// traffic via nginx `split_clients`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canary: Option<Canary>,
+    /// Local directory served directly by the proxy instead of a backend;
+    /// copied into the build context under `static_<port>` at build time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub static_dir: Option<PathBuf>,
+    /// When true the route answers every request with the maintenance page
+    /// instead of proxying; the target stays configured for flipping back.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub maintenance: bool,
+    /// Optional hostname for the route, emitted as nginx `server_name` and
+    /// picked up by `hosts sync` for dev-TLD resolution.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// Terminate TLS on this route. The listen lines gain `ssl` and the
+    /// server block expects `/etc/nginx/certs/<port>.crt`/`.key` to be
+    /// provided (mounted or baked into a derived image).
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub tls: bool,
+    /// Enable HTTP/2 on a TLS route. Defaults to on whenever `tls` is set;
+    /// ignored without TLS since h2c is not worth the confusion locally.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http2: Option<bool>,
+    /// Basic-auth protection for this route; only the apr1 hash is stored,
+    /// never the plaintext password.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub basic_auth: Option<BasicAuth>,
+    /// Cap on simultaneous connections per client address, enforced with
+    /// nginx `limit_conn` in a per-route zone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_conn: Option<u32>,
+    /// Load-balancing method for this route's backend; anything beyond
+    /// the round-robin default gets an upstream group to hang the
+    /// directive on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lb_method: Option<LbMethod>,
+    /// Scheme used to reach the backend; `https` for backends that only
+    /// speak TLS internally.
+    #[serde(default, skip_serializing_if = "is_http")]
+    pub backend_scheme: BackendScheme,
+    /// With an https backend, verify its certificate chain; disabling
+    /// this is warned about at every start.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub verify_backend_tls: bool,
+    /// CA bundle used to verify an https backend; copied into the image
+    /// as `/etc/nginx/ca/<port>.crt`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend_ca_file: Option<PathBuf>,
+    /// CA bundle clients must present certificates signed by (mTLS);
+    /// copied into the image as `/etc/nginx/ca/client_<port>.crt`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtls_ca_cert: Option<PathBuf>,
+    /// How strictly client certificates are demanded on an mTLS route;
+    /// defaults to `on` when a CA is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtls_verify: Option<MtlsVerify>,
+    /// Ports the target exposed when this route was last switched; used to
+    /// spot listen-port drift after image upgrades.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub observed_ports: Vec<u16>,
+    /// Free-form note on why this route exists; never read by the
+    /// generator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// An htpasswd credential attached to a route.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BasicAuth {
+    pub username: String,
+    /// `$apr1$...` hash as produced by [`crate::auth::hash_password`].
+    pub password_hash: String,
+}
+
+/// A weighted canary target for a route.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Canary {
+    /// Name of the canary container (must exist in `containers`).
+    pub target: String,
+    /// Port the canary listens on inside its container.
+    pub internal_port: u16,
+    /// Percentage of traffic sent to the canary (1-99); the remainder goes
+    /// to the route's main target.
+    pub percent: u8,
+}
+
+/// Load-balancing method for a route's upstream group. Round-robin is
+/// nginx's default, so it (like an unset method) emits no directive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LbMethod {
+    RoundRobin,
+    LeastConn,
+    IpHash,
+    Random,
+}
+
+/// Scheme the proxy uses to reach a route's backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendScheme {
+    #[default]
+    Http,
+    Https,
+}
+
+/// nginx `ssl_verify_client` argument for an mTLS route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MtlsVerify {
+    On,
+    Optional,
+    Off,
+}
+
+impl MtlsVerify {
+    /// The literal `ssl_verify_client` argument.
+    pub fn directive(self) -> &'static str {
+        match self {
+            MtlsVerify::On => "on",
+            MtlsVerify::Optional => "optional",
+            MtlsVerify::Off => "off",
+        }
+    }
+}
+
+fn is_http(scheme: &BackendScheme) -> bool {
+    *scheme == BackendScheme::Http
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// Serialize a map with sorted keys so repeated saves are byte-identical
+/// (std's HashMap iteration order changes between processes).
+fn sorted_map<S, V>(
+    map: &std::collections::HashMap<String, V>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    V: Serialize,
+{
+    map.iter()
+        .collect::<std::collections::BTreeMap<_, _>>()
+        .serialize(serializer)
+}
+
+/// Top-level scalar fields exposed through `config set`/`config get`, by
+/// their JSON names. Lists and nested structures stay out: routes and
+/// containers have their own commands.
+pub const SETTABLE_KEYS: &[&str] = &[
+    "proxy_name",
+    "network",
+    "base_image",
+    "proxy_backend",
+    "static_size_limit_mb",
+    "log_tail_limit",
+    "maintenance_message",
+    "resolver",
+    "resolver_valid_secs",
+    "management_port",
+    "build_dir",
+    "qualify_network_names",
+    "interpolate",
+    "connect_all_networks",
+    "connect_backends",
+    "sort_containers",
+    "compact_routes",
+    "read_only",
+];
+
+/// Check a resolver spec: "off", or one or more space-separated IP or
+/// IP:port entries.
+fn validate_resolver(resolver: &str) -> Result<()> {
+    if resolver == "off" {
+        return Ok(());
+    }
+    if resolver.trim().is_empty() {
+        bail!("resolver must be \"off\" or one or more IP[:port] entries");
+    }
+    for entry in resolver.split_whitespace() {
+        let valid = entry.parse::<std::net::IpAddr>().is_ok()
+            || entry.parse::<std::net::SocketAddr>().is_ok();
+        if !valid {
+            bail!("invalid resolver entry '{entry}'; expected IP or IP:port");
+        }
+    }
+    Ok(())
+}
+
+impl Route {
+    /// True when the route carries `tag` (exact, case-sensitive).
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// True when the route listens on `port`.
+    pub fn listens_on(&self, port: u16) -> bool {
+        self.host_ports.contains(&port)
+    }
+
+    /// First host port, used for display and for naming generated nginx
+    /// variables. Validation guarantees at least one port.
+    pub fn primary_port(&self) -> u16 {
+        self.host_ports.first().copied().unwrap_or(0)
+    }
+
+    /// True when the route serves a static directory instead of proxying.
+    pub fn is_static(&self) -> bool {
+        self.static_dir.is_some()
+    }
+
+    /// True when the generated server block should speak HTTP/2. Only TLS
+    /// routes qualify; on those it defaults to on unless explicitly
+    /// disabled.
+    pub fn http2_enabled(&self) -> bool {
+        self.tls && self.http2.unwrap_or(true)
+    }
+
+    /// Host ports joined for display ("80, 443").
+    pub fn ports_display(&self) -> String {
+        self.host_ports
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Accept either the legacy scalar `host_port` or a `host_ports` array.
+fn one_or_many_ports<'de, D>(deserializer: D) -> std::result::Result<Vec<u16>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(u16),
+        Many(Vec<u16>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(port) => vec![port],
+        OneOrMany::Many(ports) => ports,
+    })
+}
+
+/// Which proxy server the build targets; nginx remains the default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyBackend {
+    #[default]
+    Nginx,
+    Caddy,
+}
+
+fn is_nginx(backend: &ProxyBackend) -> bool {
+    *backend == ProxyBackend::Nginx
+}
+
+/// Top-level configuration as stored on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    /// Name of the proxy container (also used as the image tag).
+    #[serde(default = "default_proxy_name")]
+    pub proxy_name: String,
+    /// Default Docker network for the proxy and containers without an
+    /// explicit one.
+    #[serde(default = "default_network")]
+    pub network: String,
+    /// Base image for the generated Dockerfile.
+    #[serde(default = "default_base_image")]
+    pub base_image: String,
+    /// Proxy server to generate for; `caddy` emits a Caddyfile and builds
+    /// from `caddy:latest` instead of the nginx base image.
+    #[serde(default, skip_serializing_if = "is_nginx")]
+    pub proxy_backend: ProxyBackend,
+    /// Environment variables set on the proxy container (for `envsubst`
+    /// templating and similar); empty by default.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub proxy_env: Vec<(String, String)>,
+    /// Upper bound, in megabytes, on the total size of a static route's
+    /// directory; keeps build contexts from ballooning.
+    #[serde(default = "default_static_limit_mb")]
+    pub static_size_limit_mb: u64,
+    /// Upper bound on the `--tail` line count for log retrieval; larger
+    /// requests are clamped so a chatty proxy cannot balloon memory.
+    #[serde(default = "default_log_tail_limit")]
+    pub log_tail_limit: u32,
+    /// Body served by routes in maintenance mode; falls back to the
+    /// standard unavailable message when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance_message: Option<String>,
+    /// DNS resolver(s) for the generated config (space-separated IP or
+    /// IP:port); defaults to Docker's embedded 127.0.0.11. The special
+    /// value "off" omits the directive and proxies to fixed addresses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolver: Option<String>,
+    /// Seconds nginx caches resolver answers (the `valid=` parameter);
+    /// defaults to 30.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolver_valid_secs: Option<u32>,
+    /// Emit network-qualified backend names (`name.network`) for
+    /// containers on secondary networks; Docker's embedded DNS answers
+    /// the qualified form, which keeps lookups unambiguous when the
+    /// proxy spans several networks.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub qualify_network_names: bool,
+    /// Host port serving nginx's `stub_status` page at `/nginx_status`;
+    /// when set, startup waits for it to answer instead of only checking
+    /// the container status.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub management_port: Option<u16>,
+    /// Directory for generated build artifacts (nginx.conf, Dockerfile,
+    /// staged static trees); overrides the default `build/` folder under
+    /// the config directory. The `--build-dir` flag wins over this field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_dir: Option<PathBuf>,
+    /// When true, `${VAR}` / `${VAR:-default}` references in string fields
+    /// are resolved from the process environment before the config is used.
+    /// The file on disk always keeps the unresolved form.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub interpolate: bool,
+    /// Labels set on the proxy container so other tooling can discover
+    /// it; defaults to a single `managed-by` label.
+    #[serde(default = "default_proxy_labels", serialize_with = "sorted_map")]
+    pub proxy_labels: std::collections::HashMap<String, String>,
+    /// Networks that must already exist; the proxy joins them but never
+    /// creates them, so external DNS aliases and addressing are preserved.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub external_networks: Vec<String>,
+    /// Connect the proxy to every user-defined bridge network at start,
+    /// not just the ones containers reference. Blunt: the proxy can then
+    /// reach (and be reached from) every container on those networks, so
+    /// keep it off unless that is the point.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub connect_all_networks: bool,
+    /// Connect route targets that share no network with the proxy to the
+    /// proxy's default network at start, instead of requiring a manual
+    /// `docker network connect`. Connections made this way are recorded so
+    /// `stop --disconnect-on-stop` removes exactly them.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub connect_backends: bool,
+    /// Static proxy IPs per network, for firewall rules that reference
+    /// the container address; each pin must fall inside that network's
+    /// subnet.
+    #[serde(
+        default,
+        skip_serializing_if = "std::collections::HashMap::is_empty",
+        serialize_with = "sorted_map"
+    )]
+    pub proxy_static_ips: std::collections::HashMap<String, std::net::IpAddr>,
+    /// Sort containers by name when saving, for stable diffs of the
+    /// config file under version control; turn off to preserve insertion
+    /// order.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub sort_containers: bool,
+    /// Share one `upstream` block per unique backend instead of repeating
+    /// the address in every server block. Backend addresses are then
+    /// resolved at (re)load time rather than per request.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub compact_routes: bool,
+    /// Refuse every mutating operation; inspection commands keep working.
+    /// Intended for shared hosts (also via `--read-only` or
+    /// `PROXY_MANAGER_READ_ONLY=1`).
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub read_only: bool,
+    /// Known backend containers.
+    #[serde(default)]
+    pub containers: Vec<Container>,
+    /// Active routes.
+    #[serde(default)]
+    pub routes: Vec<Route>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            proxy_name: default_proxy_name(),
+            network: default_network(),
+            base_image: default_base_image(),
+            proxy_backend: ProxyBackend::Nginx,
+            proxy_env: Vec::new(),
+            static_size_limit_mb: default_static_limit_mb(),
+            log_tail_limit: default_log_tail_limit(),
+            maintenance_message: None,
+            resolver: None,
+            resolver_valid_secs: None,
+            qualify_network_names: false,
+            management_port: None,
+            build_dir: None,
+            proxy_labels: default_proxy_labels(),
+            sort_containers: true,
+            compact_routes: false,
+            read_only: false,
+            interpolate: false,
+            external_networks: Vec::new(),
+            connect_all_networks: false,
+            connect_backends: false,
+            proxy_static_ips: std::collections::HashMap::new(),
+            containers: Vec::new(),
+            routes: Vec::new(),
+        }
+    }
+}
+
+/// Headline counts over a [`Config`], for status displays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigStats {
+    pub container_count: usize,
+    pub route_count: usize,
+    /// Distinct networks referenced: the default, per-container overrides
+    /// and external networks.
+    pub network_count: usize,
+    /// Containers targeted by at least one route.
+    pub routed_container_count: usize,
+    pub unrouted_container_count: usize,
+}
+
+impl ConfigStats {
+    /// One-line rendering, e.g. "3 containers (2 routed), 1 network, 2 routes".
+    pub fn summary(&self) -> String {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        format!(
+            "{} container{} ({} routed), {} network{}, {} route{}",
+            self.container_count,
+            plural(self.container_count),
+            self.routed_container_count,
+            self.network_count,
+            plural(self.network_count),
+            self.route_count,
+            plural(self.route_count),
+        )
+    }
+}
+
+impl Config {
+    /// Count containers, routes and networks for status display.
+    pub fn count_stats(&self) -> ConfigStats {
+        let mut networks = vec![self.network.clone()];
+        networks.extend(self.containers.iter().filter_map(|c| c.network.clone()));
+        networks.extend(self.external_networks.iter().cloned());
+        networks.sort();
+        networks.dedup();
+        let routed_container_count = self
+            .containers
+            .iter()
+            .filter(|c| {
+                self.routes
+                    .iter()
+                    .any(|r| !r.is_static() && c.matches(&r.target))
+            })
+            .count();
+        ConfigStats {
+            container_count: self.containers.len(),
+            route_count: self.routes.len(),
+            network_count: networks.len(),
+            routed_container_count,
+            unrouted_container_count: self.containers.len() - routed_container_count,
+        }
+    }
+
+    /// Find a container by name or label.
+    pub fn find_container(&self, identifier: &str) -> Option<&Container> {
+        self.containers.iter().find(|c| c.matches(identifier))
+    }
+
+    /// Mutable variant of [`Config::find_container`].
+    pub fn find_container_mut(&mut self, identifier: &str) -> Option<&mut Container> {
+        self.containers.iter_mut().find(|c| c.matches(identifier))
+    }
+
+    /// Find the route listening on `host_port`.
+    pub fn find_route(&self, host_port: u16) -> Option<&Route> {
+        self.routes.iter().find(|r| r.listens_on(host_port))
+    }
+
+    /// Insert or update a container entry, keyed by name.
+    pub fn upsert_container(&mut self, container: Container) {
+        match self
+            .containers
+            .iter_mut()
+            .find(|c| c.name == container.name)
+        {
+            Some(existing) => *existing = container,
+            None => self.containers.push(container),
+        }
+    }
+
+    /// Point the route on `host_port` at `target:internal_port`, replacing
+    /// any existing route listening there (its tags and full listen-port set
+    /// are preserved). Routes stay sorted by primary port.
+    pub fn set_route(&mut self, host_port: u16, target: &str, internal_port: u16) {
+        let (host_ports, tags) = match self.find_route(host_port) {
+            Some(route) => (route.host_ports.clone(), route.tags.clone()),
+            None => (vec![host_port], Vec::new()),
+        };
+        self.routes.retain(|r| !r.listens_on(host_port));
+        self.routes.push(Route {
+            host_ports,
+            target: target.to_string(),
+            internal_port,
+            tags,
+            unbound: false,
+            canary: None,
+            static_dir: None,
+            maintenance: false,
+            host: None,
+            tls: false,
+            http2: None,
+            basic_auth: None,
+            max_conn: None,
+            lb_method: None,
+            backend_scheme: BackendScheme::Http,
+            verify_backend_tls: true,
+            backend_ca_file: None,
+            mtls_ca_cert: None,
+            mtls_verify: None,
+            observed_ports: Vec::new(),
+            description: None,
+        });
+        self.routes.sort_by_key(|r| r.primary_port());
+    }
+
+    /// Insert or replace the route on `host_port` with a static route
+    /// serving `dir`, preserving the old route's tags and port set like
+    /// [`Config::set_route`].
+    pub fn set_static_route(&mut self, host_port: u16, dir: PathBuf) {
+        let (host_ports, tags) = match self.find_route(host_port) {
+            Some(route) => (route.host_ports.clone(), route.tags.clone()),
+            None => (vec![host_port], Vec::new()),
+        };
+        self.routes.retain(|r| !r.listens_on(host_port));
+        self.routes.push(Route {
+            host_ports,
+            target: String::new(),
+            internal_port: 0,
+            tags,
+            unbound: false,
+            canary: None,
+            static_dir: Some(dir),
+            maintenance: false,
+            host: None,
+            tls: false,
+            http2: None,
+            basic_auth: None,
+            max_conn: None,
+            lb_method: None,
+            backend_scheme: BackendScheme::Http,
+            verify_backend_tls: true,
+            backend_ca_file: None,
+            mtls_ca_cert: None,
+            mtls_verify: None,
+            observed_ports: Vec::new(),
+            description: None,
+        });
+        self.routes.sort_by_key(|r| r.primary_port());
+    }
+
+    /// Host ports of routes targeting `name`, sorted ascending.
+    pub fn routes_targeting(&self, name: &str) -> Vec<u16> {
+        let mut ports: Vec<u16> = self
+            .routes
+            .iter()
+            .filter(|r| r.target == name)
+            .flat_map(|r| r.host_ports.iter().copied())
+            .collect();
+        ports.sort_unstable();
+        ports
+    }
+
+    /// Routes carrying `tag`.
+    pub fn routes_with_tag(&self, tag: &str) -> Vec<&Route> {
+        self.routes.iter().filter(|r| r.has_tag(tag)).collect()
+    }
+
+    /// Remove `host_port` from the route listening on it; a route left with
+    /// no ports is dropped entirely. Returns true when a route listened on
+    /// the port.
+    pub fn remove_route(&mut self, host_port: u16) -> bool {
+        let Some(route) = self.routes.iter_mut().find(|r| r.listens_on(host_port)) else {
+            return false;
+        };
+        route.host_ports.retain(|p| *p != host_port);
+        self.routes.retain(|r| !r.host_ports.is_empty());
+        true
+    }
+
+    /// All host ports the proxy must publish (unbound routes excluded),
+    /// sorted ascending.
+    pub fn host_ports(&self) -> Vec<u16> {
+        let mut ports: Vec<u16> = self
+            .routes
+            .iter()
+            .filter(|r| !r.unbound)
+            .flat_map(|r| r.host_ports.iter().copied())
+            .collect();
+        ports.sort_unstable();
+        ports.dedup();
+        ports
+    }
+
+    /// All networks the proxy must join: the default network, every
+    /// container-specific one and the external networks, deduplicated.
+    pub fn all_networks(&self) -> Vec<String> {
+        let mut networks = vec![self.network.clone()];
+        for container in &self.containers {
+            if let Some(net) = &container.network {
+                if !networks.contains(net) {
+                    networks.push(net.clone());
+                }
+            }
+        }
+        for net in &self.external_networks {
+            if !networks.contains(net) {
+                networks.push(net.clone());
+            }
+        }
+        networks
+    }
+
+    /// Hostname the proxy uses to reach `target`: the container name,
+    /// network-qualified (`name.network`) when `qualify_network_names` is
+    /// set and the container sits off the default network.
+    pub fn backend_host(&self, target: &str) -> String {
+        let Some(container) = self.find_container(target) else {
+            return target.to_string();
+        };
+        match &container.network {
+            Some(network) if self.qualify_network_names && *network != self.network => {
+                format!("{}.{network}", container.name)
+            }
+            _ => container.name.clone(),
+        }
+    }
+
+    /// Clean up common inconsistencies in place: strips stray whitespace,
+    /// drops empty labels, de-duplicates containers (the last definition
+    /// wins), removes routes whose target no longer exists and sorts both
+    /// lists. Returns one line per change made.
+    pub fn normalize(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut trimmed = 0;
+        trimmed += trim_in_place(&mut self.proxy_name) as usize;
+        trimmed += trim_in_place(&mut self.network) as usize;
+        trimmed += trim_in_place(&mut self.base_image) as usize;
+        for container in &mut self.containers {
+            trimmed += trim_in_place(&mut container.name) as usize;
+            if let Some(network) = &mut container.network {
+                trimmed += trim_in_place(network) as usize;
+            }
+            if let Some(label) = &mut container.label {
+                trimmed += trim_in_place(label) as usize;
+                if label.is_empty() {
+                    lines.push(format!("Dropped empty label on '{}'", container.name));
+                    container.label = None;
+                }
+            }
+        }
+        for route in &mut self.routes {
+            trimmed += trim_in_place(&mut route.target) as usize;
+            for tag in &mut route.tags {
+                trimmed += trim_in_place(tag) as usize;
+            }
+            if let Some(host) = &mut route.host {
+                trimmed += trim_in_place(host) as usize;
+            }
+        }
+        if trimmed > 0 {
+            lines.push(format!("Trimmed whitespace in {trimmed} field(s)"));
+        }
+        // Duplicate containers: keep the last definition.
+        let mut seen: Vec<String> = Vec::new();
+        let mut kept: Vec<Container> = Vec::new();
+        for container in self.containers.drain(..).rev() {
+            if seen.contains(&container.name) {
+                lines.push(format!(
+                    "Removed duplicate container '{}' (last definition wins)",
+                    container.name
+                ));
+            } else {
+                seen.push(container.name.clone());
+                kept.push(container);
+            }
+        }
+        kept.reverse();
+        self.containers = kept;
+        // Routes pointing at containers that are gone.
+        let names: Vec<String> = self.containers.iter().map(|c| c.name.clone()).collect();
+        self.routes.retain(|route| {
+            if route.is_static() || names.contains(&route.target) {
+                return true;
+            }
+            lines.push(format!(
+                "Removed route {} -> {}: unknown container",
+                route.primary_port(),
+                route.target
+            ));
+            false
+        });
+        // Stable ordering for diffs and display.
+        if self.sort_containers {
+            let order: Vec<String> = self.containers.iter().map(|c| c.name.clone()).collect();
+            self.containers.sort_by(|a, b| a.name.cmp(&b.name));
+            if self.containers.iter().map(|c| &c.name).ne(order.iter()) {
+                lines.push("Sorted containers by name".to_string());
+            }
+        }
+        let order: Vec<u16> = self.routes.iter().map(|r| r.primary_port()).collect();
+        self.routes.sort_by_key(|r| r.primary_port());
+        if self.routes.iter().map(|r| r.primary_port()).ne(order) {
+            lines.push("Sorted routes by host port".to_string());
+        }
+        lines
+    }
+
+    /// Read a top-level scalar field by its JSON name, for `config get`;
+    /// unset optional fields render as an empty string.
+    pub fn get_value(&self, key: &str) -> Result<String> {
+        Ok(match key {
+            "proxy_name" => self.proxy_name.clone(),
+            "network" => self.network.clone(),
+            "base_image" => self.base_image.clone(),
+            "proxy_backend" => match self.proxy_backend {
+                ProxyBackend::Nginx => "nginx".to_string(),
+                ProxyBackend::Caddy => "caddy".to_string(),
+            },
+            "static_size_limit_mb" => self.static_size_limit_mb.to_string(),
+            "log_tail_limit" => self.log_tail_limit.to_string(),
+            "maintenance_message" => self.maintenance_message.clone().unwrap_or_default(),
+            "resolver" => self.resolver.clone().unwrap_or_default(),
+            "resolver_valid_secs" => self
+                .resolver_valid_secs
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+            "management_port" => self
+                .management_port
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+            "build_dir" => self
+                .build_dir
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+            "qualify_network_names" => self.qualify_network_names.to_string(),
+            "interpolate" => self.interpolate.to_string(),
+            "connect_all_networks" => self.connect_all_networks.to_string(),
+            "connect_backends" => self.connect_backends.to_string(),
+            "sort_containers" => self.sort_containers.to_string(),
+            "compact_routes" => self.compact_routes.to_string(),
+            "read_only" => self.read_only.to_string(),
+            _ => bail!(
+                "unknown key '{key}'; settable keys: {}",
+                SETTABLE_KEYS.join(", ")
+            ),
+        })
+    }
+
+    /// Set a top-level scalar field from its string form, for `config
+    /// set`. Values are type-checked per field; optional fields accept an
+    /// empty string to unset.
+    pub fn set_value(&mut self, key: &str, value: &str) -> Result<()> {
+        fn parse<T: std::str::FromStr>(key: &str, value: &str, kind: &str) -> Result<T> {
+            value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("'{value}' is not a valid {kind} for {key}"))
+        }
+        fn optional<T: std::str::FromStr>(key: &str, value: &str, kind: &str) -> Result<Option<T>> {
+            if value.is_empty() {
+                Ok(None)
+            } else {
+                parse(key, value, kind).map(Some)
+            }
+        }
+        match key {
+            "proxy_name" | "network" | "base_image" if value.trim().is_empty() => {
+                bail!("{key} cannot be empty")
+            }
+            "proxy_name" => self.proxy_name = value.to_string(),
+            "network" => self.network = value.to_string(),
+            "base_image" => self.base_image = value.to_string(),
+            "proxy_backend" => {
+                self.proxy_backend = match value {
+                    "nginx" => ProxyBackend::Nginx,
+                    "caddy" => ProxyBackend::Caddy,
+                    _ => bail!("proxy_backend must be \"nginx\" or \"caddy\", got '{value}'"),
+                }
+            }
+            "static_size_limit_mb" => {
+                self.static_size_limit_mb = parse(key, value, "size in megabytes")?
+            }
+            "log_tail_limit" => self.log_tail_limit = parse(key, value, "number of lines")?,
+            "maintenance_message" => {
+                self.maintenance_message = (!value.is_empty()).then(|| value.to_string())
+            }
+            "resolver" => {
+                if !value.is_empty() {
+                    validate_resolver(value)?;
+                }
+                self.resolver = (!value.is_empty()).then(|| value.to_string());
+            }
+            "resolver_valid_secs" => {
+                self.resolver_valid_secs = optional(key, value, "number of seconds")?
+            }
+            "management_port" => self.management_port = optional(key, value, "port number")?,
+            "build_dir" => self.build_dir = (!value.is_empty()).then(|| PathBuf::from(value)),
+            "qualify_network_names" => self.qualify_network_names = parse(key, value, "boolean")?,
+            "interpolate" => self.interpolate = parse(key, value, "boolean")?,
+            "connect_all_networks" => self.connect_all_networks = parse(key, value, "boolean")?,
+            "connect_backends" => self.connect_backends = parse(key, value, "boolean")?,
+            "sort_containers" => self.sort_containers = parse(key, value, "boolean")?,
+            "compact_routes" => self.compact_routes = parse(key, value, "boolean")?,
+            "read_only" => self.read_only = parse(key, value, "boolean")?,
+            _ => bail!(
+                "unknown key '{key}'; settable keys: {}",
+                SETTABLE_KEYS.join(", ")
+            ),
+        }
+        Ok(())
+    }
+
+    /// Read and validate a config from a JSON stream, for piping via
+    /// `config --stdin`. Errors surface before anything is saved, so a
+    /// bad pipe never clobbers the existing file.
+    pub fn from_reader(reader: impl std::io::Read) -> Result<Config> {
+        let config: Config =
+            serde_json::from_reader(reader).context("failed to parse config JSON")?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Write the canonical JSON form to a stream, for piping via
+    /// `config --json`.
+    pub fn to_writer(&self, mut writer: impl std::io::Write) -> Result<()> {
+        writer
+            .write_all(self.to_canonical_json()?.as_bytes())
+            .context("failed to write config")
+    }
+
+    /// The canonical serialized form [`Store::save`] writes: routes
+    /// sorted by host port, containers sorted by name (unless
+    /// `sort_containers` is off), two-space indentation and a trailing
+    /// newline. Struct fields keep their declaration order and map
+    /// fields are emitted with sorted keys, so repeated save cycles are
+    /// byte-identical and config diffs stay quiet.
+    pub fn to_canonical_json(&self) -> Result<String> {
+        let mut config = self.clone();
+        config.routes.sort_by_key(Route::primary_port);
+        if config.sort_containers {
+            config.containers.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+        Ok(format!("{}\n", serde_json::to_string_pretty(&config)?))
+    }
+
+    /// Render the config as `.env` / direnv-compatible lines
+    /// (`PROXY_MANAGER_CONTAINER_0_NAME=app` and so on), covering the
+    /// identity scalars plus every container and route binding. The
+    /// inverse is [`Config::from_env_vars`].
+    pub fn to_env_file(&self) -> String {
+        let mut out = String::new();
+        out.push_str(&format!("PROXY_MANAGER_PROXY_NAME={}\n", self.proxy_name));
+        out.push_str(&format!("PROXY_MANAGER_NETWORK={}\n", self.network));
+        out.push_str(&format!("PROXY_MANAGER_BASE_IMAGE={}\n", self.base_image));
+        for (i, container) in self.containers.iter().enumerate() {
+            out.push_str(&format!(
+                "PROXY_MANAGER_CONTAINER_{i}_NAME={}\n",
+                container.name
+            ));
+            out.push_str(&format!(
+                "PROXY_MANAGER_CONTAINER_{i}_PORT={}\n",
+                container.port
+            ));
+            if let Some(network) = &container.network {
+                out.push_str(&format!("PROXY_MANAGER_CONTAINER_{i}_NETWORK={network}\n"));
+            }
+        }
+        for (i, route) in self.routes.iter().enumerate() {
+            out.push_str(&format!(
+                "PROXY_MANAGER_ROUTE_{i}_HOST_PORT={}\n",
+                route.primary_port()
+            ));
+            out.push_str(&format!(
+                "PROXY_MANAGER_ROUTE_{i}_TARGET={}\n",
+                route.target
+            ));
+            out.push_str(&format!(
+                "PROXY_MANAGER_ROUTE_{i}_INTERNAL_PORT={}\n",
+                route.internal_port
+            ));
+        }
+        out
+    }
+
+    /// Rebuild a config from `PROXY_MANAGER_*` variables in the process
+    /// environment, or `None` when none are set. Only the fields
+    /// [`Config::to_env_file`] emits are recovered; everything else keeps
+    /// its default.
+    pub fn from_env_vars() -> Option<Config> {
+        Self::from_env_pairs(&std::env::vars().collect::<Vec<_>>())
+    }
+
+    /// [`Config::from_env_vars`] over an explicit variable list.
+    pub fn from_env_pairs(pairs: &[(String, String)]) -> Option<Config> {
+        let get = |key: &str| {
+            pairs
+                .iter()
+                .find(|(k, _)| k == &format!("PROXY_MANAGER_{key}"))
+                .map(|(_, v)| v.clone())
+        };
+        if !pairs.iter().any(|(k, _)| k.starts_with("PROXY_MANAGER_")) {
+            return None;
+        }
+        let mut config = Config::default();
+        if let Some(name) = get("PROXY_NAME") {
+            config.proxy_name = name;
+        }
+        if let Some(network) = get("NETWORK") {
+            config.network = network;
+        }
+        if let Some(image) = get("BASE_IMAGE") {
+            config.base_image = image;
+        }
+        for i in 0.. {
+            let Some(name) = get(&format!("CONTAINER_{i}_NAME")) else {
+                break;
+            };
+            config.upsert_container(Container {
+                name,
+                label: None,
+                port: get(&format!("CONTAINER_{i}_PORT"))?.parse().ok()?,
+                network: get(&format!("CONTAINER_{i}_NETWORK")),
+                static_root: None,
+                response_rewrites: Vec::new(),
+                allowed_methods: None,
+                tls_backend: false,
+                tls_backend_insecure: false,
+                auth_request_url: None,
+                description: None,
+                dns_aliases: Vec::new(),
+                aliases: Vec::new(),
+            });
+        }
+        for i in 0.. {
+            let Some(host_port) = get(&format!("ROUTE_{i}_HOST_PORT")) else {
+                break;
+            };
+            config.set_route(
+                host_port.parse().ok()?,
+                &get(&format!("ROUTE_{i}_TARGET"))?,
+                get(&format!("ROUTE_{i}_INTERNAL_PORT"))?.parse().ok()?,
+            );
+        }
+        Some(config)
+    }
+
+    /// Merge environment pairs into `proxy_env`, replacing existing keys.
+    pub fn merge_proxy_env(&mut self, pairs: Vec<(String, String)>) {
+        for (key, value) in pairs {
+            match self.proxy_env.iter_mut().find(|(k, _)| *k == key) {
+                Some(existing) => existing.1 = value,
+                None => self.proxy_env.push((key, value)),
+            }
+        }
+    }
+
+    /// Sanity-check cross references before generating or starting anything.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(resolver) = &self.resolver {
+            validate_resolver(resolver)?;
+        }
+        for container in &self.containers {
+            if let Some(url) = &container.auth_request_url {
+                if !url.starts_with("http://") {
+                    bail!(
+                        "container '{}' auth_request_url '{url}' must be a full http:// address",
+                        container.name
+                    );
+                }
+            }
+            if container.tls_backend_insecure && !container.tls_backend {
+                bail!(
+                    "container '{}' sets tls_backend_insecure without tls_backend",
+                    container.name
+                );
+            }
+            for alias in &container.aliases {
+                if self.containers.iter().filter(|c| c.matches(alias)).count() > 1 {
+                    bail!(
+                        "alias '{alias}' on container '{}' is ambiguous: it also matches \
+                         another container",
+                        container.name
+                    );
+                }
+            }
+        }
+        let mut seen_ports = Vec::new();
+        for route in &self.routes {
+            if route.host_ports.is_empty() {
+                bail!("route targeting '{}' has no host ports", route.target);
+            }
+            for port in &route.host_ports {
+                if seen_ports.contains(port) {
+                    bail!("host port {port} appears in more than one route");
+                }
+                seen_ports.push(*port);
+            }
+            if route.is_static() {
+                if route.canary.is_some() {
+                    bail!(
+                        "route {} is static and cannot have a canary",
+                        route.primary_port()
+                    );
+                }
+                continue;
+            }
+            let Some(container) = self.find_container(&route.target) else {
+                bail!(
+                    "route {} targets unknown container '{}'",
+                    route.primary_port(),
+                    route.target
+                );
+            };
+            // Backend names, qualified or not, only resolve on networks
+            // the proxy actually joins.
+            if let Some(network) = &container.network {
+                if !self.all_networks().contains(network) {
+                    bail!(
+                        "route {} target '{}' is on network '{network}' which the proxy \
+                         does not join",
+                        route.primary_port(),
+                        container.name
+                    );
+                }
+            }
+            if let Some(ca) = &route.backend_ca_file {
+                if route.backend_scheme != BackendScheme::Https {
+                    bail!(
+                        "route {} sets backend_ca_file without backend_scheme https",
+                        route.primary_port()
+                    );
+                }
+                if !ca.is_file() {
+                    bail!(
+                        "route {} backend_ca_file {} does not exist",
+                        route.primary_port(),
+                        ca.display()
+                    );
+                }
+            }
+            if let Some(ca) = &route.mtls_ca_cert {
+                if !route.tls {
+                    bail!(
+                        "route {} sets mtls_ca_cert without tls",
+                        route.primary_port()
+                    );
+                }
+                if !ca.is_file() {
+                    bail!(
+                        "route {} mtls_ca_cert {} does not exist",
+                        route.primary_port(),
+                        ca.display()
+                    );
+                }
+            } else if route.mtls_verify.is_some() {
+                bail!(
+                    "route {} sets mtls_verify without mtls_ca_cert",
+                    route.primary_port()
+                );
+            }
+            if self.compact_routes && route.lb_method == Some(LbMethod::IpHash) {
+                bail!(
+                    "route {} uses ip_hash, which cannot share the named upstream groups \
+                     compact_routes creates",
+                    route.primary_port()
+                );
+            }
+            if let Some(canary) = &route.canary {
+                if self.find_container(&canary.target).is_none() {
+                    bail!(
+                        "route {} canary targets unknown container '{}'",
+                        route.primary_port(),
+                        canary.target
+                    );
+                }
+                if canary.percent == 0 || canary.percent > 99 {
+                    bail!(
+                        "route {} canary percentage must be between 1 and 99, got {}",
+                        route.primary_port(),
+                        canary.percent
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Graphviz DOT rendering of the routing topology: one node per
+    /// container (grouped into clusters by network), one node per host
+    /// port, and an edge from each port to its target. Canary targets get a
+    /// dashed edge labelled with their traffic share.
+    pub fn to_dot_graph(&self) -> String {
+        let mut out = String::from("digraph routes {\n    rankdir=LR;\n");
+        for network in self.all_networks() {
+            let members: Vec<&Container> = self
+                .containers
+                .iter()
+                .filter(|c| c.network.as_deref().unwrap_or(&self.network) == network)
+                .collect();
+            if members.is_empty() {
+                continue;
+            }
+            out.push_str(&format!(
+                "    subgraph \"cluster_{network}\" {{\n        label=\"{network}\";\n"
+            ));
+            for container in members {
+                out.push_str(&format!(
+                    "        \"{}\" [label=\"{}:{}\"];\n",
+                    container.name, container.name, container.port
+                ));
+            }
+            out.push_str("    }\n");
+        }
+        for route in self.routes.iter().filter(|r| !r.unbound) {
+            for port in &route.host_ports {
+                out.push_str(&format!(
+                    "    \"port_{port}\" [label=\":{port}\" shape=diamond];\n"
+                ));
+                out.push_str(&format!("    \"port_{port}\" -> \"{}\";\n", route.target));
+                if let Some(canary) = &route.canary {
+                    out.push_str(&format!(
+                        "    \"port_{port}\" -> \"{}\" [style=dashed label=\"{}%\"];\n",
+                        canary.target, canary.percent
+                    ));
+                }
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Mermaid flowchart rendering of the same topology as
+    /// [`Config::to_dot_graph`].
+    pub fn to_mermaid_graph(&self) -> String {
+        let mut out = String::from("graph LR\n");
+        for network in self.all_networks() {
+            let members: Vec<&Container> = self
+                .containers
+                .iter()
+                .filter(|c| c.network.as_deref().unwrap_or(&self.network) == network)
+                .collect();
+            if members.is_empty() {
+                continue;
+            }
+            out.push_str(&format!("    subgraph {network}\n"));
+            for container in members {
+                out.push_str(&format!(
+                    "        {}[\"{}:{}\"]\n",
+                    container.name, container.name, container.port
+                ));
+            }
+            out.push_str("    end\n");
+        }
+        for route in self.routes.iter().filter(|r| !r.unbound) {
+            for port in &route.host_ports {
+                out.push_str(&format!(
+                    "    port_{port}([\":{port}\"]) --> {}\n",
+                    route.target
+                ));
+                if let Some(canary) = &route.canary {
+                    out.push_str(&format!(
+                        "    port_{port} -. \"{}%\" .-> {}\n",
+                        canary.percent, canary.target
+                    ));
+                }
+            }
+        }
+        out
+    }
+
+    /// Plain-text rendering of the topology, one line per port binding.
+    pub fn to_ascii_graph(&self) -> String {
+        let mut out = String::new();
+        for route in &self.routes {
+            let network = self
+                .find_container(&route.target)
+                .and_then(|c| c.network.clone())
+                .unwrap_or_else(|| self.network.clone());
+            for port in &route.host_ports {
+                let suffix = if route.unbound { "  [unbound]" } else { "" };
+                out.push_str(&format!(
+                    ":{port} -> {}:{} ({network}){suffix}\n",
+                    route.target, route.internal_port
+                ));
+                if let Some(canary) = &route.canary {
+                    out.push_str(&format!(
+                        ":{port} ~> {}:{} ({}% canary)\n",
+                        canary.target, canary.internal_port, canary.percent
+                    ));
+                }
+            }
+        }
+        out
+    }
+
+    /// A copy of the config with `${VAR}` references resolved from the
+    /// process environment, or a plain clone when [`Config::interpolate`] is
+    /// off. Never persist the result: saving must keep the raw strings.
+    pub fn interpolated(&self) -> Result<Config> {
+        let mut config = self.clone();
+        if !self.interpolate {
+            return Ok(config);
+        }
+        config.proxy_name = substitute_env(&config.proxy_name)?;
+        config.network = substitute_env(&config.network)?;
+        config.base_image = substitute_env(&config.base_image)?;
+        for (_, value) in &mut config.proxy_env {
+            *value = substitute_env(value)?;
+        }
+        for container in &mut config.containers {
+            container.name = substitute_env(&container.name)?;
+            if let Some(label) = &mut container.label {
+                *label = substitute_env(label)?;
+            }
+            if let Some(network) = &mut container.network {
+                *network = substitute_env(network)?;
+            }
+            if let Some(root) = &mut container.static_root {
+                *root = substitute_env(root)?;
+            }
+        }
+        for route in &mut config.routes {
+            route.target = substitute_env(&route.target)?;
+            if let Some(canary) = &mut route.canary {
+                canary.target = substitute_env(&canary.target)?;
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Substitute `${VAR}` and `${VAR:-default}` references in `input` using
+/// `lookup` to resolve variables.
+///
+/// `$$` produces a literal `$`; a `$` not followed by `$` or `{` is kept
+/// as-is. Nested braces are not supported. An unset variable without a
+/// default is an error naming the variable.
+pub fn substitute<F>(input: &str, lookup: F) -> Result<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            out.push(c);
+            continue;
+        }
+        match chars.peek() {
+            Some('$') => {
+                chars.next();
+                out.push('$');
+            }
+            Some('{') => {
+                chars.next();
+                let mut body = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    body.push(c);
+                }
+                if !closed {
+                    bail!("unterminated '${{' in '{input}'");
+                }
+                let (name, default) = match body.split_once(":-") {
+                    Some((name, default)) => (name, Some(default)),
+                    None => (body.as_str(), None),
+                };
+                if name.is_empty() {
+                    bail!("empty variable name in '{input}'");
+                }
+                match lookup(name) {
+                    Some(value) => out.push_str(&value),
+                    None => match default {
+                        Some(default) => out.push_str(default),
+                        None => bail!("environment variable '{name}' is not set"),
+                    },
+                }
+            }
+            _ => out.push('$'),
+        }
+    }
+    Ok(out)
+}
+
+/// [`substitute`] against the process environment.
+pub fn substitute_env(input: &str) -> Result<String> {
+    substitute(input, |name| std::env::var(name).ok())
+}
+
+/// Parse a single `KEY=VALUE` pair as used by `--env` and env files.
+pub fn parse_env_pair(input: &str) -> Result<(String, String)> {
+    match input.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => bail!("invalid environment variable '{input}'; expected KEY=VALUE"),
+    }
+}
+
+/// Parse the contents of an env file: one `KEY=VALUE` per line, blank lines
+/// and `#` comments ignored.
+pub fn parse_env_file(contents: &str) -> Result<Vec<(String, String)>> {
+    let mut pairs = Vec::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        pairs.push(parse_env_pair(line)?);
+    }
+    Ok(pairs)
+}
+
+/// Directory layout and persistence for the configuration.
+#[derive(Debug, Clone)]
+pub struct Store {
+    config_dir: PathBuf,
+    /// Explicit build directory (from `--build-dir` or `Config.build_dir`);
+    /// `None` keeps the default location under `config_dir`.
+    build_dir_override: Option<PathBuf>,
+}
+
+impl Store {
+    /// Store rooted at the user's config directory
+    /// (`~/.config/proxy-manager` on Linux).
+    pub fn new() -> Result<Self> {
+        let base = dirs::config_dir().context("could not determine config directory")?;
+        Ok(Self::with_dir(base.join("proxy-manager")))
+    }
+
+    /// Store rooted at an explicit directory (used by tests).
+    pub fn with_dir(config_dir: PathBuf) -> Self {
+        Self {
+            config_dir,
+            build_dir_override: None,
+        }
+    }
+
+    /// Redirect build artifacts to an explicit directory.
+    pub fn set_build_dir(&mut self, dir: PathBuf) {
+        self.build_dir_override = Some(dir);
+    }
+
+    /// Directory holding the config file and build artifacts.
+    pub fn config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+
+    /// Path of the JSON config file.
+    pub fn config_file(&self) -> PathBuf {
+        self.config_dir.join("config.json")
+    }
+
+    /// Directory where build artifacts (nginx.conf, Dockerfile) are written;
+    /// under the config dir unless overridden.
+    pub fn build_dir(&self) -> PathBuf {
+        match &self.build_dir_override {
+            Some(dir) => dir.clone(),
+            None => self.config_dir.join("build"),
+        }
+    }
+
+    /// Sidecar file holding per-port switch history for `switch --undo`.
+    pub fn route_history_file(&self) -> PathBuf {
+        self.config_dir.join("route_history.json")
+    }
+
+    /// Sidecar file recording the backend containers `connect_backends`
+    /// attached to the proxy network, so stop can undo exactly those.
+    pub fn connected_backends_file(&self) -> PathBuf {
+        self.config_dir.join("connected_backends.json")
+    }
+
+    /// Load the config, falling back to defaults when the file is missing.
+    pub fn load(&self) -> Result<Config> {
+        let _span = tracing::debug_span!("config_load").entered();
+        let path = self.config_file();
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let data = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let mut config: Config = serde_json::from_str(&data)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        // Hand-edited files accumulate small inconsistencies; clean them up
+        // on the way in (the file itself is only rewritten on save).
+        config.normalize();
+        Ok(config)
+    }
+
+    /// Persist the config as pretty-printed JSON.
+    pub fn save(&self, config: &Config) -> Result<()> {
+        let _span = tracing::debug_span!("config_save").entered();
+        std::fs::create_dir_all(&self.config_dir)
+            .with_context(|| format!("failed to create {}", self.config_dir.display()))?;
+        let path = self.config_file();
+        let data = config.to_canonical_json()?;
+        // Write-to-temp-then-rename so a crash mid-write can never leave a
+        // truncated config: the temp file lives in the same directory (same
+        // filesystem), making the rename atomic on POSIX.
+        let tmp = path.with_extension("json.tmp");
+        let mut file = std::fs::File::create(&tmp)
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        use std::io::Write;
+        file.write_all(data.as_bytes())?;
+        file.sync_all()
+            .with_context(|| format!("failed to sync {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("failed to replace {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// Thread-safe, in-process handle to the configuration.
+///
+/// Wraps a [`Store`] and keeps the loaded [`Config`] behind a `Mutex` so the
+/// CLI, TUI and library embedders share one coherent view. Mutations go
+/// through [`ConfigManager::mutate`], which persists to disk before
+/// releasing the lock.
+pub struct ConfigManager {
+    store: Store,
+    config: Mutex<Config>,
+}
+
+impl ConfigManager {
+    /// Load (or default) the config from `config_dir`.
+    pub fn new(config_dir: PathBuf) -> Result<Self> {
+        Self::from_store(Store::with_dir(config_dir))
+    }
+
+    /// Wrap an existing [`Store`]. A `build_dir` set in the config file
+    /// takes effect here, unless the store already carries an explicit
+    /// override (the `--build-dir` flag).
+    pub fn from_store(mut store: Store) -> Result<Self> {
+        let config = store.load()?;
+        if store.build_dir_override.is_none() {
+            if let Some(dir) = &config.build_dir {
+                store.set_build_dir(dir.clone());
+            }
+        }
+        Ok(Self {
+            store,
+            config: Mutex::new(config),
+        })
+    }
+
+    /// The underlying store (paths, raw load/save).
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// Lock and read the current config.
+    pub fn get(&self) -> MutexGuard<'_, Config> {
+        self.config.lock().expect("config mutex poisoned")
+    }
+
+    /// Apply `f` to the config and persist the result before the lock is
+    /// released, so concurrent readers never observe unsaved state.
+    pub fn mutate<F: FnOnce(&mut Config)>(&self, f: F) -> Result<()> {
+        let mut guard = self.get();
+        f(&mut guard);
+        self.store.save(&guard)
+    }
+
+    /// Replace the whole config and persist it.
+    pub fn replace(&self, config: Config) -> Result<()> {
+        let mut guard = self.get();
+        *guard = config;
+        self.store.save(&guard)
+    }
+
+    /// Turn on `${VAR}` substitution for this process without writing the
+    /// flag back to disk (the `--interpolate` CLI flag).
+    pub fn force_interpolate(&self) {
+        self.get().interpolate = true;
+    }
+
+    /// Turn on backend auto-connect for this process without writing the
+    /// flag back to disk (the `--connect-backends` CLI flag).
+    pub fn force_connect_backends(&self) {
+        self.get().connect_backends = true;
+    }
+
+    /// Re-read the config from disk, picking up external edits.
+    pub fn reload(&self) -> Result<()> {
+        let fresh = self.store.load()?;
+        *self.get() = fresh;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_route_replaces_existing_port() {
+        let mut config = Config::default();
+        config.set_route(8000, "app1", 8080);
+        config.set_route(8000, "app2", 3000);
+        assert_eq!(config.routes.len(), 1);
+        assert_eq!(config.routes[0].target, "app2");
+        assert_eq!(config.routes[0].internal_port, 3000);
+    }
+
+    #[test]
+    fn find_container_matches_name_and_label() {
+        let mut config = Config::default();
+        config.upsert_container(Container {
+            name: "my-app".into(),
+            label: Some("web".into()),
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        assert!(config.find_container("my-app").is_some());
+        assert!(config.find_container("web").is_some());
+        assert!(config.find_container("nope").is_none());
+    }
+
+    #[test]
+    fn validate_rejects_dangling_route() {
+        let mut config = Config::default();
+        config.set_route(8000, "ghost", 80);
+        assert!(config.validate().is_err());
+    }
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "NET" => Some("lan".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn substitute_passes_plain_text_through() {
+        assert_eq!(substitute("proxy-net", lookup).unwrap(), "proxy-net");
+    }
+
+    #[test]
+    fn substitute_resolves_variables() {
+        assert_eq!(
+            substitute("pre-${NET}-post", lookup).unwrap(),
+            "pre-lan-post"
+        );
+        assert_eq!(substitute("${NET}${NET}", lookup).unwrap(), "lanlan");
+        assert_eq!(substitute("${EMPTY}x", lookup).unwrap(), "x");
+    }
+
+    #[test]
+    fn substitute_applies_defaults_only_when_unset() {
+        assert_eq!(
+            substitute("${MISSING:-fallback}", lookup).unwrap(),
+            "fallback"
+        );
+        assert_eq!(substitute("${NET:-fallback}", lookup).unwrap(), "lan");
+        assert_eq!(substitute("${MISSING:-}", lookup).unwrap(), "");
+    }
+
+    #[test]
+    fn substitute_escapes_dollar_signs() {
+        assert_eq!(substitute("$$", lookup).unwrap(), "$");
+        assert_eq!(substitute("a$$${NET}", lookup).unwrap(), "a$lan");
+        // A lone `$` not starting a reference stays literal.
+        assert_eq!(substitute("5$ fee", lookup).unwrap(), "5$ fee");
+        assert_eq!(substitute("end$", lookup).unwrap(), "end$");
+    }
+
+    #[test]
+    fn substitute_names_the_missing_variable() {
+        let err = substitute("${MISSING}", lookup).unwrap_err();
+        assert!(err.to_string().contains("MISSING"), "{err}");
+    }
+
+    #[test]
+    fn substitute_rejects_malformed_references() {
+        assert!(substitute("${NET", lookup).is_err());
+        assert!(substitute("${}", lookup).is_err());
+    }
+
+    #[test]
+    fn interpolated_resolves_fields_but_save_keeps_raw() {
+        std::env::set_var("PM_TEST_NETWORK", "lan");
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::with_dir(dir.path().to_path_buf());
+        let config = Config {
+            interpolate: true,
+            network: "${PM_TEST_NETWORK}".to_string(),
+            ..Config::default()
+        };
+        store.save(&config).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.network, "${PM_TEST_NETWORK}");
+        assert_eq!(loaded.interpolated().unwrap().network, "lan");
+    }
+
+    #[test]
+    fn interpolated_is_a_no_op_when_disabled() {
+        let config = Config {
+            network: "${NOT_SET_ANYWHERE}".to_string(),
+            ..Config::default()
+        };
+        assert_eq!(
+            config.interpolated().unwrap().network,
+            "${NOT_SET_ANYWHERE}"
+        );
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_canary_percent() {
+        let mut config = Config::default();
+        config.upsert_container(Container {
+            name: "app1".into(),
+            label: None,
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.set_route(8000, "app1", 8080);
+        config.routes[0].canary = Some(Canary {
+            target: "app1".into(),
+            internal_port: 8080,
+            percent: 100,
+        });
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn build_dir_override_beats_the_default_location() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = Store::with_dir(dir.path().to_path_buf());
+        assert_eq!(store.build_dir(), dir.path().join("build"));
+        store.set_build_dir(PathBuf::from("/tmp/artifacts"));
+        assert_eq!(store.build_dir(), PathBuf::from("/tmp/artifacts"));
+
+        // A build_dir in the config file is picked up on load...
+        let store = Store::with_dir(dir.path().to_path_buf());
+        store
+            .save(&Config {
+                build_dir: Some(dir.path().join("ci-out")),
+                ..Config::default()
+            })
+            .unwrap();
+        let manager = ConfigManager::from_store(store).unwrap();
+        assert_eq!(manager.store().build_dir(), dir.path().join("ci-out"));
+
+        // ...but an explicit override (the --build-dir flag) wins.
+        let mut store = Store::with_dir(dir.path().to_path_buf());
+        store.set_build_dir(dir.path().join("flag-out"));
+        let manager = ConfigManager::from_store(store).unwrap();
+        assert_eq!(manager.store().build_dir(), dir.path().join("flag-out"));
+    }
+
+    #[test]
+    fn reader_round_trips_and_rejects_broken_pipes() {
+        let mut config = Config::default();
+        config.upsert_container(Container {
+            name: "app1".into(),
+            label: None,
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.set_route(8000, "app1", 8080);
+
+        let mut buf = Vec::new();
+        config.to_writer(&mut buf).unwrap();
+        let parsed = Config::from_reader(buf.as_slice()).unwrap();
+        assert_eq!(
+            parsed.to_canonical_json().unwrap(),
+            config.to_canonical_json().unwrap()
+        );
+
+        let err = Config::from_reader("not json".as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("failed to parse config JSON"));
+
+        // Parses but fails validation: the route targets nothing.
+        let broken = String::from_utf8(buf)
+            .unwrap()
+            .replace("\"target\": \"app1\"", "\"target\": \"ghost\"");
+        assert!(Config::from_reader(broken.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn saves_are_byte_identical_across_cycles() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::with_dir(dir.path().to_path_buf());
+        let mut config = Config::default();
+        for name in ["zeta", "alpha"] {
+            config.upsert_container(Container {
+                name: name.into(),
+                label: None,
+                port: 8080,
+                network: None,
+                static_root: None,
+                response_rewrites: Vec::new(),
+                allowed_methods: None,
+                tls_backend: false,
+                tls_backend_insecure: false,
+                auth_request_url: None,
+                description: None,
+                dns_aliases: Vec::new(),
+                aliases: Vec::new(),
+            });
+        }
+        config.set_route(9000, "zeta", 8080);
+        config.set_route(8000, "alpha", 8080);
+        config
+            .proxy_labels
+            .insert("team".to_string(), "platform".to_string());
+        store.save(&config).unwrap();
+
+        let first = std::fs::read_to_string(store.config_file()).unwrap();
+        assert!(first.ends_with("}\n"));
+        // Containers and routes come out sorted, maps with sorted keys.
+        assert!(first.find("alpha").unwrap() < first.find("zeta").unwrap());
+        assert!(first.find("8000").unwrap() < first.find("9000").unwrap());
+        assert!(first.find("managed-by").unwrap() < first.find("team").unwrap());
+
+        // Load/save cycles never change a byte.
+        for _ in 0..3 {
+            let reloaded = store.load().unwrap();
+            store.save(&reloaded).unwrap();
+            assert_eq!(std::fs::read_to_string(store.config_file()).unwrap(), first);
+        }
+    }
+
+    #[test]
+    fn container_sorting_can_be_disabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::with_dir(dir.path().to_path_buf());
+        let mut config = Config {
+            sort_containers: false,
+            ..Config::default()
+        };
+        for name in ["zeta", "alpha"] {
+            config.upsert_container(Container {
+                name: name.into(),
+                label: None,
+                port: 8080,
+                network: None,
+                static_root: None,
+                response_rewrites: Vec::new(),
+                allowed_methods: None,
+                tls_backend: false,
+                tls_backend_insecure: false,
+                auth_request_url: None,
+                description: None,
+                dns_aliases: Vec::new(),
+                aliases: Vec::new(),
+            });
+        }
+        store.save(&config).unwrap();
+        let reloaded = store.load().unwrap();
+        let names: Vec<&str> = reloaded
+            .containers
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["zeta", "alpha"]);
+    }
+
+    #[test]
+    fn store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::with_dir(dir.path().to_path_buf());
+        let mut config = Config::default();
+        config.upsert_container(Container {
+            name: "app1".into(),
+            label: None,
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.set_route(8000, "app1", 8080);
+        store.save(&config).unwrap();
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.containers, config.containers);
+        assert_eq!(loaded.routes, config.routes);
+    }
+
+    #[test]
+    fn tagless_route_json_still_parses() {
+        let route: Route =
+            serde_json::from_str(r#"{"host_port": 8000, "target": "app1", "internal_port": 8080}"#)
+                .unwrap();
+        assert!(route.tags.is_empty());
+        // And tags are not serialized when empty.
+        let json = serde_json::to_string(&route).unwrap();
+        assert!(!json.contains("tags"));
+    }
+
+    #[test]
+    fn legacy_scalar_host_port_still_parses() {
+        let route: Route =
+            serde_json::from_str(r#"{"host_port": 8000, "target": "app1", "internal_port": 8080}"#)
+                .unwrap();
+        assert_eq!(route.host_ports, vec![8000]);
+        // Saving rewrites to the multi-port shape.
+        let json = serde_json::to_string(&route).unwrap();
+        assert!(json.contains(r#""host_ports":[8000]"#));
+        assert!(!json.contains(r#""host_port":"#));
+    }
+
+    #[test]
+    fn host_ports_array_parses_and_flattens() {
+        let route: Route = serde_json::from_str(
+            r#"{"host_ports": [80, 443], "target": "app1", "internal_port": 8080}"#,
+        )
+        .unwrap();
+        assert_eq!(route.host_ports, vec![80, 443]);
+
+        let mut config = Config::default();
+        config.set_route(8000, "app1", 8080);
+        config.routes[0].host_ports.push(8443);
+        assert_eq!(config.host_ports(), vec![8000, 8443]);
+    }
+
+    #[test]
+    fn validate_rejects_duplicate_and_empty_port_sets() {
+        let mut config = Config::default();
+        config.upsert_container(Container {
+            name: "app1".into(),
+            label: None,
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.set_route(8000, "app1", 8080);
+        config.set_route(8001, "app1", 8080);
+        config.routes[1].host_ports = vec![8001, 8000];
+        assert!(config.validate().is_err());
+
+        config.routes[1].host_ports = vec![];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn routes_with_tag_is_exact_and_case_sensitive() {
+        let mut config = Config::default();
+        config.set_route(8000, "app1", 8080);
+        config.set_route(8001, "app1", 8080);
+        config.routes[0].tags.push("Project-X".to_string());
+        config.routes[1].tags.push("project-x".to_string());
+        assert_eq!(config.routes_with_tag("project-x").len(), 1);
+        assert_eq!(config.routes_with_tag("project").len(), 0);
+    }
+
+    #[test]
+    fn parse_env_pair_requires_key() {
+        assert_eq!(
+            parse_env_pair("FOO=bar").unwrap(),
+            ("FOO".to_string(), "bar".to_string())
+        );
+        assert_eq!(
+            parse_env_pair("FOO=").unwrap(),
+            ("FOO".to_string(), String::new())
+        );
+        assert!(parse_env_pair("=bar").is_err());
+        assert!(parse_env_pair("novalue").is_err());
+    }
+
+    #[test]
+    fn parse_env_file_skips_comments_and_blanks() {
+        let pairs = parse_env_file("# comment\n\nFOO=bar\n  BAZ=qux  \n").unwrap();
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[0].0, "FOO");
+        assert_eq!(pairs[1], ("BAZ".to_string(), "qux".to_string()));
+    }
+
+    #[test]
+    fn config_manager_mutate_persists_before_unlock() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = ConfigManager::new(dir.path().to_path_buf()).unwrap();
+        manager
+            .mutate(|c| {
+                c.upsert_container(Container {
+                    name: "app1".into(),
+                    label: None,
+                    port: 8080,
+                    network: None,
+                    static_root: None,
+                    response_rewrites: Vec::new(),
+                    allowed_methods: None,
+                    tls_backend: false,
+                    tls_backend_insecure: false,
+                    auth_request_url: None,
+                    description: None,
+                    dns_aliases: Vec::new(),
+                    aliases: Vec::new(),
+                });
+                c.set_route(8000, "app1", 8080);
+            })
+            .unwrap();
+        assert_eq!(manager.get().routes.len(), 1);
+        // A fresh load from disk sees the mutation.
+        let reloaded = manager.store().load().unwrap();
+        assert_eq!(reloaded.routes.len(), 1);
+    }
+
+    #[test]
+    fn failed_save_leaves_the_existing_file_intact() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::with_dir(dir.path().to_path_buf());
+        let mut config = Config::default();
+        config.upsert_container(Container {
+            name: "app1".into(),
+            label: None,
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.set_route(8000, "app1", 8080);
+        store.save(&config).unwrap();
+
+        // Occupying the temp path with a directory makes the staged write
+        // fail before the real file is touched.
+        let tmp = store.config_file().with_extension("json.tmp");
+        std::fs::create_dir(&tmp).unwrap();
+        config.upsert_container(Container {
+            name: "app2".into(),
+            label: None,
+            port: 80,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.set_route(9000, "app2", 80);
+        assert!(store.save(&config).is_err());
+        std::fs::remove_dir(&tmp).unwrap();
+
+        let reloaded = store.load().unwrap();
+        assert_eq!(reloaded.routes.len(), 1);
+        assert!(reloaded.find_route(8000).is_some());
+    }
+
+    #[test]
+    fn descriptions_round_trip_and_vanish_when_unset() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::with_dir(dir.path().to_path_buf());
+        let mut config = Config::default();
+        config.upsert_container(Container {
+            name: "app1".into(),
+            label: None,
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: Some("billing backend".into()),
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.set_route(8000, "app1", 8080);
+        config.routes[0].description = Some("customer traffic".into());
+        store.save(&config).unwrap();
+        let reloaded = store.load().unwrap();
+        assert_eq!(
+            reloaded
+                .find_container("app1")
+                .unwrap()
+                .description
+                .as_deref(),
+            Some("billing backend")
+        );
+        assert_eq!(
+            reloaded.routes[0].description.as_deref(),
+            Some("customer traffic")
+        );
+        // Unset descriptions never hit the file.
+        let bare = serde_json::to_string(&Config::default()).unwrap();
+        assert!(!bare.contains("description"));
+    }
+
+    #[test]
+    fn normalize_cleans_up_and_reports_every_change() {
+        let mut config = Config::default();
+        config.containers.push(Container {
+            name: "  zeta ".into(),
+            label: Some("  ".into()),
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.containers.push(Container {
+            name: "alpha".into(),
+            label: None,
+            port: 8081,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.containers.push(Container {
+            name: "alpha".into(),
+            label: None,
+            port: 9000,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.set_route(9000, "alpha", 9000);
+        config.set_route(8000, "ghost", 8080);
+        let lines = config.normalize();
+        // Duplicates collapse to the last definition, sorted by name.
+        let names: Vec<&str> = config.containers.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["alpha", "zeta"]);
+        assert_eq!(config.find_container("alpha").unwrap().port, 9000);
+        assert_eq!(config.containers[1].label, None);
+        // The ghost route is gone; the surviving one keeps its port.
+        assert_eq!(config.routes.len(), 1);
+        assert_eq!(config.routes[0].target, "alpha");
+        assert!(lines.iter().any(|l| l.contains("Trimmed whitespace")));
+        assert!(lines
+            .iter()
+            .any(|l| l.contains("duplicate container 'alpha'")));
+        assert!(lines.iter().any(|l| l.contains("unknown container")));
+        // A second pass is a no-op.
+        assert!(config.normalize().is_empty());
+    }
+
+    #[test]
+    fn load_missing_file_gives_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::with_dir(dir.path().join("nope"));
+        let config = store.load().unwrap();
+        assert_eq!(config.proxy_name, DEFAULT_PROXY_NAME);
+        assert!(config.containers.is_empty());
+    }
+
+    fn graph_config() -> Config {
+        let mut config = Config::default();
+        config.upsert_container(Container {
+            name: "app1".into(),
+            label: None,
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.upsert_container(Container {
+            name: "db-ui".into(),
+            label: None,
+            port: 9000,
+            network: Some("backend".into()),
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.set_route(8000, "app1", 8080);
+        config.set_route(9090, "db-ui", 9000);
+        config
+    }
+
+    #[test]
+    fn dot_graph_clusters_by_network_and_links_ports() {
+        let dot = graph_config().to_dot_graph();
+        assert!(dot.starts_with("digraph routes {"));
+        assert!(dot.contains("subgraph \"cluster_proxy-net\""));
+        assert!(dot.contains("subgraph \"cluster_backend\""));
+        assert!(dot.contains("\"app1\" [label=\"app1:8080\"];"));
+        assert!(dot.contains("\"port_8000\" -> \"app1\";"));
+        assert!(dot.contains("\"port_9090\" -> \"db-ui\";"));
+    }
+
+    #[test]
+    fn dot_graph_skips_unbound_routes_and_marks_canaries() {
+        let mut config = graph_config();
+        config.routes[1].unbound = true;
+        config.routes[0].canary = Some(Canary {
+            target: "db-ui".into(),
+            internal_port: 9000,
+            percent: 25,
+        });
+        let dot = config.to_dot_graph();
+        assert!(!dot.contains("port_9090"));
+        assert!(dot.contains("\"port_8000\" -> \"db-ui\" [style=dashed label=\"25%\"];"));
+    }
+
+    #[test]
+    fn resolver_validation_accepts_addresses_and_off() {
+        let mut config = Config::default();
+        for ok in [
+            "off",
+            "127.0.0.11",
+            "10.0.0.2:5353",
+            "127.0.0.11 8.8.8.8:53",
+        ] {
+            config.resolver = Some(ok.to_string());
+            config.validate().unwrap();
+        }
+        for bad in ["", "dns.internal", "127.0.0.11,8.8.8.8"] {
+            config.resolver = Some(bad.to_string());
+            assert!(config.validate().is_err(), "accepted {bad:?}");
+        }
+    }
+
+    #[test]
+    fn static_route_validates_without_a_target_container() {
+        let mut config = Config::default();
+        config.set_static_route(8008, PathBuf::from("./site"));
+        config.validate().unwrap();
+        let route = config.find_route(8008).unwrap();
+        assert!(route.is_static());
+        assert!(route.target.is_empty());
+    }
+
+    #[test]
+    fn static_route_rejects_a_canary() {
+        let mut config = Config::default();
+        config.set_static_route(8008, PathBuf::from("./site"));
+        config.routes[0].canary = Some(Canary {
+            target: "app1".into(),
+            internal_port: 80,
+            percent: 10,
+        });
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn mermaid_and_ascii_graphs_cover_every_binding() {
+        let config = graph_config();
+        let mermaid = config.to_mermaid_graph();
+        assert!(mermaid.starts_with("graph LR"));
+        assert!(mermaid.contains("subgraph backend"));
+        assert!(mermaid.contains("port_8000([\":8000\"]) --> app1"));
+        let ascii = config.to_ascii_graph();
+        assert!(ascii.contains(":8000 -> app1:8080 (proxy-net)"));
+        assert!(ascii.contains(":9090 -> db-ui:9000 (backend)"));
+    }
+
+    #[test]
+    fn all_networks_covers_externals_and_backend_hosts_qualify() {
+        let mut config = Config {
+            external_networks: vec!["shared".to_string()],
+            ..Config::default()
+        };
+        config.upsert_container(Container {
+            name: "app1".into(),
+            label: None,
+            port: 8080,
+            network: Some("backend-net".into()),
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        assert_eq!(
+            config.all_networks(),
+            vec!["proxy-net", "backend-net", "shared"]
+        );
+
+        // Plain names by default; qualified only for secondary networks.
+        assert_eq!(config.backend_host("app1"), "app1");
+        config.qualify_network_names = true;
+        assert_eq!(config.backend_host("app1"), "app1.backend-net");
+        config.find_container_mut("app1").unwrap().network = Some(config.network.clone());
+        assert_eq!(config.backend_host("app1"), "app1");
+        assert_eq!(config.backend_host("ghost"), "ghost");
+    }
+
+    #[test]
+    fn aliases_resolve_like_names_and_reject_ambiguity() {
+        let mut config = Config::default();
+        config.upsert_container(Container {
+            name: "app1".into(),
+            label: Some("web".into()),
+            aliases: vec!["frontend".into(), "ui".into()],
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+        });
+        config.upsert_container(Container {
+            name: "app2".into(),
+            label: None,
+            aliases: Vec::new(),
+            port: 9000,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+        });
+        // Name, label and every alias all resolve to the same container.
+        for identifier in ["app1", "web", "frontend", "ui"] {
+            assert_eq!(config.find_container(identifier).unwrap().name, "app1");
+        }
+        config.find_container_mut("frontend").unwrap().port = 8081;
+        assert_eq!(config.find_container("app1").unwrap().port, 8081);
+        config.validate().unwrap();
+
+        // An alias shadowing another container's identifier is rejected.
+        config.find_container_mut("app2").unwrap().aliases = vec!["frontend".into()];
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("ambiguous"));
+        config.find_container_mut("app2").unwrap().aliases = vec!["app1".into()];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn env_file_round_trips_containers_and_routes() {
+        let mut config = Config {
+            proxy_name: "edge".to_string(),
+            network: "edge-net".to_string(),
+            ..Config::default()
+        };
+        config.upsert_container(Container {
+            name: "app1".into(),
+            label: None,
+            port: 8080,
+            network: Some("backend".into()),
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.set_route(8000, "app1", 8080);
+
+        let env = config.to_env_file();
+        assert!(env.contains("PROXY_MANAGER_PROXY_NAME=edge\n"));
+        assert!(env.contains("PROXY_MANAGER_CONTAINER_0_NAME=app1\n"));
+        assert!(env.contains("PROXY_MANAGER_CONTAINER_0_NETWORK=backend\n"));
+        assert!(env.contains("PROXY_MANAGER_ROUTE_0_HOST_PORT=8000\n"));
+
+        // The emitted file parses back into an equivalent config.
+        let pairs = parse_env_file(&env).unwrap();
+        let rebuilt = Config::from_env_pairs(&pairs).unwrap();
+        assert_eq!(rebuilt.proxy_name, "edge");
+        assert_eq!(rebuilt.network, "edge-net");
+        assert_eq!(rebuilt.find_container("app1").unwrap().port, 8080);
+        assert_eq!(
+            rebuilt.find_container("app1").unwrap().network.as_deref(),
+            Some("backend")
+        );
+        assert_eq!(rebuilt.find_route(8000).unwrap().target, "app1");
+        assert_eq!(rebuilt.find_route(8000).unwrap().internal_port, 8080);
+
+        // No variables at all means no config.
+        assert!(Config::from_env_pairs(&[]).is_none());
+    }
+
+    #[test]
+    fn config_values_round_trip_through_get_and_set() {
+        let mut config = Config::default();
+        config.set_value("network", "edge-net").unwrap();
+        assert_eq!(config.get_value("network").unwrap(), "edge-net");
+        config.set_value("proxy_backend", "caddy").unwrap();
+        assert_eq!(config.proxy_backend, ProxyBackend::Caddy);
+        config.set_value("management_port", "9000").unwrap();
+        assert_eq!(config.management_port, Some(9000));
+        config.set_value("management_port", "").unwrap();
+        assert_eq!(config.management_port, None);
+        assert_eq!(config.get_value("management_port").unwrap(), "");
+        config.set_value("compact_routes", "true").unwrap();
+        assert!(config.compact_routes);
+
+        // Every advertised key answers `get`.
+        for key in SETTABLE_KEYS {
+            config.get_value(key).unwrap();
+        }
+    }
+
+    #[test]
+    fn config_set_rejects_bad_keys_and_values() {
+        let mut config = Config::default();
+        let err = config.set_value("restart_delay_ms", "5").unwrap_err();
+        assert!(err.to_string().contains("settable keys: proxy_name"));
+        assert!(config.get_value("no_such_key").is_err());
+        assert!(config.set_value("network", "  ").is_err());
+        assert!(config.set_value("static_size_limit_mb", "lots").is_err());
+        assert!(config.set_value("compact_routes", "yes").is_err());
+        assert!(config.set_value("proxy_backend", "traefik").is_err());
+        assert!(config.set_value("resolver", "not an ip").is_err());
+    }
+
+    #[test]
+    fn backend_ca_files_must_exist_and_require_https() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca = dir.path().join("ca.crt");
+        let mut config = Config::default();
+        config.upsert_container(Container {
+            name: "app1".into(),
+            label: None,
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.set_route(8000, "app1", 8080);
+        config.routes[0].backend_ca_file = Some(ca.clone());
+
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("without backend_scheme https"));
+
+        config.routes[0].backend_scheme = BackendScheme::Https;
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("does not exist"));
+
+        std::fs::write(&ca, "CERT").unwrap();
+        config.validate().unwrap();
+
+        config.routes[0].mtls_ca_cert = Some(dir.path().join("clients.crt"));
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("without tls"));
+
+        config.routes[0].tls = true;
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("does not exist"));
+
+        std::fs::write(dir.path().join("clients.crt"), "CERT").unwrap();
+        config.validate().unwrap();
+
+        config.routes[0].mtls_ca_cert = None;
+        config.routes[0].mtls_verify = Some(MtlsVerify::Optional);
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("without mtls_ca_cert"));
+    }
+
+    #[test]
+    fn ip_hash_conflicts_with_compact_routes() {
+        let mut config = Config::default();
+        config.upsert_container(Container {
+            name: "app1".into(),
+            label: None,
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.set_route(8000, "app1", 8080);
+        config.routes[0].lb_method = Some(LbMethod::IpHash);
+        config.validate().unwrap();
+
+        config.compact_routes = true;
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("ip_hash"));
+    }
+
+    #[test]
+    fn validate_rejects_non_http_auth_request_urls() {
+        let mut config = Config::default();
+        config.upsert_container(Container {
+            name: "app1".into(),
+            label: None,
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: Some("auth-service/verify".into()),
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("full http:// address"));
+
+        config.find_container_mut("app1").unwrap().auth_request_url =
+            Some("http://auth-service:9000/verify".into());
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn count_stats_covers_every_field() {
+        let mut config = Config::default();
+        for (name, network) in [("app1", None), ("app2", Some("other-net")), ("idle", None)] {
+            config.upsert_container(Container {
+                name: name.into(),
+                label: None,
+                port: 8080,
+                network: network.map(String::from),
+                static_root: None,
+                response_rewrites: Vec::new(),
+                allowed_methods: None,
+                tls_backend: false,
+                tls_backend_insecure: false,
+                auth_request_url: None,
+                description: None,
+                dns_aliases: Vec::new(),
+                aliases: Vec::new(),
+            });
+        }
+        config.external_networks = vec!["shared".to_string()];
+        config.set_route(8000, "app1", 8080);
+        config.set_route(8001, "app2", 8080);
+        let stats = config.count_stats();
+        assert_eq!(stats.container_count, 3);
+        assert_eq!(stats.route_count, 2);
+        // default + other-net + shared
+        assert_eq!(stats.network_count, 3);
+        assert_eq!(stats.routed_container_count, 2);
+        assert_eq!(stats.unrouted_container_count, 1);
+        assert_eq!(
+            stats.summary(),
+            "3 containers (2 routed), 3 networks, 2 routes"
+        );
+    }
+}
@@ -0,0 +1,525 @@
+//! Long-running daemon mode.
+//!
+//! The daemon polls Docker and the config file on an interval, turns the
+//! difference between two observations into events, and reconciles those
+//! events into actions. Observation and execution talk to the outside
+//! world; [`diff`] and [`reconcile`] are pure so the decision logic can be
+//! tested with scripted sequences.
+
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::app::App;
+use crate::config::Config;
+
+/// Name of the status file the daemon keeps in the config directory.
+const STATUS_FILE: &str = "daemon-status.json";
+
+/// Schema version stamped on every emitted event line; bump only for
+/// incompatible changes, consumers match on it.
+const EVENT_SCHEMA_VERSION: u32 = 1;
+
+/// A significant occurrence in a long-running mode, emitted for machine
+/// consumption. Variant and field names are a public contract.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProxyEvent {
+    ConfigChanged,
+    ReloadStarted,
+    ReloadSucceeded,
+    ReloadFailed { error: String },
+    ProxyRestarted,
+    RouteSynced,
+}
+
+impl ProxyEvent {
+    /// Render as exactly one line of JSON with the `v` schema version;
+    /// JSON string escaping keeps multi-line payloads on one line.
+    pub fn to_json_line(&self) -> String {
+        let mut value = serde_json::to_value(self).expect("event serializes");
+        value["v"] = EVENT_SCHEMA_VERSION.into();
+        value.to_string()
+    }
+}
+
+/// Where emitted events go; lets the same event feed several consumers
+/// without each call site knowing about them.
+pub trait EventSink: Send {
+    fn emit(&mut self, event: &ProxyEvent);
+}
+
+/// Discards events; the default when no machine-readable output was
+/// requested (human-readable logs go to stderr via tracing regardless).
+pub struct NullSink;
+
+impl EventSink for NullSink {
+    fn emit(&mut self, _event: &ProxyEvent) {}
+}
+
+/// Writes one JSON line per event; pointed at stdout by the CLI, at a
+/// buffer in tests.
+pub struct JsonLineSink<W: Write + Send>(pub W);
+
+impl<W: Write + Send> EventSink for JsonLineSink<W> {
+    fn emit(&mut self, event: &ProxyEvent) {
+        // A failing stdout (closed pipe) must not take the daemon down.
+        let _ = writeln!(self.0, "{}", event.to_json_line());
+        let _ = self.0.flush();
+    }
+}
+
+/// A snapshot of the world as seen in one poll.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Observation {
+    /// Whether the proxy container is currently running.
+    pub proxy_running: bool,
+    /// Size and mtime of the config file, `None` when it does not exist.
+    pub config_fingerprint: Option<(u64, i64)>,
+    /// Names of all running containers.
+    pub running_containers: Vec<String>,
+}
+
+/// Something that changed between two observations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The proxy container was running and no longer is.
+    ProxyDown,
+    /// The config file changed on disk.
+    ConfigChanged,
+    /// A container that was not running before started.
+    ContainerAppeared(String),
+}
+
+/// What the daemon should do in response to a batch of events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Rebuild and restart the proxy container.
+    RestartProxy,
+    /// Re-read the config file and reload the proxy.
+    ReloadConfig,
+    /// Run auto-discovery to pick up newly appeared containers.
+    Discover,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::RestartProxy => write!(f, "restart proxy"),
+            Action::ReloadConfig => write!(f, "reload config"),
+            Action::Discover => write!(f, "discover containers"),
+        }
+    }
+}
+
+/// Events raised by `current` relative to `previous`.
+pub fn diff(previous: &Observation, current: &Observation) -> Vec<Event> {
+    let mut events = Vec::new();
+    if previous.proxy_running && !current.proxy_running {
+        events.push(Event::ProxyDown);
+    }
+    if previous.config_fingerprint != current.config_fingerprint {
+        events.push(Event::ConfigChanged);
+    }
+    for name in &current.running_containers {
+        if !previous.running_containers.contains(name) {
+            events.push(Event::ContainerAppeared(name.clone()));
+        }
+    }
+    events
+}
+
+/// Decide what to do about `events` given the desired `config`.
+///
+/// A route target coming back needs nothing (nginx resolves per request),
+/// so only the proxy itself dying triggers a restart. A restart already
+/// picks up the latest config, making a separate reload redundant.
+/// Discovery runs when `discover_prefix` is set and an unconfigured
+/// container matching it appeared; the empty prefix matches everything.
+pub fn reconcile(config: &Config, discover_prefix: Option<&str>, events: &[Event]) -> Vec<Action> {
+    let mut actions = Vec::new();
+    for event in events {
+        let action = match event {
+            Event::ProxyDown => Some(Action::RestartProxy),
+            Event::ConfigChanged => Some(Action::ReloadConfig),
+            Event::ContainerAppeared(name) => match discover_prefix {
+                Some(prefix)
+                    if name.starts_with(prefix)
+                        && *name != config.proxy_name
+                        && config.find_container(name).is_none() =>
+                {
+                    Some(Action::Discover)
+                }
+                _ => None,
+            },
+        };
+        if let Some(action) = action {
+            if !actions.contains(&action) {
+                actions.push(action);
+            }
+        }
+    }
+    if actions.contains(&Action::RestartProxy) {
+        actions.retain(|a| *a != Action::ReloadConfig);
+    }
+    actions
+}
+
+/// What the daemon reports via `daemon status`, persisted after every
+/// action so the file is current even if the daemon is later killed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    pub started_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_action: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_action_at: Option<String>,
+}
+
+impl DaemonStatus {
+    fn new() -> Self {
+        Self {
+            pid: std::process::id(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+            last_action: None,
+            last_action_at: None,
+        }
+    }
+
+    fn record(&mut self, action: Action) {
+        self.last_action = Some(action.to_string());
+        self.last_action_at = Some(chrono::Utc::now().to_rfc3339());
+    }
+
+    /// Whether the recorded pid still refers to a live process.
+    pub fn alive(&self) -> bool {
+        Path::new(&format!("/proc/{}", self.pid)).exists()
+    }
+}
+
+/// Path of the daemon status file inside `config_dir`.
+pub fn status_path(config_dir: &Path) -> PathBuf {
+    config_dir.join(STATUS_FILE)
+}
+
+/// Read the status file, or `None` when no daemon has run yet.
+pub fn read_status(path: &Path) -> Result<Option<DaemonStatus>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let status = serde_json::from_str(&contents)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    Ok(Some(status))
+}
+
+fn write_status(path: &Path, status: &DaemonStatus) -> Result<()> {
+    let contents = serde_json::to_string_pretty(status)?;
+    std::fs::write(path, contents).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Take one observation through the app's Docker and store handles.
+async fn observe(app: &App) -> Result<Observation> {
+    let proxy_name = app.config_manager().get().proxy_name.clone();
+    let proxy_running = app.docker().container_running(&proxy_name).await?;
+    let config_fingerprint = std::fs::metadata(app.store().config_file())
+        .ok()
+        .map(|meta| {
+            let mtime = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_nanos() as i64)
+                .unwrap_or(0);
+            (meta.len(), mtime)
+        });
+    let mut running_containers: Vec<String> = app
+        .docker()
+        .list_containers(false)
+        .await?
+        .into_iter()
+        .map(|info| info.name)
+        .collect();
+    running_containers.sort();
+    Ok(Observation {
+        proxy_running,
+        config_fingerprint,
+        running_containers,
+    })
+}
+
+async fn execute(
+    app: &App,
+    action: Action,
+    discover_prefix: Option<&str>,
+    sink: &mut dyn EventSink,
+) -> Result<Vec<String>> {
+    match action {
+        Action::RestartProxy => {
+            let output = app.start(true, true).await?;
+            sink.emit(&ProxyEvent::ProxyRestarted);
+            Ok(output)
+        }
+        Action::ReloadConfig => {
+            sink.emit(&ProxyEvent::ConfigChanged);
+            app.config_manager().reload()?;
+            sink.emit(&ProxyEvent::ReloadStarted);
+            match app.reload(true).await {
+                Ok(output) => {
+                    sink.emit(&ProxyEvent::ReloadSucceeded);
+                    Ok(output)
+                }
+                Err(err) => {
+                    sink.emit(&ProxyEvent::ReloadFailed {
+                        error: format!("{err:#}"),
+                    });
+                    Err(err)
+                }
+            }
+        }
+        Action::Discover => {
+            let mut output = app.auto_discover_and_add(discover_prefix, false).await?;
+            output.extend(app.reload(true).await?);
+            sink.emit(&ProxyEvent::RouteSynced);
+            Ok(output)
+        }
+    }
+}
+
+/// Run the daemon until killed, polling every `interval`. Events go to
+/// `sink`; human-readable diagnostics stay on stderr via tracing.
+pub async fn run(
+    app: &App,
+    interval: Duration,
+    discover_prefix: Option<&str>,
+    sink: &mut dyn EventSink,
+) -> Result<()> {
+    let path = status_path(app.store().config_dir());
+    let mut status = DaemonStatus::new();
+    write_status(&path, &status)?;
+    tracing::info!(pid = status.pid, "daemon started");
+
+    let mut previous = observe(app).await?;
+    loop {
+        tokio::time::sleep(interval).await;
+        let current = match observe(app).await {
+            Ok(observation) => observation,
+            Err(err) => {
+                tracing::warn!(error = %err, "observation failed, retrying next tick");
+                continue;
+            }
+        };
+        let events = diff(&previous, &current);
+        if !events.is_empty() {
+            tracing::debug!(?events, "observed changes");
+        }
+        let config = app.config_manager().get().clone();
+        for action in reconcile(&config, discover_prefix, &events) {
+            tracing::info!(%action, "reconciling");
+            match execute(app, action, discover_prefix, sink).await {
+                Ok(lines) => {
+                    for line in lines {
+                        tracing::debug!("{line}");
+                    }
+                    status.record(action);
+                    write_status(&path, &status)?;
+                }
+                Err(err) => tracing::warn!(%action, error = %err, "action failed"),
+            }
+        }
+        // Re-observe after acting so our own restarts are not mistaken for
+        // new events on the next tick.
+        previous = match observe(app).await {
+            Ok(observation) => observation,
+            Err(_) => current,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observation(proxy: bool, fingerprint: i64, containers: &[&str]) -> Observation {
+        Observation {
+            proxy_running: proxy,
+            config_fingerprint: Some((100, fingerprint)),
+            running_containers: containers.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn config_with_container(name: &str) -> Config {
+        let mut config = Config::default();
+        config.upsert_container(crate::config::Container {
+            name: name.into(),
+            label: None,
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config
+    }
+
+    #[test]
+    fn diff_raises_events_for_each_kind_of_change() {
+        let previous = observation(true, 1, &["app1"]);
+        let current = observation(false, 2, &["app1", "app2"]);
+        assert_eq!(
+            diff(&previous, &current),
+            vec![
+                Event::ProxyDown,
+                Event::ConfigChanged,
+                Event::ContainerAppeared("app2".into()),
+            ]
+        );
+        // No change, no events; containers going away are nginx's problem.
+        assert!(diff(&current, &current).is_empty());
+        assert!(diff(&current, &observation(false, 2, &[])).is_empty());
+    }
+
+    #[test]
+    fn proxy_death_restarts_and_supersedes_a_reload() {
+        let config = Config::default();
+        let actions = reconcile(
+            &config,
+            None,
+            &[Event::ConfigChanged, Event::ProxyDown, Event::ConfigChanged],
+        );
+        assert_eq!(actions, vec![Action::RestartProxy]);
+    }
+
+    #[test]
+    fn config_change_alone_reloads_once() {
+        let config = Config::default();
+        let actions = reconcile(&config, None, &[Event::ConfigChanged, Event::ConfigChanged]);
+        assert_eq!(actions, vec![Action::ReloadConfig]);
+    }
+
+    #[test]
+    fn appeared_containers_only_trigger_discovery_when_enabled_and_new() {
+        let config = config_with_container("app1");
+        let events = vec![
+            Event::ContainerAppeared("app1".into()),
+            Event::ContainerAppeared("svc-new".into()),
+            Event::ContainerAppeared(config.proxy_name.clone()),
+        ];
+        // Disabled by default.
+        assert!(reconcile(&config, None, &events).is_empty());
+        // Enabled: only the unconfigured non-proxy container counts, once.
+        assert_eq!(
+            reconcile(&config, Some(""), &events),
+            vec![Action::Discover]
+        );
+        // A prefix filters out non-matching names.
+        assert!(reconcile(&config, Some("web-"), &events).is_empty());
+        assert_eq!(
+            reconcile(&config, Some("svc-"), &events),
+            vec![Action::Discover]
+        );
+    }
+
+    #[test]
+    fn scripted_sequence_produces_the_expected_action_log() {
+        let config = config_with_container("app1");
+        let ticks: Vec<Vec<Event>> = vec![
+            vec![],
+            vec![Event::ContainerAppeared("app1".into())],
+            vec![Event::ConfigChanged],
+            vec![Event::ProxyDown, Event::ContainerAppeared("svc-a".into())],
+        ];
+        let mut log = Vec::new();
+        for events in &ticks {
+            log.extend(reconcile(&config, Some("svc-"), events));
+        }
+        assert_eq!(
+            log,
+            vec![Action::ReloadConfig, Action::RestartProxy, Action::Discover]
+        );
+    }
+
+    #[test]
+    fn events_serialize_to_the_contracted_json_shape() {
+        assert_eq!(
+            ProxyEvent::ConfigChanged.to_json_line(),
+            r#"{"event":"config_changed","v":1}"#
+        );
+        assert_eq!(
+            ProxyEvent::ReloadStarted.to_json_line(),
+            r#"{"event":"reload_started","v":1}"#
+        );
+        assert_eq!(
+            ProxyEvent::ReloadSucceeded.to_json_line(),
+            r#"{"event":"reload_succeeded","v":1}"#
+        );
+        assert_eq!(
+            ProxyEvent::ReloadFailed {
+                error: "boom".into()
+            }
+            .to_json_line(),
+            r#"{"error":"boom","event":"reload_failed","v":1}"#
+        );
+        assert_eq!(
+            ProxyEvent::ProxyRestarted.to_json_line(),
+            r#"{"event":"proxy_restarted","v":1}"#
+        );
+        assert_eq!(
+            ProxyEvent::RouteSynced.to_json_line(),
+            r#"{"event":"route_synced","v":1}"#
+        );
+    }
+
+    #[test]
+    fn multi_line_errors_stay_on_one_event_line() {
+        let event = ProxyEvent::ReloadFailed {
+            error: "nginx: [emerg]\nline two".into(),
+        };
+        let line = event.to_json_line();
+        assert!(!line.contains('\n'));
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["error"], "nginx: [emerg]\nline two");
+    }
+
+    #[test]
+    fn json_sink_writes_exactly_one_parseable_line_per_event() {
+        let mut sink = JsonLineSink(Vec::new());
+        sink.emit(&ProxyEvent::ReloadStarted);
+        sink.emit(&ProxyEvent::ReloadFailed {
+            error: "a\nb".into(),
+        });
+        let written = String::from_utf8(sink.0).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for line in lines {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(value["v"], 1);
+        }
+    }
+
+    #[test]
+    fn status_round_trips_through_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = status_path(dir.path());
+        assert!(read_status(&path).unwrap().is_none());
+        let mut status = DaemonStatus::new();
+        status.record(Action::ReloadConfig);
+        write_status(&path, &status).unwrap();
+        let loaded = read_status(&path).unwrap().unwrap();
+        assert_eq!(loaded.pid, status.pid);
+        assert_eq!(loaded.last_action.as_deref(), Some("reload config"));
+        assert!(loaded.alive());
+    }
+}
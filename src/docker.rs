@@ -0,0 +1,1235 @@
+//! Docker access layer.
+//!
+//! All Docker interaction goes through the [`DockerApi`] trait so the
+//! higher-level logic ([`crate::manager::ProxyManager`], [`crate::app::App`])
+//! can be exercised in tests with a fake implementation. [`DockerClient`] is
+//! the real implementation backed by bollard.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use bollard::container::{
+    Config as ContainerConfig, CreateContainerOptions, DownloadFromContainerOptions,
+    ListContainersOptions, LogsOptions, RemoveContainerOptions, RestartContainerOptions,
+    StartContainerOptions, StopContainerOptions,
+};
+use bollard::exec::{CreateExecOptions, StartExecResults};
+use bollard::image::{BuildImageOptions, ListImagesOptions};
+use bollard::models::{HostConfig, Ipam, IpamConfig, PortBinding};
+use bollard::network::{
+    ConnectNetworkOptions, CreateNetworkOptions, InspectNetworkOptions, ListNetworksOptions,
+};
+use bollard::system::EventsOptions;
+use bollard::Docker;
+use futures_util::{Stream, StreamExt};
+
+/// Summary of a Docker container as needed by this tool.
+#[derive(Debug, Clone)]
+pub struct ContainerInfo {
+    pub name: String,
+    pub image: String,
+    pub status: String,
+    /// Networks the container is attached to.
+    pub networks: Vec<String>,
+    /// Ports exposed by the container (private/internal ports).
+    pub ports: Vec<u16>,
+}
+
+/// A container lifecycle event from the daemon, reduced to what a watch
+/// loop needs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DockerEvent {
+    pub container_name: String,
+    /// The daemon's action string, e.g. "start", "die", "destroy".
+    pub action: String,
+    /// Seconds since the epoch, as reported by the daemon.
+    pub timestamp: i64,
+}
+
+/// Summary of a Docker image as needed for pruning.
+#[derive(Debug, Clone)]
+pub struct ImageInfo {
+    pub id: String,
+    /// Size in bytes.
+    pub size: i64,
+    pub tags: Vec<String>,
+}
+
+/// The slice of `docker info` this tool cares about.
+#[derive(Debug, Clone, Default)]
+pub struct DaemonInfo {
+    /// True when the daemon reports running in rootless mode.
+    pub rootless: bool,
+}
+
+/// Oldest Docker API version this tool is tested against; older daemons
+/// produce confusing low-level errors, so they are rejected up front.
+pub const MIN_API_VERSION: &str = "1.41";
+
+/// Label set on networks created by this tool, so cleanup can tell them
+/// apart from pre-existing ones.
+pub const MANAGED_BY_LABEL: &str = "managed-by";
+/// Label value identifying networks this tool created.
+pub const MANAGED_BY_VALUE: &str = "proxy-manager";
+/// Label carrying a hash of the config a proxy container was started from,
+/// letting a second invocation tell "same deployment" from "different one".
+pub const CONFIG_HASH_LABEL: &str = "config-hash";
+
+/// Creation parameters for a Docker network. `Default` gives a plain
+/// bridge network carrying the managed-by label, matching what
+/// `ensure_network` has always created.
+#[derive(Debug, Clone)]
+pub struct NetworkOptions {
+    pub driver: String,
+    pub subnet: Option<String>,
+    pub gateway: Option<String>,
+    pub labels: HashMap<String, String>,
+}
+
+impl Default for NetworkOptions {
+    fn default() -> Self {
+        let mut labels = HashMap::new();
+        labels.insert(MANAGED_BY_LABEL.to_string(), MANAGED_BY_VALUE.to_string());
+        Self {
+            driver: "bridge".to_string(),
+            subnet: None,
+            gateway: None,
+            labels,
+        }
+    }
+}
+
+/// A container attached to a network, as reported by network inspect.
+#[derive(Debug, Clone)]
+pub struct NetworkContainerInfo {
+    pub name: String,
+    pub ipv4_address: String,
+    pub mac_address: String,
+}
+
+/// Summary of a Docker network.
+#[derive(Debug, Clone)]
+pub struct NetworkInfo {
+    pub name: String,
+    pub driver: String,
+    /// Number of containers attached to the network, when known.
+    pub container_count: Option<usize>,
+    /// Names of the attached containers; `None` when Docker did not
+    /// include the member map (as opposed to a genuinely empty network).
+    pub containers: Option<Vec<String>>,
+    /// True when the network carries this tool's managed-by label.
+    pub managed: bool,
+}
+
+/// The subset of Docker operations used by the proxy manager.
+#[async_trait]
+pub trait DockerApi: Send + Sync {
+    /// List containers; `all` includes stopped ones.
+    async fn list_containers(&self, all: bool) -> Result<Vec<ContainerInfo>>;
+
+    /// Status string of a container (`running`, `exited`, ...), or `None`
+    /// when no container with that name exists.
+    async fn get_container_status(&self, name: &str) -> Result<Option<String>>;
+
+    /// Create the network if it does not exist yet; returns true when it was
+    /// created by this call. `options` overrides driver, addressing and
+    /// labels; `None` gives the usual labelled bridge network.
+    async fn ensure_network(&self, name: &str, options: Option<&NetworkOptions>) -> Result<bool>;
+
+    /// List user-visible networks.
+    async fn list_networks(&self) -> Result<Vec<NetworkInfo>>;
+
+    /// Containers attached to a network with their addresses, sorted by
+    /// name; empty when the network does not exist.
+    async fn inspect_network(&self, name: &str) -> Result<Vec<NetworkContainerInfo>>;
+
+    /// Remove a network by name; a missing network is not an error.
+    async fn remove_network(&self, name: &str) -> Result<()>;
+
+    /// Connect a container to a network, optionally with a fixed address;
+    /// connecting an already-connected container is not an error.
+    async fn connect_container_to_network(
+        &self,
+        container: &str,
+        network: &str,
+        static_ip: Option<std::net::IpAddr>,
+    ) -> Result<()>;
+
+    /// Connect a container to a network with DNS aliases, so Docker's
+    /// resolver answers those names; already-connected is not an error.
+    async fn connect_network_with_alias(
+        &self,
+        container: &str,
+        network: &str,
+        aliases: &[String],
+    ) -> Result<()>;
+
+    /// Disconnect a container from a network; a missing network or a
+    /// container that is not attached is not an error.
+    async fn disconnect_container_from_network(&self, container: &str, network: &str)
+        -> Result<()>;
+
+    /// First subnet of a network's IPAM config, or `None` when the network
+    /// does not exist or has no addressing configured.
+    async fn network_subnet(&self, name: &str) -> Result<Option<String>>;
+
+    /// Build an image from an in-memory tar build context.
+    async fn build_image(&self, tag: &str, build_context: Vec<u8>) -> Result<()>;
+
+    /// Create and start a container publishing the given host ports 1:1
+    /// (host port N -> container port N), attached to `network`, with the
+    /// given environment variables set.
+    async fn run_container_with_ports(
+        &self,
+        name: &str,
+        image: &str,
+        network: &str,
+        host_ports: &[u16],
+        env: &[(String, String)],
+        labels: &[(String, String)],
+    ) -> Result<()>;
+
+    /// Stop (if running) and remove a container; missing containers are not
+    /// an error.
+    async fn stop_and_remove_container(&self, name: &str) -> Result<()>;
+
+    /// Restart a container, giving it `timeout_secs` to stop gracefully.
+    async fn restart_container(&self, name: &str, timeout_secs: u32) -> Result<()>;
+
+    /// Freeze a container's processes (`docker pause`).
+    async fn pause_container(&self, name: &str) -> Result<()>;
+
+    /// Resume a paused container (`docker unpause`).
+    async fn unpause_container(&self, name: &str) -> Result<()>;
+
+    /// A container's labels, or `None` when it does not exist.
+    async fn container_labels(&self, name: &str) -> Result<Option<HashMap<String, String>>>;
+
+    /// List dangling (untagged) images.
+    async fn list_dangling_images(&self) -> Result<Vec<ImageInfo>>;
+
+    /// Remove an image by id or tag.
+    async fn remove_image(&self, id: &str) -> Result<()>;
+
+    /// Image id a container was created from, or `None` when the container
+    /// does not exist.
+    async fn container_image_id(&self, name: &str) -> Result<Option<String>>;
+
+    /// How long the container has been running, or `None` when it does not
+    /// exist or is not running.
+    async fn container_age(&self, name: &str) -> Result<Option<std::time::Duration>>;
+
+    /// Host ports a container publishes, or an empty list when it does not
+    /// exist or publishes nothing.
+    async fn container_published_ports(&self, name: &str) -> Result<Vec<u16>>;
+
+    /// Daemon-level facts from `docker info`.
+    async fn daemon_info(&self) -> Result<DaemonInfo>;
+
+    /// Fetch container log lines, most recent `tail` lines when given;
+    /// `since` (unix seconds) drops anything older.
+    async fn get_logs(
+        &self,
+        name: &str,
+        tail: Option<u32>,
+        since: Option<i64>,
+    ) -> Result<Vec<String>>;
+
+    /// Read a single file out of a container's filesystem.
+    async fn copy_file_from_container(&self, name: &str, path: &str) -> Result<Vec<u8>>;
+
+    /// Run a command inside a running container, returning its exit code
+    /// and combined output.
+    async fn exec_in_container(&self, name: &str, cmd: &[String]) -> Result<(i64, String)>;
+
+    /// Convenience: whether the container exists and is running.
+    async fn container_running(&self, name: &str) -> Result<bool> {
+        Ok(self.get_container_status(name).await?.as_deref() == Some("running"))
+    }
+}
+
+/// Real Docker client backed by bollard.
+#[derive(Clone)]
+pub struct DockerClient {
+    docker: Docker,
+}
+
+/// Container runtime to talk to; Podman exposes a Docker-compatible
+/// socket, so both end up on the same API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Runtime {
+    Docker,
+    Podman,
+}
+
+/// Where Podman's Docker-compatible socket usually lives: per-user under
+/// `XDG_RUNTIME_DIR`, system-wide under `/run`.
+fn podman_socket_path() -> Option<std::path::PathBuf> {
+    let candidates = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(|dir| std::path::PathBuf::from(dir).join("podman/podman.sock"))
+        .into_iter()
+        .chain([std::path::PathBuf::from("/run/podman/podman.sock")]);
+    candidates.into_iter().find(|path| path.exists())
+}
+
+impl DockerClient {
+    /// Connect using the local defaults (`DOCKER_HOST` or the platform
+    /// socket), falling back to a Podman socket when the Docker one is
+    /// absent.
+    pub fn new() -> Result<Self> {
+        Self::with_runtime(None)
+    }
+
+    /// Connect to an explicit runtime, or pick one: `DOCKER_HOST` and the
+    /// platform Docker socket first, then Podman's compatible socket.
+    pub fn with_runtime(runtime: Option<Runtime>) -> Result<Self> {
+        let runtime = runtime.unwrap_or_else(|| {
+            if std::env::var_os("DOCKER_HOST").is_none()
+                && !std::path::Path::new("/var/run/docker.sock").exists()
+                && podman_socket_path().is_some()
+            {
+                Runtime::Podman
+            } else {
+                Runtime::Docker
+            }
+        });
+        let docker = match runtime {
+            Runtime::Docker => {
+                tracing::debug!(endpoint = "local defaults", "connecting to Docker");
+                Docker::connect_with_local_defaults()
+                    .context("failed to connect to Docker daemon")?
+            }
+            Runtime::Podman => {
+                let socket = podman_socket_path().ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "no Podman socket found (checked XDG_RUNTIME_DIR/podman/podman.sock \
+                         and /run/podman/podman.sock); is the podman.socket unit running?"
+                    )
+                })?;
+                tracing::debug!(endpoint = %socket.display(), "connecting to Podman");
+                Docker::connect_with_unix(
+                    &socket.to_string_lossy(),
+                    120,
+                    bollard::API_DEFAULT_VERSION,
+                )
+                .context("failed to connect to Podman socket")?
+            }
+        };
+        Ok(Self { docker })
+    }
+
+    /// Live stream of container events, optionally filtered to
+    /// `filter_names`; `Send + 'static` so callers can hand it to a
+    /// spawned task. Events without an actor name are dropped.
+    pub fn watch_container_events(
+        &self,
+        filter_names: &[String],
+    ) -> impl Stream<Item = DockerEvent> + Send + 'static {
+        let mut filters = HashMap::new();
+        filters.insert("type".to_string(), vec!["container".to_string()]);
+        if !filter_names.is_empty() {
+            filters.insert("container".to_string(), filter_names.to_vec());
+        }
+        self.docker
+            .events(Some(EventsOptions::<String> {
+                filters,
+                ..Default::default()
+            }))
+            .filter_map(|message| async move {
+                let message = message.ok()?;
+                let name = message.actor?.attributes?.remove("name")?;
+                Some(DockerEvent {
+                    container_name: name,
+                    action: message.action.unwrap_or_default(),
+                    timestamp: message.time.unwrap_or_default(),
+                })
+            })
+    }
+
+    /// Container log lines as a stream, oldest first, without ever
+    /// buffering the full history; pair with [`ring_collect`] to cap the
+    /// lines kept in memory.
+    pub fn stream_logs(
+        &self,
+        name: &str,
+        tail: Option<u32>,
+        since: Option<i64>,
+    ) -> impl Stream<Item = Result<String>> + Send + 'static {
+        let options = LogsOptions::<String> {
+            stdout: true,
+            stderr: true,
+            tail: tail.map_or_else(|| "all".to_string(), |t| t.to_string()),
+            since: since.unwrap_or(0),
+            ..Default::default()
+        };
+        let name = name.to_string();
+        self.docker
+            .logs(&name, Some(options))
+            .flat_map(move |chunk| {
+                let lines: Vec<Result<String>> =
+                    match chunk {
+                        Ok(chunk) => chunk
+                            .to_string()
+                            .lines()
+                            .map(|line| Ok(line.to_string()))
+                            .collect(),
+                        Err(e) => vec![Err(anyhow::Error::new(e)
+                            .context(format!("failed to read logs of '{name}'")))],
+                    };
+                futures_util::stream::iter(lines)
+            })
+    }
+
+    /// Export a container's filesystem to a tar archive at `output`.
+    /// Unlike an image export this snapshots the live container, runtime
+    /// state included; the stream is written out chunk by chunk so large
+    /// filesystems never sit in memory.
+    pub async fn export_container_to_tar(
+        &self,
+        name: &str,
+        output: &std::path::Path,
+    ) -> Result<()> {
+        use std::io::Write;
+        let file = std::fs::File::create(output)
+            .with_context(|| format!("failed to create {}", output.display()))?;
+        let mut writer = std::io::BufWriter::new(file);
+        let mut stream = self.docker.export_container(name);
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.with_context(|| format!("failed to export container '{name}'"))?;
+            writer
+                .write_all(&chunk)
+                .with_context(|| format!("failed to write {}", output.display()))?;
+        }
+        writer
+            .flush()
+            .with_context(|| format!("failed to write {}", output.display()))?;
+        Ok(())
+    }
+
+    /// Access the underlying bollard handle for operations not covered by
+    /// [`DockerApi`] (log following, etc.).
+    pub fn raw(&self) -> &Docker {
+        &self.docker
+    }
+
+    /// Create a network with explicit driver, addressing and labels.
+    pub async fn create_network_with_options(
+        &self,
+        name: &str,
+        options: &NetworkOptions,
+    ) -> Result<()> {
+        let ipam = if options.subnet.is_some() || options.gateway.is_some() {
+            Ipam {
+                config: Some(vec![IpamConfig {
+                    subnet: options.subnet.clone(),
+                    gateway: options.gateway.clone(),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }
+        } else {
+            Ipam::default()
+        };
+        self.docker
+            .create_network(CreateNetworkOptions {
+                name: name.to_string(),
+                driver: options.driver.clone(),
+                labels: options.labels.clone(),
+                ipam,
+                ..Default::default()
+            })
+            .await
+            .with_context(|| format!("failed to create network '{name}'"))?;
+        Ok(())
+    }
+
+    /// Negotiated Docker API version reported by the daemon.
+    pub async fn api_version(&self) -> Result<String> {
+        let version = self
+            .docker
+            .version()
+            .await
+            .context("failed to query the Docker daemon version")?;
+        version
+            .api_version
+            .context("daemon did not report an API version")
+    }
+
+    /// Fail fast with a readable message when the daemon's API version is
+    /// below [`MIN_API_VERSION`]. An unreachable daemon is not an error
+    /// here; the operation that actually needs it reports that with more
+    /// context.
+    pub async fn check_api_version(&self) -> Result<()> {
+        let version = match self.api_version().await {
+            Ok(version) => version,
+            Err(e) => {
+                tracing::debug!("skipping API version check: {e:#}");
+                return Ok(());
+            }
+        };
+        tracing::debug!(api_version = %version, "negotiated Docker API version");
+        if !api_version_at_least(&version, MIN_API_VERSION) {
+            bail!(
+                "Docker API version {version} is older than the minimum supported \
+                 {MIN_API_VERSION}; upgrade the Docker daemon"
+            );
+        }
+        Ok(())
+    }
+
+    fn strip_name(names: &Option<Vec<String>>) -> String {
+        names
+            .as_ref()
+            .and_then(|n| n.first())
+            .map(|n| n.trim_start_matches('/').to_string())
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl DockerApi for DockerClient {
+    async fn list_containers(&self, all: bool) -> Result<Vec<ContainerInfo>> {
+        let options = ListContainersOptions::<String> {
+            all,
+            ..Default::default()
+        };
+        let summaries = self
+            .docker
+            .list_containers(Some(options))
+            .await
+            .context("failed to list containers")?;
+        let mut infos = Vec::with_capacity(summaries.len());
+        for summary in summaries {
+            let networks = summary
+                .network_settings
+                .as_ref()
+                .and_then(|ns| ns.networks.as_ref())
+                .map(|nets| nets.keys().cloned().collect())
+                .unwrap_or_default();
+            let mut ports: Vec<u16> = summary
+                .ports
+                .as_ref()
+                .map(|ps| ps.iter().map(|p| p.private_port).collect())
+                .unwrap_or_default();
+            ports.sort_unstable();
+            ports.dedup();
+            infos.push(ContainerInfo {
+                name: Self::strip_name(&summary.names),
+                image: summary.image.unwrap_or_default(),
+                status: summary.state.unwrap_or_default(),
+                networks,
+                ports,
+            });
+        }
+        Ok(infos)
+    }
+
+    async fn get_container_status(&self, name: &str) -> Result<Option<String>> {
+        match self.docker.inspect_container(name, None).await {
+            Ok(details) => Ok(details.state.and_then(|s| s.status).map(|s| s.to_string())),
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("failed to inspect container '{name}'")),
+        }
+    }
+
+    async fn ensure_network(&self, name: &str, options: Option<&NetworkOptions>) -> Result<bool> {
+        let mut filters = HashMap::new();
+        filters.insert("name".to_string(), vec![name.to_string()]);
+        let existing = self
+            .docker
+            .list_networks(Some(ListNetworksOptions { filters }))
+            .await
+            .context("failed to list networks")?;
+        // The name filter is a substring match; check for an exact hit.
+        if existing.iter().any(|n| n.name.as_deref() == Some(name)) {
+            return Ok(false);
+        }
+        let default_options = NetworkOptions::default();
+        self.create_network_with_options(name, options.unwrap_or(&default_options))
+            .await?;
+        Ok(true)
+    }
+
+    async fn list_networks(&self) -> Result<Vec<NetworkInfo>> {
+        let networks = self
+            .docker
+            .list_networks(None::<ListNetworksOptions<String>>)
+            .await
+            .context("failed to list networks")?;
+        Ok(networks
+            .into_iter()
+            .map(|n| NetworkInfo {
+                name: n.name.unwrap_or_default(),
+                driver: n.driver.unwrap_or_default(),
+                container_count: n.containers.as_ref().map(|c| c.len()),
+                containers: n.containers.as_ref().map(|members| {
+                    let mut names: Vec<String> =
+                        members.values().filter_map(|c| c.name.clone()).collect();
+                    names.sort();
+                    names
+                }),
+                managed: n
+                    .labels
+                    .as_ref()
+                    .and_then(|l| l.get(MANAGED_BY_LABEL))
+                    .map(|v| v == MANAGED_BY_VALUE)
+                    .unwrap_or(false),
+            })
+            .collect())
+    }
+
+    async fn inspect_network(&self, name: &str) -> Result<Vec<NetworkContainerInfo>> {
+        let network = match self
+            .docker
+            .inspect_network(name, None::<InspectNetworkOptions<String>>)
+            .await
+        {
+            Ok(network) => network,
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("failed to inspect network '{name}'")),
+        };
+        let mut containers: Vec<NetworkContainerInfo> = network
+            .containers
+            .unwrap_or_default()
+            .into_values()
+            .map(|c| NetworkContainerInfo {
+                name: c.name.unwrap_or_default(),
+                ipv4_address: c.ipv4_address.unwrap_or_default(),
+                mac_address: c.mac_address.unwrap_or_default(),
+            })
+            .collect();
+        containers.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(containers)
+    }
+
+    async fn remove_network(&self, name: &str) -> Result<()> {
+        match self.docker.remove_network(name).await {
+            Ok(())
+            | Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(()),
+            Err(e) => Err(e).with_context(|| format!("failed to remove network '{name}'")),
+        }
+    }
+
+    async fn connect_container_to_network(
+        &self,
+        container: &str,
+        network: &str,
+        static_ip: Option<std::net::IpAddr>,
+    ) -> Result<()> {
+        let endpoint_config = static_ip
+            .map(|ip| bollard::models::EndpointSettings {
+                ipam_config: Some(bollard::models::EndpointIpamConfig {
+                    ipv4_address: Some(ip.to_string()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .unwrap_or_default();
+        match self
+            .docker
+            .connect_network(
+                network,
+                ConnectNetworkOptions {
+                    container,
+                    endpoint_config,
+                },
+            )
+            .await
+        {
+            Ok(()) => Ok(()),
+            // Already connected: treat as success.
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 403, ..
+            }) => Ok(()),
+            Err(e) => Err(e)
+                .with_context(|| format!("failed to connect '{container}' to network '{network}'")),
+        }
+    }
+
+    async fn connect_network_with_alias(
+        &self,
+        container: &str,
+        network: &str,
+        aliases: &[String],
+    ) -> Result<()> {
+        match self
+            .docker
+            .connect_network(
+                network,
+                ConnectNetworkOptions {
+                    container,
+                    endpoint_config: bollard::models::EndpointSettings {
+                        aliases: Some(aliases.to_vec()),
+                        ..Default::default()
+                    },
+                },
+            )
+            .await
+        {
+            Ok(()) => Ok(()),
+            // Already connected: treat as success.
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 403, ..
+            }) => Ok(()),
+            Err(e) => Err(e)
+                .with_context(|| format!("failed to connect '{container}' to network '{network}'")),
+        }
+    }
+
+    async fn disconnect_container_from_network(
+        &self,
+        container: &str,
+        network: &str,
+    ) -> Result<()> {
+        match self
+            .docker
+            .disconnect_network(
+                network,
+                bollard::network::DisconnectNetworkOptions {
+                    container,
+                    force: false,
+                },
+            )
+            .await
+        {
+            Ok(()) => Ok(()),
+            // Not attached or no such network: nothing to do.
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 403 | 404,
+                ..
+            }) => Ok(()),
+            Err(e) => Err(e).with_context(|| {
+                format!("failed to disconnect '{container}' from network '{network}'")
+            }),
+        }
+    }
+
+    async fn network_subnet(&self, name: &str) -> Result<Option<String>> {
+        let network = match self
+            .docker
+            .inspect_network(name, None::<InspectNetworkOptions<String>>)
+            .await
+        {
+            Ok(network) => network,
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("failed to inspect network '{name}'")),
+        };
+        Ok(network
+            .ipam
+            .and_then(|ipam| ipam.config)
+            .unwrap_or_default()
+            .into_iter()
+            .find_map(|c| c.subnet))
+    }
+
+    async fn build_image(&self, tag: &str, build_context: Vec<u8>) -> Result<()> {
+        let options = BuildImageOptions {
+            dockerfile: "Dockerfile".to_string(),
+            t: format!("{tag}:latest"),
+            rm: true,
+            ..Default::default()
+        };
+        let mut stream = self
+            .docker
+            .build_image(options, None, Some(build_context.into()));
+        while let Some(message) = stream.next().await {
+            let message = message.context("image build failed")?;
+            if let Some(error) = message.error {
+                anyhow::bail!("image build failed: {error}");
+            }
+        }
+        Ok(())
+    }
+
+    async fn run_container_with_ports(
+        &self,
+        name: &str,
+        image: &str,
+        network: &str,
+        host_ports: &[u16],
+        env: &[(String, String)],
+        labels: &[(String, String)],
+    ) -> Result<()> {
+        let mut port_bindings = HashMap::new();
+        let mut exposed_ports = HashMap::new();
+        for port in host_ports {
+            let key = format!("{port}/tcp");
+            exposed_ports.insert(key.clone(), HashMap::new());
+            port_bindings.insert(
+                key,
+                Some(vec![PortBinding {
+                    host_ip: None,
+                    host_port: Some(port.to_string()),
+                }]),
+            );
+        }
+        let env_vars: Vec<String> = env.iter().map(|(k, v)| format!("{k}={v}")).collect();
+        let mut label_map = HashMap::new();
+        label_map.insert(MANAGED_BY_LABEL.to_string(), MANAGED_BY_VALUE.to_string());
+        for (key, value) in labels {
+            label_map.insert(key.clone(), value.clone());
+        }
+        let config = ContainerConfig {
+            image: Some(image.to_string()),
+            env: (!env_vars.is_empty()).then_some(env_vars),
+            labels: Some(label_map),
+            exposed_ports: Some(exposed_ports),
+            host_config: Some(HostConfig {
+                port_bindings: Some(port_bindings),
+                network_mode: Some(network.to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        self.docker
+            .create_container(
+                Some(CreateContainerOptions {
+                    name: name.to_string(),
+                    platform: None,
+                }),
+                config,
+            )
+            .await
+            .with_context(|| format!("failed to create container '{name}'"))?;
+        self.docker
+            .start_container(name, None::<StartContainerOptions<String>>)
+            .await
+            .with_context(|| format!("failed to start container '{name}'"))?;
+        Ok(())
+    }
+
+    async fn stop_and_remove_container(&self, name: &str) -> Result<()> {
+        match self
+            .docker
+            .stop_container(name, Some(StopContainerOptions { t: 10 }))
+            .await
+        {
+            Ok(())
+            | Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            })
+            | Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 304, ..
+            }) => {}
+            Err(e) => return Err(e).with_context(|| format!("failed to stop container '{name}'")),
+        }
+        match self
+            .docker
+            .remove_container(
+                name,
+                Some(RemoveContainerOptions {
+                    force: true,
+                    ..Default::default()
+                }),
+            )
+            .await
+        {
+            Ok(())
+            | Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(()),
+            Err(e) => Err(e).with_context(|| format!("failed to remove container '{name}'")),
+        }
+    }
+
+    async fn container_labels(&self, name: &str) -> Result<Option<HashMap<String, String>>> {
+        match self.docker.inspect_container(name, None).await {
+            Ok(details) => Ok(Some(
+                details.config.and_then(|c| c.labels).unwrap_or_default(),
+            )),
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("failed to inspect container '{name}'")),
+        }
+    }
+
+    async fn restart_container(&self, name: &str, timeout_secs: u32) -> Result<()> {
+        self.docker
+            .restart_container(
+                name,
+                Some(RestartContainerOptions {
+                    t: timeout_secs as isize,
+                }),
+            )
+            .await
+            .with_context(|| format!("failed to restart container '{name}'"))
+    }
+
+    async fn pause_container(&self, name: &str) -> Result<()> {
+        self.docker
+            .pause_container(name)
+            .await
+            .with_context(|| format!("failed to pause container '{name}'"))
+    }
+
+    async fn unpause_container(&self, name: &str) -> Result<()> {
+        self.docker
+            .unpause_container(name)
+            .await
+            .with_context(|| format!("failed to unpause container '{name}'"))
+    }
+
+    async fn list_dangling_images(&self) -> Result<Vec<ImageInfo>> {
+        let mut filters = HashMap::new();
+        filters.insert("dangling".to_string(), vec!["true".to_string()]);
+        let images = self
+            .docker
+            .list_images(Some(ListImagesOptions {
+                filters,
+                ..Default::default()
+            }))
+            .await
+            .context("failed to list images")?;
+        Ok(images
+            .into_iter()
+            .map(|i| ImageInfo {
+                id: i.id,
+                size: i.size,
+                tags: i.repo_tags,
+            })
+            .collect())
+    }
+
+    async fn remove_image(&self, id: &str) -> Result<()> {
+        match self.docker.remove_image(id, None, None).await {
+            Ok(_)
+            | Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(()),
+            Err(e) => Err(e).with_context(|| format!("failed to remove image '{id}'")),
+        }
+    }
+
+    async fn container_image_id(&self, name: &str) -> Result<Option<String>> {
+        match self.docker.inspect_container(name, None).await {
+            Ok(details) => Ok(details.image),
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("failed to inspect container '{name}'")),
+        }
+    }
+
+    async fn container_published_ports(&self, name: &str) -> Result<Vec<u16>> {
+        let details = match self.docker.inspect_container(name, None).await {
+            Ok(details) => details,
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to inspect container '{name}'"))
+            }
+        };
+        let mut ports: Vec<u16> = details
+            .network_settings
+            .and_then(|settings| settings.ports)
+            .unwrap_or_default()
+            .into_values()
+            .flatten()
+            .flatten()
+            .filter_map(|binding| binding.host_port.and_then(|p| p.parse().ok()))
+            .collect();
+        ports.sort_unstable();
+        ports.dedup();
+        Ok(ports)
+    }
+
+    async fn container_age(&self, name: &str) -> Result<Option<std::time::Duration>> {
+        let details = match self.docker.inspect_container(name, None).await {
+            Ok(details) => details,
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to inspect container '{name}'"))
+            }
+        };
+        let Some(state) = details.state else {
+            return Ok(None);
+        };
+        if state.running != Some(true) {
+            return Ok(None);
+        }
+        let Some(started_at) = state.started_at else {
+            return Ok(None);
+        };
+        let started = chrono::DateTime::parse_from_rfc3339(&started_at)
+            .with_context(|| format!("unparsable started_at '{started_at}' for '{name}'"))?;
+        let age = chrono::Utc::now().signed_duration_since(started);
+        Ok(age.to_std().ok())
+    }
+
+    async fn daemon_info(&self) -> Result<DaemonInfo> {
+        let info = self
+            .docker
+            .info()
+            .await
+            .context("failed to query docker info")?;
+        let rootless = info
+            .security_options
+            .unwrap_or_default()
+            .iter()
+            .any(|opt| opt.contains("rootless"));
+        Ok(DaemonInfo { rootless })
+    }
+
+    async fn get_logs(
+        &self,
+        name: &str,
+        tail: Option<u32>,
+        since: Option<i64>,
+    ) -> Result<Vec<String>> {
+        let options = LogsOptions::<String> {
+            stdout: true,
+            stderr: true,
+            tail: tail.map_or_else(|| "all".to_string(), |t| t.to_string()),
+            since: since.unwrap_or(0),
+            ..Default::default()
+        };
+        let mut stream = self.docker.logs(name, Some(options));
+        let mut lines = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.with_context(|| format!("failed to read logs of '{name}'"))?;
+            for line in chunk.to_string().lines() {
+                lines.push(line.to_string());
+            }
+        }
+        Ok(lines)
+    }
+
+    async fn exec_in_container(&self, name: &str, cmd: &[String]) -> Result<(i64, String)> {
+        let exec = self
+            .docker
+            .create_exec(
+                name,
+                CreateExecOptions {
+                    cmd: Some(cmd.to_vec()),
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await
+            .with_context(|| format!("failed to create exec in '{name}'"))?;
+        let mut output = String::new();
+        if let StartExecResults::Attached {
+            output: mut stream, ..
+        } = self
+            .docker
+            .start_exec(&exec.id, None)
+            .await
+            .with_context(|| format!("failed to start exec in '{name}'"))?
+        {
+            while let Some(chunk) = stream.next().await {
+                output.push_str(&chunk?.to_string());
+            }
+        }
+        let inspect = self.docker.inspect_exec(&exec.id).await?;
+        Ok((inspect.exit_code.unwrap_or(-1), output))
+    }
+
+    async fn copy_file_from_container(&self, name: &str, path: &str) -> Result<Vec<u8>> {
+        let options = DownloadFromContainerOptions { path };
+        let mut stream = self.docker.download_from_container(name, Some(options));
+        let mut tarball = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk =
+                chunk.with_context(|| format!("failed to download '{path}' from '{name}'"))?;
+            tarball.extend_from_slice(&chunk);
+        }
+        // The API hands back a tar archive containing the requested file.
+        let mut archive = tar::Archive::new(tarball.as_slice());
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if entry.header().entry_type().is_file() {
+                let mut contents = Vec::new();
+                std::io::Read::read_to_end(&mut entry, &mut contents)?;
+                return Ok(contents);
+            }
+        }
+        bail!("'{path}' not found in container '{name}'")
+    }
+}
+
+/// True when the dotted API `version` is at least `min` (major, then minor).
+fn api_version_at_least(version: &str, min: &str) -> bool {
+    match (parse_api_version(version), parse_api_version(min)) {
+        (Some(v), Some(m)) => v >= m,
+        // An unparsable version is treated as new enough rather than
+        // blocking on a format change.
+        _ => true,
+    }
+}
+
+/// Parse "1.41" into (1, 41).
+fn parse_api_version(version: &str) -> Option<(u64, u64)> {
+    let (major, minor) = version.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// Parse a `--since` argument: a relative duration ("45s", "10m", "2h",
+/// "1d") counted back from `now`, or an RFC3339 timestamp. Returns unix
+/// seconds, as Docker's `since` filter expects.
+pub fn parse_since(input: &str, now: chrono::DateTime<chrono::Utc>) -> Result<i64> {
+    if let Some(value) = input.strip_suffix(|c: char| c.is_ascii_alphabetic()) {
+        if let Ok(value) = value.parse::<i64>() {
+            let unit = &input[value.to_string().len()..];
+            let seconds = match unit {
+                "s" => value,
+                "m" => value * 60,
+                "h" => value * 3600,
+                "d" => value * 86_400,
+                _ => bail!("unknown duration unit '{unit}' in '{input}'; use s, m, h or d"),
+            };
+            return Ok(now.timestamp() - seconds);
+        }
+    }
+    chrono::DateTime::parse_from_rfc3339(input)
+        .map(|t| t.timestamp())
+        .map_err(|_| {
+            anyhow::anyhow!("'{input}' is neither a duration (10m, 2h) nor an RFC3339 timestamp")
+        })
+}
+
+/// Drain a log stream keeping only the last `cap` lines, so memory stays
+/// bounded no matter how much the container has logged.
+pub async fn ring_collect<S>(stream: S, cap: usize) -> Result<Vec<String>>
+where
+    S: Stream<Item = Result<String>>,
+{
+    if cap == 0 {
+        return Ok(Vec::new());
+    }
+    let mut ring = std::collections::VecDeque::new();
+    let mut stream = std::pin::pin!(stream);
+    while let Some(line) = stream.next().await {
+        if ring.len() == cap {
+            ring.pop_front();
+        }
+        ring.push_back(line?);
+    }
+    Ok(ring.into())
+}
+
+/// Collapse bursts of container events: while new events keep arriving
+/// within `window` of each other only the most recent one is kept, and it
+/// is yielded once the stream goes quiet (or ends).
+pub fn debounce<S>(
+    stream: S,
+    window: std::time::Duration,
+) -> impl Stream<Item = DockerEvent> + Send + 'static
+where
+    S: Stream<Item = DockerEvent> + Send + 'static,
+{
+    futures_util::stream::unfold(
+        (Box::pin(stream), false),
+        move |(mut stream, done)| async move {
+            if done {
+                return None;
+            }
+            let mut pending = stream.next().await?;
+            loop {
+                match tokio::time::timeout(window, stream.next()).await {
+                    Ok(Some(event)) => pending = event,
+                    Ok(None) => return Some((pending, (stream, true))),
+                    Err(_) => return Some((pending, (stream, false))),
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_version_comparison_is_numeric_not_lexical() {
+        assert!(api_version_at_least("1.41", "1.41"));
+        assert!(api_version_at_least("1.45", "1.41"));
+        assert!(api_version_at_least("2.0", "1.41"));
+        // "1.9" < "1.41" numerically even though it sorts after lexically.
+        assert!(!api_version_at_least("1.9", "1.41"));
+        assert!(!api_version_at_least("1.40", "1.41"));
+    }
+
+    #[test]
+    fn unparsable_versions_are_not_rejected() {
+        assert!(api_version_at_least("unknown", MIN_API_VERSION));
+        assert!(api_version_at_least("", MIN_API_VERSION));
+    }
+
+    fn event(action: &str, at: i64) -> DockerEvent {
+        DockerEvent {
+            container_name: "app1".to_string(),
+            action: action.to_string(),
+            timestamp: at,
+        }
+    }
+
+    #[test]
+    fn since_accepts_durations_and_timestamps() {
+        let now = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        assert_eq!(parse_since("45s", now).unwrap(), 1_700_000_000 - 45);
+        assert_eq!(parse_since("10m", now).unwrap(), 1_700_000_000 - 600);
+        assert_eq!(parse_since("2h", now).unwrap(), 1_700_000_000 - 7200);
+        assert_eq!(parse_since("1d", now).unwrap(), 1_700_000_000 - 86_400);
+        // RFC3339 timestamps pass through as-is.
+        assert_eq!(
+            parse_since("2023-11-14T22:13:20Z", now).unwrap(),
+            1_700_000_000
+        );
+        assert!(parse_since("10w", now).is_err());
+        assert!(parse_since("yesterday", now).is_err());
+    }
+
+    #[tokio::test]
+    async fn ring_keeps_memory_bounded_on_huge_logs() {
+        let lines = futures_util::stream::iter((0..10_000).map(|i| Ok(format!("line {i}"))));
+        let kept = ring_collect(lines, 100).await.unwrap();
+        assert_eq!(kept.len(), 100);
+        assert_eq!(kept.first().unwrap(), "line 9900");
+        assert_eq!(kept.last().unwrap(), "line 9999");
+
+        let empty = ring_collect(futures_util::stream::iter([Ok("x".to_string())]), 0)
+            .await
+            .unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[tokio::test]
+    async fn debounce_keeps_only_the_last_event_of_a_burst() {
+        let burst = futures_util::stream::iter(vec![
+            event("create", 1),
+            event("start", 2),
+            event("die", 3),
+        ]);
+        let events: Vec<DockerEvent> = debounce(burst, std::time::Duration::from_millis(50))
+            .collect()
+            .await;
+        assert_eq!(events, vec![event("die", 3)]);
+    }
+
+    #[tokio::test]
+    async fn debounce_separates_quiet_periods() {
+        let window = std::time::Duration::from_millis(50);
+        let second = futures_util::stream::once(async move {
+            tokio::time::sleep(window * 3).await;
+            event("start", 9)
+        });
+        let stream =
+            futures_util::stream::iter(vec![event("create", 1), event("die", 2)]).chain(second);
+        let events: Vec<DockerEvent> = debounce(stream, window).collect().await;
+        assert_eq!(events, vec![event("die", 2), event("start", 9)]);
+    }
+}
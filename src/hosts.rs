@@ -0,0 +1,133 @@
+//! /etc/hosts integration for dev-TLD route hostnames.
+//!
+//! `hosts sync` maintains a managed block between begin/end markers so
+//! everything the user wrote stays untouched; only the block is replaced.
+//! Rendering and merging are pure so they can be tested without touching
+//! the real file.
+
+use crate::config::Config;
+
+/// First line of the managed block.
+pub const BEGIN_MARKER: &str = "# proxy-manager begin";
+/// Last line of the managed block.
+pub const END_MARKER: &str = "# proxy-manager end";
+
+/// Hostnames of bound routes ending in `tld`, sorted and deduplicated.
+pub fn dev_hosts(config: &Config, tld: &str) -> Vec<String> {
+    let mut hosts: Vec<String> = config
+        .routes
+        .iter()
+        .filter(|r| !r.unbound)
+        .filter_map(|r| r.host.clone())
+        .filter(|h| h.ends_with(tld))
+        .collect();
+    hosts.sort();
+    hosts.dedup();
+    hosts
+}
+
+/// Render the managed block mapping every host to 127.0.0.1.
+pub fn render_block(hosts: &[String]) -> String {
+    let mut out = format!("{BEGIN_MARKER}\n");
+    for host in hosts {
+        out.push_str(&format!("127.0.0.1 {host}\n"));
+    }
+    out.push_str(END_MARKER);
+    out.push('\n');
+    out
+}
+
+/// Replace the managed block in `existing` with `block`, appending it when
+/// no markers are present. Everything outside the markers is preserved
+/// byte-for-byte, including user edits made inside a previous block.
+pub fn merge_block(existing: &str, block: &str) -> String {
+    match block_span(existing) {
+        Some((start, end)) => format!("{}{}{}", &existing[..start], block, &existing[end..]),
+        None if existing.is_empty() => block.to_string(),
+        None => {
+            let separator = if existing.ends_with('\n') { "" } else { "\n" };
+            format!("{existing}{separator}{block}")
+        }
+    }
+}
+
+/// Remove the managed block (and nothing else) from `existing`.
+pub fn remove_block(existing: &str) -> String {
+    match block_span(existing) {
+        Some((start, end)) => format!("{}{}", &existing[..start], &existing[end..]),
+        None => existing.to_string(),
+    }
+}
+
+/// Byte span of the managed block including both marker lines and the
+/// trailing newline, or `None` when no complete block exists.
+fn block_span(content: &str) -> Option<(usize, usize)> {
+    let start = content.find(BEGIN_MARKER)?;
+    let end_marker = content[start..].find(END_MARKER)? + start;
+    let end = match content[end_marker..].find('\n') {
+        Some(offset) => end_marker + offset + 1,
+        None => content.len(),
+    };
+    Some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block() -> String {
+        render_block(&["app1.test".to_string(), "app2.test".to_string()])
+    }
+
+    #[test]
+    fn dev_hosts_filters_by_tld_and_skips_unbound() {
+        let mut config = Config::default();
+        config.set_route(8000, "app1", 8080);
+        config.set_route(8001, "app2", 8080);
+        config.set_route(8002, "app3", 8080);
+        config.routes[0].host = Some("app1.test".to_string());
+        config.routes[1].host = Some("app2.example.com".to_string());
+        config.routes[2].host = Some("app3.test".to_string());
+        config.routes[2].unbound = true;
+        assert_eq!(dev_hosts(&config, ".test"), vec!["app1.test"]);
+    }
+
+    #[test]
+    fn merge_appends_when_no_block_exists() {
+        let merged = merge_block("127.0.0.1 localhost\n", &block());
+        assert!(merged.starts_with("127.0.0.1 localhost\n# proxy-manager begin\n"));
+        assert!(merged.ends_with("# proxy-manager end\n"));
+        // A file without a trailing newline still gets a separating one.
+        let merged = merge_block("127.0.0.1 localhost", &block());
+        assert!(merged.starts_with("127.0.0.1 localhost\n# proxy-manager begin\n"));
+    }
+
+    #[test]
+    fn merge_replaces_an_existing_block_and_user_edits_inside_it() {
+        let existing = format!(
+            "127.0.0.1 localhost\n{BEGIN_MARKER}\n127.0.0.1 stale.test\n# my manual note\n{END_MARKER}\n::1 ip6-localhost\n"
+        );
+        let merged = merge_block(&existing, &block());
+        assert!(merged.contains("127.0.0.1 app1.test\n"));
+        assert!(!merged.contains("stale.test"));
+        assert!(!merged.contains("my manual note"));
+        // Content outside the markers survives on both sides.
+        assert!(merged.starts_with("127.0.0.1 localhost\n"));
+        assert!(merged.ends_with("::1 ip6-localhost\n"));
+    }
+
+    #[test]
+    fn resync_is_idempotent() {
+        let once = merge_block("127.0.0.1 localhost\n", &block());
+        let twice = merge_block(&once, &block());
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn remove_strips_only_the_managed_block() {
+        let synced = merge_block("127.0.0.1 localhost\n", &block());
+        assert_eq!(remove_block(&synced), "127.0.0.1 localhost\n");
+        // Without a block, the content is returned unchanged.
+        assert_eq!(remove_block("unrelated\n"), "unrelated\n");
+    }
+}
@@ -0,0 +1,25 @@
+//! proxy-manager: manage an nginx reverse proxy in Docker for local
+//! containers.
+//!
+//! The crate is usable as a library: construct an [`app::App`] with a
+//! [`config::Store`] and a [`docker::DockerApi`] implementation and drive it
+//! programmatically, or use the `proxy-manager` binary for the CLI/TUI.
+
+pub mod app;
+pub mod auth;
+pub mod batch;
+pub mod caddy;
+pub mod config;
+pub mod daemon;
+pub mod docker;
+pub mod hosts;
+pub mod manager;
+pub mod nginx;
+pub mod probe;
+pub mod tui;
+pub mod update;
+
+pub use app::App;
+pub use config::{Config, ConfigManager, Container, Route, Store};
+pub use docker::{DockerApi, DockerClient};
+pub use manager::ProxyManager;
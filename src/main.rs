@@ -0,0 +1,1530 @@
+//! Command-line entry point for proxy-manager.
+
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+
+use proxy_manager::app::{
+    clamp_log_tail, render_status_html, render_status_markdown, App, ReadOnlyError, SwitchOptions,
+    READ_ONLY_EXIT_CODE,
+};
+use proxy_manager::batch;
+use proxy_manager::config::{self, ConfigManager, Store};
+use proxy_manager::daemon;
+use proxy_manager::docker::{DockerApi, DockerClient};
+use proxy_manager::hosts;
+use proxy_manager::manager::BuildOverwrite;
+use proxy_manager::probe;
+use proxy_manager::tui;
+use proxy_manager::update;
+
+#[derive(Parser)]
+#[command(
+    name = "proxy-manager",
+    version,
+    about = "Manage an nginx reverse proxy for Docker containers"
+)]
+struct Cli {
+    /// Resolve ${VAR} references in the config even when the file does not
+    /// set "interpolate": true
+    #[arg(long, global = true)]
+    interpolate: bool,
+    /// Refuse mutating commands (also enabled by PROXY_MANAGER_READ_ONLY=1
+    /// or the "read_only" config field)
+    #[arg(long, global = true)]
+    read_only: bool,
+    /// Log level for diagnostics on stderr (overridden by RUST_LOG)
+    #[arg(long, global = true, value_name = "LEVEL")]
+    log_level: Option<String>,
+    /// Diagnostic log format
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    /// Container runtime to talk to; picked automatically when omitted
+    /// (Docker first, then Podman's compatible socket)
+    #[arg(long, global = true, value_enum)]
+    runtime: Option<RuntimeArg>,
+    /// Persist route changes without reloading the proxy; apply the
+    /// batch later with a single 'reload'
+    #[arg(long, global = true)]
+    no_reload: bool,
+    /// Write build artifacts (nginx.conf, Dockerfile, staged files) here
+    /// instead of the default build/ folder under the config directory
+    #[arg(long, global = true, value_name = "PATH")]
+    build_dir: Option<std::path::PathBuf>,
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RuntimeArg {
+    Docker,
+    Podman,
+}
+
+impl From<RuntimeArg> for proxy_manager::docker::Runtime {
+    fn from(runtime: RuntimeArg) -> Self {
+        match runtime {
+            RuntimeArg::Docker => proxy_manager::docker::Runtime::Docker,
+            RuntimeArg::Podman => proxy_manager::docker::Runtime::Podman,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SortBy {
+    Name,
+    Port,
+    Network,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum MaintenanceState {
+    On,
+    Off,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum GraphFormat {
+    Dot,
+    Mermaid,
+    Ascii,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Build and start the proxy with the configured routes
+    Start {
+        /// Environment variable for the proxy container (KEY=VALUE,
+        /// repeatable); persisted into the config
+        #[arg(long = "env", value_name = "KEY=VALUE")]
+        env: Vec<String>,
+        /// File with one KEY=VALUE per line to load proxy environment
+        /// variables from
+        #[arg(long)]
+        env_file: Option<std::path::PathBuf>,
+        /// Wait until every route target container is running before
+        /// starting the proxy
+        #[arg(long)]
+        wait_targets: bool,
+        /// Seconds to wait for targets with --wait-targets
+        #[arg(long, default_value_t = 30, requires = "wait_targets")]
+        timeout: u64,
+        /// With --wait-targets, fail instead of starting when targets are
+        /// still down after the timeout
+        #[arg(long, requires = "wait_targets")]
+        strict: bool,
+        /// Suppress the warning about host ports below the unprivileged
+        /// range on rootless Docker
+        #[arg(long)]
+        privileged_ports_ok: bool,
+        /// Replace a proxy started from a different config (e.g. by
+        /// another proxy-manager instance on a shared daemon)
+        #[arg(long)]
+        take_over: bool,
+        /// Regenerate build files even when they were edited by hand
+        #[arg(long)]
+        overwrite_build: bool,
+        /// Build with hand-edited build files instead of regenerating them
+        #[arg(long, conflicts_with = "overwrite_build")]
+        keep_local: bool,
+        /// Connect route targets that share no network with the proxy to
+        /// the proxy network before starting
+        #[arg(long)]
+        connect_backends: bool,
+    },
+    /// Stop the proxy, or remove a single route when a port is given
+    Stop {
+        /// Host port whose route should be removed
+        port: Option<u16>,
+        /// Remove all routes carrying this tag (single reload)
+        #[arg(long, conflicts_with = "port")]
+        tag: Option<String>,
+        /// Keep the route in the config but unbind its host port
+        #[arg(long, requires = "port")]
+        keep: bool,
+        /// Also remove the built image and networks created by proxy-manager
+        #[arg(long, conflicts_with_all = ["port", "tag", "keep"])]
+        all: bool,
+        /// With --all, keep the built image
+        #[arg(long, requires = "all")]
+        keep_image: bool,
+        /// With --all, keep created networks
+        #[arg(long, requires = "all")]
+        keep_networks: bool,
+        /// Also remove the backend network connections --connect-backends
+        /// made (recorded ones only; manual attachments stay)
+        #[arg(long, conflicts_with_all = ["port", "tag", "keep"])]
+        disconnect_on_stop: bool,
+    },
+    /// Re-bind a route unbound with 'stop <port> --keep'
+    Resume {
+        /// Host port of the unbound route
+        port: u16,
+    },
+    /// Stop and start the proxy
+    Restart {
+        /// Wait for active connections to drain before restarting
+        #[arg(long)]
+        when_idle: bool,
+        /// Seconds to wait for idle before giving up
+        #[arg(long, default_value_t = 300, requires = "when_idle")]
+        max_wait: u64,
+        /// Restart anyway when --max-wait expires instead of aborting
+        #[arg(long, requires = "when_idle")]
+        force_after_wait: bool,
+    },
+    /// Regenerate config and restart the proxy
+    Reload {
+        /// Automatically restore the last-good deployment when the start
+        /// phase fails after the proxy was already stopped
+        #[arg(long)]
+        auto_recover: bool,
+        /// Connect route targets that share no network with the proxy to
+        /// the proxy network when starting again
+        #[arg(long)]
+        connect_backends: bool,
+        /// Regenerate build files even when they were edited by hand
+        #[arg(long)]
+        overwrite_build: bool,
+        /// Build with hand-edited build files instead of regenerating them
+        #[arg(long, conflicts_with = "overwrite_build")]
+        keep_local: bool,
+    },
+    /// Rebuild and start the proxy from the last successful deployment
+    Recover,
+    /// Point a host port at a container
+    Switch {
+        /// Host port the proxy listens on (not the container port; see
+        /// --container-port for that)
+        #[arg(required_unless_present = "host_port")]
+        port: Option<u16>,
+        /// Target container (name or label)
+        #[arg(required_unless_present = "static_dir")]
+        target: Option<String>,
+        /// Host port the proxy listens on (explicit form of the positional)
+        #[arg(long, conflicts_with = "port")]
+        host_port: Option<u16>,
+        /// Port inside the container (defaults to the configured one)
+        #[arg(long)]
+        container_port: Option<u16>,
+        /// Tag to attach to the route (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Send a percentage of traffic to another container, as
+        /// "container:percent" (e.g. "app2:10")
+        #[arg(long, value_name = "CONTAINER:PERCENT")]
+        canary: Option<String>,
+        /// Cap simultaneous connections per client address on this route
+        #[arg(long, value_name = "N")]
+        max_conn: Option<u32>,
+        /// Note on why the route exists, shown in list/status
+        #[arg(long = "desc", value_name = "TEXT")]
+        description: Option<String>,
+        /// Load-balancing method for the route's backend
+        #[arg(long, value_enum)]
+        lb_method: Option<LbMethodArg>,
+        /// Scheme used to reach the backend; https for backends that
+        /// only speak TLS internally
+        #[arg(long, value_enum)]
+        backend_scheme: Option<BackendSchemeArg>,
+        /// With an https backend, skip verifying its certificate
+        /// (self-signed backends; warned about at every start)
+        #[arg(long)]
+        no_verify_backend_tls: bool,
+        /// CA bundle used to verify the https backend; copied into the
+        /// image
+        #[arg(long, value_name = "FILE")]
+        backend_ca_file: Option<std::path::PathBuf>,
+        /// CA bundle clients must present certificates signed by (mTLS);
+        /// copied into the image
+        #[arg(long = "mtls-ca", value_name = "PATH")]
+        mtls_ca_cert: Option<std::path::PathBuf>,
+        /// How strictly client certificates are demanded (requires
+        /// --mtls-ca)
+        #[arg(long, value_enum)]
+        mtls_verify: Option<MtlsVerifyArg>,
+        /// Restore the port's previous target from the switch history
+        #[arg(long, conflicts_with_all = ["target", "static_dir"])]
+        undo: bool,
+        /// Update the container's configured port to the single port it
+        /// currently exposes before routing
+        #[arg(long)]
+        refresh_port: bool,
+        /// Serve this local directory of static files instead of proxying
+        /// to a container
+        #[arg(
+            long = "static",
+            value_name = "DIR",
+            conflicts_with_all = ["target", "container_port", "canary"]
+        )]
+        static_dir: Option<std::path::PathBuf>,
+    },
+    /// Serve the maintenance page on a route instead of proxying
+    Maintenance {
+        /// Host port of the route
+        port: u16,
+        /// Turn maintenance mode on or off
+        #[arg(value_enum)]
+        state: MaintenanceState,
+    },
+    /// Manage route metadata
+    Route {
+        #[command(subcommand)]
+        command: RouteCommands,
+    },
+    /// Register a container in the config
+    Add {
+        /// Docker container name
+        #[arg(required_unless_present_any = ["from_file", "from_stdin"])]
+        name: Option<String>,
+        /// Register a batch from a file: a JSON array of
+        /// {name, label?, port?, network?} or name[:port][@network] lines
+        #[arg(long, value_name = "FILE", conflicts_with = "name")]
+        from_file: Option<std::path::PathBuf>,
+        /// Like --from-file but reading standard input
+        #[arg(long, conflicts_with_all = ["name", "from_file"])]
+        from_stdin: bool,
+        /// Short label usable instead of the name
+        #[arg(long)]
+        label: Option<String>,
+        /// Port the app listens on inside the container (auto-detected when
+        /// omitted)
+        #[arg(long)]
+        port: Option<u16>,
+        /// Docker network of the container (auto-detected when omitted)
+        #[arg(long)]
+        network: Option<String>,
+        /// Path inside the proxy container to serve static files from
+        /// before proxying (emits nginx try_files)
+        #[arg(long)]
+        static_root: Option<String>,
+        /// Note on why the container is registered, shown in list/status
+        #[arg(long = "desc", value_name = "TEXT")]
+        description: Option<String>,
+    },
+    /// Remove a container from the config
+    Remove {
+        /// Container name or label
+        identifier: String,
+        /// Also drop any routes still targeting the container
+        #[arg(long, conflicts_with = "retarget")]
+        force: bool,
+        /// Repoint routes targeting the container at another configured
+        /// container before removing
+        #[arg(long, value_name = "CONTAINER")]
+        retarget: Option<String>,
+    },
+    /// List configured containers and routes
+    List {
+        /// Only show routes carrying this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Sort the listing instead of keeping config order
+        #[arg(long, value_enum)]
+        sort: Option<SortBy>,
+        /// Reverse the sort order
+        #[arg(long, requires = "sort")]
+        reverse: bool,
+    },
+    /// Print the fully-resolved config as JSON (after ${VAR} expansion),
+    /// exactly as used to generate nginx
+    Effective,
+    /// Clean up config inconsistencies (duplicates, stale routes, stray
+    /// whitespace)
+    Normalize {
+        /// Report what would change without saving
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show proxy and backend status
+    Status {
+        /// Output format: plain text, a markdown report or a standalone
+        /// HTML page
+        #[arg(long, value_enum, default_value_t = StatusFormat::Text)]
+        format: StatusFormat,
+    },
+    /// Show the proxy and all route targets in a docker-ps-style table
+    Ps,
+    /// List running containers not yet in the config
+    Detect {
+        /// Emit a JSON array with image, status and network metadata
+        #[arg(long)]
+        json: bool,
+    },
+    /// Register running containers that are not yet in the config
+    AutoDiscover {
+        /// Only consider containers whose name starts with this prefix
+        #[arg(long)]
+        prefix: Option<String>,
+        /// Report what would be added without changing the config
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List Docker networks
+    Networks {
+        /// List the attached containers under each network
+        #[arg(long)]
+        containers: bool,
+    },
+    /// Create a Docker network with explicit driver and addressing
+    CreateNetwork {
+        /// Network name
+        name: String,
+        /// Network driver
+        #[arg(long, default_value = "bridge")]
+        network_driver: String,
+        /// CIDR subnet for the network (e.g. "172.28.0.0/16")
+        #[arg(long)]
+        subnet: Option<String>,
+        /// Gateway address within the subnet
+        #[arg(long, requires = "subnet")]
+        gateway: Option<String>,
+    },
+    /// Remove dangling images left behind by proxy rebuilds
+    PruneImages,
+    /// List managed proxy containers orphaned by an old proxy_name
+    Orphans {
+        /// Stop and remove every orphaned proxy container
+        #[arg(long)]
+        remove: bool,
+        /// Point proxy_name at this orphan instead of removing it
+        #[arg(long, conflicts_with = "remove")]
+        adopt: Option<String>,
+    },
+    /// Show proxy container logs
+    Logs {
+        /// Number of lines from the end of the logs
+        #[arg(long, default_value_t = 100)]
+        tail: u32,
+        /// Only print lines matching this regular expression
+        #[arg(long)]
+        grep: Option<String>,
+        /// Only show lines newer than this ("10m", "2h", or an RFC3339
+        /// timestamp)
+        #[arg(long)]
+        since: Option<String>,
+    },
+    /// Write nginx.conf and Dockerfile to the build directory without
+    /// building or starting anything
+    Generate,
+    /// Print the nginx.conf the running proxy actually uses
+    CaptureConfig {
+        /// Fail when the proxy is not running instead of falling back to
+        /// the generated config
+        #[arg(long)]
+        live: bool,
+    },
+    /// Render the routing topology as a graph
+    Graph {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+    },
+    /// Print the current configuration
+    Config {
+        /// Print raw JSON
+        #[arg(long)]
+        json: bool,
+        /// Show the file as stored, without ${VAR} substitution
+        #[arg(long)]
+        raw: bool,
+        /// Replace the config with JSON read from standard input
+        #[arg(long, conflicts_with_all = ["json", "raw"])]
+        stdin: bool,
+    },
+    /// Read one top-level config field by its JSON name
+    ConfigGet {
+        /// Field name, e.g. "network" or "management_port"
+        key: String,
+    },
+    /// Set one top-level config field; an empty value unsets optional
+    /// fields
+    ConfigSet {
+        /// Field name, e.g. "network" or "management_port"
+        key: String,
+        /// New value in its string form
+        value: String,
+    },
+    /// Pause a backend container and verify its routes serve the 503
+    /// fallback page, then resume it
+    SimulateFailover {
+        /// Container name or label
+        identifier: String,
+    },
+    /// Rewrite the config file in canonical form for clean diffs
+    ConfigFmt {
+        /// Only verify; exit non-zero when the file is not canonical
+        #[arg(long)]
+        check: bool,
+    },
+    /// Export a container's filesystem to a tar archive
+    ExportContainer {
+        /// Container name or label
+        identifier: String,
+        /// Path of the tar file to write
+        output: std::path::PathBuf,
+    },
+    /// Export the config as PROXY_MANAGER_* variables (.env format)
+    EnvFile {
+        /// Write here instead of stdout
+        #[arg(long)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Download and install the latest release over this binary
+    SelfUpdate {
+        /// Release metadata URL (JSON with version, url, sha256)
+        #[arg(long)]
+        url: Option<String>,
+        /// Only report whether an update is available
+        #[arg(long)]
+        check_only: bool,
+    },
+    /// Send a test HTTP request through a routed port
+    Test {
+        /// Host port to probe
+        port: u16,
+        /// Request path (default /)
+        path: Option<String>,
+        /// Exit non-zero unless the final status matches
+        #[arg(long)]
+        expect_status: Option<u16>,
+    },
+    /// Check that the proxy can reach each configured container
+    CheckNetwork {
+        /// Also print latency for reachable containers
+        #[arg(long)]
+        verbose: bool,
+    },
+    /// Restart a configured container in place
+    RestartContainer {
+        /// Container name or label from the config
+        identifier: String,
+        /// Seconds to wait for a graceful stop (default 10)
+        #[arg(long)]
+        timeout: Option<u32>,
+    },
+    /// Manage basic-auth protection on routes
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommands,
+    },
+    /// Maintain dev-TLD hostnames in /etc/hosts (or a dnsmasq conf file)
+    Hosts {
+        #[command(subcommand)]
+        command: HostsCommands,
+    },
+    /// Run continuously, watching Docker and the config for changes
+    Daemon {
+        /// Seconds between polls
+        #[arg(long, default_value_t = 5)]
+        interval: u64,
+        /// Auto-add appearing containers whose name starts with this prefix
+        /// (use an empty string to match everything)
+        #[arg(long)]
+        discover_prefix: Option<String>,
+        /// Emit machine-readable events on stdout (one JSON object per
+        /// line with a schema version); logs keep going to stderr
+        #[arg(long, value_enum, default_value_t = EventsFormat::Text)]
+        events_format: EventsFormat,
+        #[command(subcommand)]
+        command: Option<DaemonCommands>,
+    },
+    /// Serve Prometheus metrics over HTTP until interrupted
+    Serve {
+        /// Port to listen on
+        #[arg(long, default_value_t = 9641)]
+        port: u16,
+    },
+    /// Launch the interactive terminal UI
+    Tui {
+        /// Leave mouse capture off so terminal-native text selection works
+        #[arg(long)]
+        no_mouse: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuthCommands {
+    /// Protect a route with a username and password
+    Set {
+        /// Host port of the route
+        port: u16,
+        /// Username required by the route
+        username: String,
+        /// Read the password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Remove basic-auth protection from a route
+    Remove {
+        /// Host port of the route
+        port: u16,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LbMethodArg {
+    /// nginx's default; emits no directive
+    RoundRobin,
+    /// Prefer the backend with the fewest active connections
+    LeastConn,
+    /// Pin each client address to one backend
+    IpHash,
+    /// Pick a backend at random per request
+    Random,
+}
+
+impl From<LbMethodArg> for config::LbMethod {
+    fn from(method: LbMethodArg) -> Self {
+        match method {
+            LbMethodArg::RoundRobin => config::LbMethod::RoundRobin,
+            LbMethodArg::LeastConn => config::LbMethod::LeastConn,
+            LbMethodArg::IpHash => config::LbMethod::IpHash,
+            LbMethodArg::Random => config::LbMethod::Random,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum MtlsVerifyArg {
+    /// Reject requests without a valid client certificate
+    On,
+    /// Accept missing certificates but still validate presented ones
+    Optional,
+    Off,
+}
+
+impl From<MtlsVerifyArg> for config::MtlsVerify {
+    fn from(verify: MtlsVerifyArg) -> Self {
+        match verify {
+            MtlsVerifyArg::On => config::MtlsVerify::On,
+            MtlsVerifyArg::Optional => config::MtlsVerify::Optional,
+            MtlsVerifyArg::Off => config::MtlsVerify::Off,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BackendSchemeArg {
+    Http,
+    Https,
+}
+
+impl From<BackendSchemeArg> for config::BackendScheme {
+    fn from(scheme: BackendSchemeArg) -> Self {
+        match scheme {
+            BackendSchemeArg::Http => config::BackendScheme::Http,
+            BackendSchemeArg::Https => config::BackendScheme::Https,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum StatusFormat {
+    /// Human-readable lines (the default)
+    Text,
+    /// Markdown report with a route table
+    Markdown,
+    /// Standalone styled HTML page
+    Html,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum EventsFormat {
+    /// Human-readable logs only
+    Text,
+    /// One JSON event object per line on stdout
+    Json,
+}
+
+#[derive(Subcommand)]
+enum DaemonCommands {
+    /// Report whether a daemon is running and what it last did
+    Status,
+}
+
+#[derive(Subcommand)]
+enum HostsCommands {
+    /// Write the managed block for routes with a dev-TLD hostname
+    Sync {
+        /// Only sync hostnames ending in this TLD
+        #[arg(long, default_value = ".test")]
+        tld: String,
+        /// Write to this file instead of /etc/hosts (e.g. dnsmasq conf.d)
+        #[arg(long)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Remove the managed block
+    Clean {
+        /// File to clean instead of /etc/hosts
+        #[arg(long)]
+        output: Option<std::path::PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RouteCommands {
+    /// Attach a tag to a route
+    Tag { port: u16, tag: String },
+    /// Remove a tag from a route
+    Untag { port: u16, tag: String },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    init_tracing(cli.log_level.as_deref(), cli.log_format);
+    let mut store = Store::new()?;
+    if let Some(dir) = cli.build_dir {
+        store.set_build_dir(dir);
+    }
+    let config_manager = Arc::new(ConfigManager::from_store(store)?);
+    let client = DockerClient::with_runtime(cli.runtime.map(Into::into))?;
+    // Turn obscure bollard errors from old daemons into an actionable one.
+    client.check_api_version().await?;
+    let docker: Arc<dyn DockerApi> = Arc::new(client.clone());
+    let mut app = App::new(config_manager, docker);
+    if cli.read_only || std::env::var("PROXY_MANAGER_READ_ONLY").is_ok_and(|v| v == "1") {
+        app.force_read_only();
+    }
+    if cli.interpolate {
+        app.config_manager().force_interpolate();
+    }
+    if cli.no_reload {
+        app.set_no_reload();
+    }
+
+    if let Err(error) = run(cli.command, app, client).await {
+        if error.chain().any(|cause| cause.is::<ReadOnlyError>()) {
+            eprintln!("Error: {error:#}");
+            std::process::exit(READ_ONLY_EXIT_CODE);
+        }
+        return Err(error);
+    }
+    Ok(())
+}
+
+async fn run(command: Commands, mut app: App, client: DockerClient) -> Result<()> {
+    match command {
+        Commands::Start {
+            env,
+            env_file,
+            wait_targets,
+            timeout,
+            strict,
+            privileged_ports_ok,
+            take_over,
+            overwrite_build,
+            keep_local,
+            connect_backends,
+        } => {
+            app.set_build_overwrite(build_overwrite_policy(overwrite_build, keep_local));
+            apply_env_flags(&app, &env, env_file.as_deref())?;
+            if connect_backends {
+                app.config_manager().force_connect_backends();
+            }
+            if wait_targets {
+                print_lines(&app.wait_for_targets(timeout, strict).await?);
+            }
+            print_lines(&app.start(privileged_ports_ok, take_over).await?);
+        }
+        Commands::Stop {
+            port,
+            tag,
+            keep,
+            all,
+            keep_image,
+            keep_networks,
+            disconnect_on_stop,
+        } => match (port, tag, all) {
+            (_, _, true) => {
+                let mut lines = Vec::new();
+                if disconnect_on_stop {
+                    // Free the networks first so unused ones can be removed.
+                    lines.extend(app.disconnect_backends().await?);
+                }
+                lines.extend(app.down(keep_image, keep_networks).await?);
+                print_lines(&lines)
+            }
+            (_, Some(tag), false) => print_lines(&app.stop_tag(&tag).await?),
+            (Some(port), None, false) => print_lines(&app.stop_port(port, keep).await?),
+            (None, None, false) => {
+                let mut lines = app.stop().await?;
+                if disconnect_on_stop {
+                    lines.extend(app.disconnect_backends().await?);
+                }
+                print_lines(&lines)
+            }
+        },
+        Commands::Resume { port } => print_lines(&app.resume_port(port).await?),
+        Commands::Restart {
+            when_idle,
+            max_wait,
+            force_after_wait,
+        } => {
+            if when_idle {
+                let idle = app
+                    .wait_until_idle(std::time::Duration::from_secs(max_wait))
+                    .await?;
+                if !idle && !force_after_wait {
+                    anyhow::bail!(
+                        "proxy did not go idle within {max_wait}s; \
+                         rerun with --force-after-wait to restart anyway"
+                    );
+                }
+                if !idle {
+                    println!("Proceeding after {max_wait}s wait (--force-after-wait)");
+                }
+            }
+            print_lines(&app.stop().await?);
+            print_lines(&app.start(false, true).await?);
+        }
+        Commands::Reload {
+            auto_recover,
+            overwrite_build,
+            keep_local,
+            connect_backends,
+        } => {
+            app.set_build_overwrite(build_overwrite_policy(overwrite_build, keep_local));
+            if connect_backends {
+                app.config_manager().force_connect_backends();
+            }
+            print_lines(&app.reload(auto_recover).await?)
+        }
+        Commands::Recover => print_lines(&app.recover().await?),
+        Commands::Switch {
+            port,
+            target,
+            host_port,
+            container_port,
+            tags,
+            canary,
+            max_conn,
+            description,
+            lb_method,
+            backend_scheme,
+            no_verify_backend_tls,
+            backend_ca_file,
+            mtls_ca_cert,
+            mtls_verify,
+            refresh_port,
+            static_dir,
+            undo,
+        } => {
+            let port = port.or(host_port).expect("clap enforces one port form");
+            if undo {
+                print_lines(&app.undo_switch(port).await?);
+                return Ok(());
+            }
+            match (target, static_dir) {
+                (_, Some(dir)) => print_lines(&app.switch_static(port, &dir).await?),
+                (Some(target), None) => {
+                    let canary = canary.as_deref().map(parse_canary_spec).transpose()?;
+                    let options = SwitchOptions {
+                        internal_port: container_port,
+                        tags,
+                        canary,
+                        max_conn,
+                        description,
+                        lb_method: lb_method.map(Into::into),
+                        backend_scheme: backend_scheme.map(Into::into),
+                        verify_backend_tls: no_verify_backend_tls.then_some(false),
+                        backend_ca_file,
+                        mtls_ca_cert,
+                        mtls_verify: mtls_verify.map(Into::into),
+                        refresh_port,
+                    };
+                    print_lines(&app.switch(port, &target, options).await?)
+                }
+                (None, None) => unreachable!("clap enforces target or --static"),
+            }
+        }
+        Commands::Maintenance { port, state } => print_lines(
+            &app.set_maintenance(port, state == MaintenanceState::On)
+                .await?,
+        ),
+        Commands::Route { command } => match command {
+            RouteCommands::Tag { port, tag } => print_lines(&app.tag_route(port, &tag, true)?),
+            RouteCommands::Untag { port, tag } => print_lines(&app.tag_route(port, &tag, false)?),
+        },
+        Commands::Add {
+            name,
+            from_file,
+            from_stdin,
+            label,
+            port,
+            network,
+            static_root,
+            description,
+        } => match name {
+            Some(name) => print_lines(
+                &app.add_container(&name, label, port, network, static_root, description)
+                    .await?,
+            ),
+            None => {
+                let input = match &from_file {
+                    Some(path) => std::fs::read_to_string(path)
+                        .with_context(|| format!("failed to read {}", path.display()))?,
+                    None => {
+                        debug_assert!(from_stdin);
+                        std::io::read_to_string(std::io::stdin())?
+                    }
+                };
+                let entries = batch::parse_batch(&input)?;
+                let (output, failed) = app.add_containers_batch(entries).await?;
+                print_lines(&output);
+                if failed > 0 {
+                    anyhow::bail!(
+                        "{failed} batch entr{} failed",
+                        if failed == 1 { "y" } else { "ies" }
+                    );
+                }
+            }
+        },
+        Commands::Remove {
+            identifier,
+            force,
+            retarget,
+        } => print_lines(
+            &app.remove_container(&identifier, force, retarget.as_deref())
+                .await?,
+        ),
+        Commands::List { tag, sort, reverse } => cmd_list(&app, tag.as_deref(), sort, reverse)?,
+        Commands::Status { format } => cmd_status(&app, format).await?,
+        Commands::Ps => print_lines(&app.ps().await?),
+        Commands::Detect { json } => cmd_detect(&app, json).await?,
+        Commands::AutoDiscover { prefix, dry_run } => {
+            for line in app
+                .auto_discover_and_add(prefix.as_deref(), dry_run)
+                .await?
+            {
+                println!("{line}");
+            }
+        }
+        Commands::Networks { containers } => cmd_networks(&app, containers).await?,
+        Commands::CreateNetwork {
+            name,
+            network_driver,
+            subnet,
+            gateway,
+        } => {
+            let options = proxy_manager::docker::NetworkOptions {
+                driver: network_driver,
+                subnet,
+                gateway,
+                ..Default::default()
+            };
+            if app.docker().ensure_network(&name, Some(&options)).await? {
+                println!("Created network '{name}'");
+            } else {
+                println!("Network '{name}' already exists");
+            }
+        }
+        Commands::PruneImages => print_lines(&app.prune_images().await?),
+        Commands::Orphans { remove, adopt } => {
+            print_lines(&app.orphans(remove, adopt.as_deref()).await?)
+        }
+        Commands::Logs { tail, grep, since } => {
+            cmd_logs(&app, tail, grep.as_deref(), since.as_deref()).await?
+        }
+        Commands::CaptureConfig { live } => cmd_capture_config(&app, live).await?,
+        Commands::Generate => {
+            let config = app.config_manager().get().clone().interpolated()?;
+            config.validate()?;
+            let (conf, dockerfile) = app.manager().write_build_files(&config)?;
+            println!("{}", conf.display());
+            println!("{}", dockerfile.display());
+        }
+        Commands::Graph { format } => {
+            let config = app.config_manager().get().clone();
+            let graph = match format {
+                GraphFormat::Dot => config.to_dot_graph(),
+                GraphFormat::Mermaid => config.to_mermaid_graph(),
+                GraphFormat::Ascii => config.to_ascii_graph(),
+            };
+            print!("{graph}");
+        }
+        Commands::Config { json, raw, stdin } => {
+            if stdin {
+                print_lines(&app.config_import(std::io::stdin().lock())?);
+            } else {
+                cmd_config(&app, json, raw)?;
+            }
+        }
+        Commands::ConfigGet { key } => println!("{}", app.config_get(&key)?),
+        Commands::ConfigSet { key, value } => print_lines(&app.config_set(&key, &value)?),
+        Commands::SimulateFailover { identifier } => {
+            print_lines(&app.simulate_failover(&identifier).await?)
+        }
+        Commands::ConfigFmt { check } => print_lines(&app.config_fmt(check)?),
+        Commands::ExportContainer { identifier, output } => {
+            // Labels and aliases resolve like everywhere else; unknown
+            // names pass through so unmanaged containers export too.
+            let config = app.config_manager().get().clone();
+            let name = config
+                .find_container(&identifier)
+                .map(|c| c.name.clone())
+                .unwrap_or(identifier);
+            client.export_container_to_tar(&name, &output).await?;
+            println!("Exported container '{name}' to {}", output.display());
+        }
+        Commands::EnvFile { output } => {
+            let env = app.config_manager().get().clone().to_env_file();
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, &env)
+                        .with_context(|| format!("failed to write {}", path.display()))?;
+                    println!("Wrote {}", path.display());
+                }
+                None => print!("{env}"),
+            }
+        }
+        Commands::Normalize { dry_run } => print_lines(&app.normalize(dry_run)?),
+        Commands::Effective => {
+            let config = app.config_manager().get().clone().interpolated()?;
+            println!("{}", serde_json::to_string_pretty(&config)?);
+        }
+        Commands::SelfUpdate { url, check_only } => {
+            print_lines(&update::self_update(url.as_deref(), check_only).await?)
+        }
+        Commands::Test {
+            port,
+            path,
+            expect_status,
+        } => cmd_test(&app, port, path.as_deref(), expect_status).await?,
+        Commands::CheckNetwork { verbose } => cmd_check_network(&app, verbose).await?,
+        Commands::RestartContainer {
+            identifier,
+            timeout,
+        } => print_lines(
+            &app.restart_container(&identifier, timeout.unwrap_or(10))
+                .await?,
+        ),
+        Commands::Auth { command } => match command {
+            AuthCommands::Set {
+                port,
+                username,
+                password_stdin,
+            } => {
+                let password = if password_stdin {
+                    let mut buf = String::new();
+                    std::io::Read::read_to_string(&mut std::io::stdin(), &mut buf)?;
+                    buf.trim_end_matches(['\r', '\n']).to_string()
+                } else {
+                    rpassword::prompt_password("Password: ")?
+                };
+                print_lines(&app.set_auth(port, &username, &password).await?);
+            }
+            AuthCommands::Remove { port } => print_lines(&app.remove_auth(port).await?),
+        },
+        Commands::Hosts { command } => match command {
+            HostsCommands::Sync { tld, output } => cmd_hosts_sync(&app, &tld, output.as_deref())?,
+            HostsCommands::Clean { output } => cmd_hosts_clean(output.as_deref())?,
+        },
+        Commands::Daemon {
+            interval,
+            discover_prefix,
+            events_format,
+            command,
+        } => match command {
+            Some(DaemonCommands::Status) => cmd_daemon_status(&app)?,
+            None => {
+                let mut sink: Box<dyn daemon::EventSink> = match events_format {
+                    EventsFormat::Text => Box::new(daemon::NullSink),
+                    EventsFormat::Json => Box::new(daemon::JsonLineSink(std::io::stdout())),
+                };
+                daemon::run(
+                    &app,
+                    std::time::Duration::from_secs(interval),
+                    discover_prefix.as_deref(),
+                    sink.as_mut(),
+                )
+                .await?
+            }
+        },
+        Commands::Serve { port } => cmd_serve(&app, port).await?,
+        Commands::Tui { no_mouse } => tui::run_tui(app, !no_mouse).await?,
+    }
+    Ok(())
+}
+
+/// Map the --overwrite-build / --keep-local flags (mutually exclusive per
+/// clap) onto the build policy.
+fn build_overwrite_policy(overwrite_build: bool, keep_local: bool) -> BuildOverwrite {
+    match (overwrite_build, keep_local) {
+        (true, _) => BuildOverwrite::Force,
+        (_, true) => BuildOverwrite::KeepLocal,
+        _ => BuildOverwrite::Refuse,
+    }
+}
+
+/// Serve `App::export_metrics_prometheus` on every request to `port`.
+/// One connection at a time is plenty for a scraper.
+async fn cmd_serve(app: &App, port: u16) -> Result<()> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", port))
+        .await
+        .with_context(|| format!("failed to listen on port {port}"))?;
+    println!("Serving metrics on http://0.0.0.0:{port}/metrics (Ctrl-C to stop)");
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        // Drain the request line and headers; the path does not matter.
+        let mut buf = [0u8; 4096];
+        let _ = stream.read(&mut buf).await;
+        let response = match app.export_metrics_prometheus().await {
+            Ok(body) => format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            ),
+            Err(err) => {
+                let body = format!("metrics unavailable: {err}\n");
+                format!(
+                    "HTTP/1.1 500 Internal Server Error\r\nContent-Type: text/plain\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+            }
+        };
+        let _ = stream.write_all(response.as_bytes()).await;
+    }
+}
+
+/// Diagnostics go to stderr so scripts parsing stdout are unaffected;
+/// default level is warn unless RUST_LOG or --log-level says otherwise.
+fn init_tracing(log_level: Option<&str>, format: LogFormat) {
+    let filter = match std::env::var("RUST_LOG") {
+        Ok(value) => tracing_subscriber::EnvFilter::new(value),
+        Err(_) => tracing_subscriber::EnvFilter::new(log_level.unwrap_or("warn")),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    match format {
+        LogFormat::Json => builder.json().init(),
+        LogFormat::Text => builder.init(),
+    }
+}
+
+/// Merge `--env`/`--env-file` pairs into the stored config before starting.
+fn apply_env_flags(app: &App, env: &[String], env_file: Option<&std::path::Path>) -> Result<()> {
+    if env.is_empty() && env_file.is_none() {
+        return Ok(());
+    }
+    let mut pairs = Vec::new();
+    if let Some(path) = env_file {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read env file {}", path.display()))?;
+        pairs.extend(config::parse_env_file(&contents)?);
+    }
+    for pair in env {
+        pairs.push(config::parse_env_pair(pair)?);
+    }
+    app.config_manager()
+        .mutate(|cfg| cfg.merge_proxy_env(pairs))?;
+    Ok(())
+}
+
+fn print_lines(lines: &[String]) {
+    for line in lines {
+        println!("{line}");
+    }
+}
+
+/// Parse a `--canary` value of the form "container:percent".
+fn parse_canary_spec(spec: &str) -> Result<(String, u8)> {
+    let Some((target, percent)) = spec.rsplit_once(':') else {
+        anyhow::bail!("invalid canary spec '{spec}'; expected 'container:percent'");
+    };
+    let percent: u8 = percent
+        .parse()
+        .with_context(|| format!("invalid canary percentage '{percent}'"))?;
+    Ok((target.to_string(), percent))
+}
+
+fn cmd_list(app: &App, tag: Option<&str>, sort: Option<SortBy>, reverse: bool) -> Result<()> {
+    let config = app.config_manager().get().clone();
+    // Display-only sorting; the stored config keeps insertion order.
+    let mut containers: Vec<&config::Container> = config.containers.iter().collect();
+    if let Some(sort) = sort {
+        match sort {
+            SortBy::Name => containers.sort_by_key(|c| c.name.clone()),
+            // Container port, with the first routed host port breaking ties.
+            SortBy::Port => {
+                containers.sort_by_key(|c| {
+                    (
+                        c.port,
+                        config
+                            .routes_targeting(&c.name)
+                            .first()
+                            .copied()
+                            .unwrap_or(u16::MAX),
+                    )
+                });
+            }
+            SortBy::Network => containers
+                .sort_by_key(|c| c.network.clone().unwrap_or_else(|| config.network.clone())),
+        }
+        if reverse {
+            containers.reverse();
+        }
+    }
+    if containers.is_empty() {
+        println!("No containers configured");
+    } else {
+        println!("Containers:");
+        for container in containers {
+            // Label and aliases share the parenthesised identifier list.
+            let mut identifiers: Vec<&str> = container.label.as_deref().into_iter().collect();
+            identifiers.extend(container.aliases.iter().map(String::as_str));
+            let label = if identifiers.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", identifiers.join(", "))
+            };
+            let network = container
+                .network
+                .as_deref()
+                .unwrap_or(config.network.as_str());
+            let note = container
+                .description
+                .as_deref()
+                .map(|d| format!("  — {d}"))
+                .unwrap_or_default();
+            println!(
+                "  {}{label}  port {}  network {network}{note}",
+                container.name, container.port
+            );
+        }
+    }
+    let mut routes: Vec<_> = config
+        .routes
+        .iter()
+        .filter(|r| tag.is_none_or(|t| r.has_tag(t)))
+        .collect();
+    if let Some(sort) = sort {
+        match sort {
+            SortBy::Name => routes.sort_by_key(|r| r.target.clone()),
+            SortBy::Port => routes.sort_by_key(|r| r.primary_port()),
+            SortBy::Network => routes.sort_by_key(|r| {
+                config
+                    .find_container(&r.target)
+                    .and_then(|c| c.network.clone())
+                    .unwrap_or_else(|| config.network.clone())
+            }),
+        }
+        if reverse {
+            routes.reverse();
+        }
+    }
+    if routes.is_empty() {
+        match tag {
+            Some(tag) => println!("No routes carry tag '{tag}'"),
+            None => println!("No routes configured"),
+        }
+    } else {
+        println!("Routes:");
+        for route in routes {
+            let tags = if route.tags.is_empty() {
+                String::new()
+            } else {
+                format!("  [{}]", route.tags.join(", "))
+            };
+            let lock = if route.basic_auth.is_some() {
+                "  [locked]"
+            } else {
+                ""
+            };
+            let note = route
+                .description
+                .as_deref()
+                .map(|d| format!("  — {d}"))
+                .unwrap_or_default();
+            if route.is_static() {
+                println!("  {} -> [static]{lock}{tags}{note}", route.ports_display());
+            } else {
+                println!(
+                    "  {} -> {}:{}{lock}{tags}{note}",
+                    route.ports_display(),
+                    route.target,
+                    route.internal_port
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn cmd_status(app: &App, format: StatusFormat) -> Result<()> {
+    match format {
+        StatusFormat::Text => {}
+        StatusFormat::Markdown => {
+            print!("{}", render_status_markdown(&app.status_info().await?));
+            return Ok(());
+        }
+        StatusFormat::Html => {
+            print!("{}", render_status_html(&app.status_info().await?));
+            return Ok(());
+        }
+    }
+    let config = app.config_manager().get().clone();
+    let proxy_status = app
+        .docker()
+        .get_container_status(&config.proxy_name)
+        .await?;
+    println!(
+        "Proxy '{}': {}",
+        config.proxy_name,
+        proxy_status.as_deref().unwrap_or("not running")
+    );
+    println!("{}", config.count_stats().summary());
+    for route in &config.routes {
+        let unbound = if route.unbound { "  (unbound)" } else { "" };
+        let note = route
+            .description
+            .as_deref()
+            .map(|d| format!("  — {d}"))
+            .unwrap_or_default();
+        if route.is_static() {
+            println!("  {} -> [static]{unbound}{note}", route.ports_display());
+            continue;
+        }
+        let backend_status = app.docker().get_container_status(&route.target).await?;
+        println!(
+            "  {} -> {}:{}  [{}]{unbound}{note}",
+            route.ports_display(),
+            route.target,
+            route.internal_port,
+            backend_status.as_deref().unwrap_or("not found")
+        );
+    }
+    print_lines(&app.proxy_static_ip_report().await?);
+    print_lines(&app.port_drift_warnings().await?);
+    Ok(())
+}
+
+async fn cmd_detect(app: &App, json: bool) -> Result<()> {
+    let detected = app.detect_full().await?;
+    if json {
+        let entries: Vec<serde_json::Value> = detected
+            .iter()
+            .map(|c| {
+                serde_json::json!({
+                    "name": c.name,
+                    "image": c.image,
+                    "status": c.status,
+                    "network": c.networks.first(),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+    } else if detected.is_empty() {
+        println!("No unconfigured running containers found");
+    } else {
+        for info in detected {
+            println!("{}", info.name);
+        }
+    }
+    Ok(())
+}
+
+async fn cmd_networks(app: &App, containers: bool) -> Result<()> {
+    let networks = app.docker().list_networks().await?;
+    let config = app.config_manager().get().clone();
+    print_lines(&proxy_manager::app::network_listing(
+        &networks, &config, containers,
+    ));
+    Ok(())
+}
+
+const HOSTS_FILE: &str = "/etc/hosts";
+
+async fn cmd_capture_config(app: &App, live: bool) -> Result<()> {
+    if live {
+        let config = app.config_manager().get().clone().interpolated()?;
+        if !app.docker().container_running(&config.proxy_name).await? {
+            anyhow::bail!(
+                "proxy '{}' is not running; drop --live to see the generated config",
+                config.proxy_name
+            );
+        }
+    }
+    print!("{}", app.manager().capture_config().await?);
+    Ok(())
+}
+
+async fn cmd_test(
+    app: &App,
+    port: u16,
+    path: Option<&str>,
+    expect_status: Option<u16>,
+) -> Result<()> {
+    let path = path.unwrap_or("/");
+    let path = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{path}")
+    };
+    let url = format!("http://127.0.0.1:{port}{path}");
+    let client = probe::ReqwestClient::new()?;
+    let started = std::time::Instant::now();
+    let (response, redirects) = probe::probe(&client, &url).await?;
+    let elapsed = started.elapsed();
+    let config = app.config_manager().get().clone();
+    print_lines(&probe::render_report(
+        &config, port, &response, redirects, elapsed,
+    ));
+    if let Some(expected) = expect_status {
+        if response.status != expected {
+            anyhow::bail!("expected status {expected}, got {}", response.status);
+        }
+    }
+    Ok(())
+}
+
+async fn cmd_check_network(app: &App, verbose: bool) -> Result<()> {
+    let results = app.network_connectivity_check().await?;
+    if results.is_empty() {
+        println!("No containers configured");
+        return Ok(());
+    }
+    let mut unreachable = 0;
+    for result in &results {
+        if result.reachable {
+            match (verbose, result.latency_ms) {
+                (true, Some(ms)) => {
+                    println!(
+                        "  {} ({}): ok ({ms} ms)",
+                        result.container_name, result.address
+                    )
+                }
+                _ => println!("  {} ({}): ok", result.container_name, result.address),
+            }
+        } else {
+            unreachable += 1;
+            println!(
+                "  {} ({}): unreachable",
+                result.container_name, result.address
+            );
+        }
+    }
+    if unreachable > 0 {
+        anyhow::bail!("{unreachable} container(s) unreachable from the proxy");
+    }
+    Ok(())
+}
+
+fn cmd_daemon_status(app: &App) -> Result<()> {
+    let path = daemon::status_path(app.store().config_dir());
+    match daemon::read_status(&path)? {
+        None => println!("No daemon has run yet"),
+        Some(status) => {
+            if status.alive() {
+                println!("Daemon running (pid {})", status.pid);
+            } else {
+                println!("Daemon not running (last pid {})", status.pid);
+            }
+            println!("Started: {}", status.started_at);
+            match (&status.last_action, &status.last_action_at) {
+                (Some(action), Some(at)) => println!("Last action: {action} at {at}"),
+                _ => println!("Last action: none"),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn cmd_hosts_sync(app: &App, tld: &str, output: Option<&std::path::Path>) -> Result<()> {
+    let config = app.config_manager().get().clone().interpolated()?;
+    let hosts = hosts::dev_hosts(&config, tld);
+    if hosts.is_empty() {
+        println!("No routes with a '{tld}' hostname; nothing to sync");
+        return Ok(());
+    }
+    let block = hosts::render_block(&hosts);
+    let path = output.unwrap_or_else(|| std::path::Path::new(HOSTS_FILE));
+    let existing = std::fs::read_to_string(path).unwrap_or_default();
+    let merged = hosts::merge_block(&existing, &block);
+    match std::fs::write(path, &merged) {
+        Ok(()) => println!("Synced {} host(s) to {}", hosts.len(), path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            println!(
+                "{} is not writable; append this block yourself or rerun with sudo:\n\n{block}",
+                path.display()
+            );
+        }
+        Err(e) => {
+            return Err(e).with_context(|| format!("failed to write {}", path.display()));
+        }
+    }
+    Ok(())
+}
+
+fn cmd_hosts_clean(output: Option<&std::path::Path>) -> Result<()> {
+    let path = output.unwrap_or_else(|| std::path::Path::new(HOSTS_FILE));
+    let existing = std::fs::read_to_string(path).unwrap_or_default();
+    let cleaned = hosts::remove_block(&existing);
+    if cleaned == existing {
+        println!("No managed block in {}", path.display());
+        return Ok(());
+    }
+    std::fs::write(path, cleaned).with_context(|| format!("failed to write {}", path.display()))?;
+    println!("Removed managed block from {}", path.display());
+    Ok(())
+}
+
+async fn cmd_logs(app: &App, tail: u32, grep: Option<&str>, since: Option<&str>) -> Result<()> {
+    // Compile up front so a bad pattern fails before we touch Docker.
+    let filter = grep
+        .map(regex::Regex::new)
+        .transpose()
+        .context("invalid --grep pattern")?;
+    let since = since
+        .map(|s| proxy_manager::docker::parse_since(s, chrono::Utc::now()))
+        .transpose()
+        .context("invalid --since value")?;
+    let config = app.config_manager().get().clone();
+    let (tail, clamped) = clamp_log_tail(tail, config.log_tail_limit);
+    if let Some(notice) = clamped {
+        eprintln!("{notice}");
+    }
+    for line in app
+        .docker()
+        .get_logs(&config.proxy_name, Some(tail), since)
+        .await?
+    {
+        if filter.as_ref().is_none_or(|re| re.is_match(&line)) {
+            println!("{line}");
+        }
+    }
+    Ok(())
+}
+
+fn cmd_config(app: &App, json: bool, raw: bool) -> Result<()> {
+    let config = app.config_manager().get().clone();
+    let config = if raw { config } else { config.interpolated()? };
+    if json {
+        // Canonical form straight to stdout: stable ordering, trailing
+        // newline, nothing extra, so the output pipes cleanly.
+        config.to_writer(std::io::stdout().lock())?;
+    } else {
+        println!("Config file: {}", app.store().config_file().display());
+        println!("Proxy name:  {}", config.proxy_name);
+        println!("Network:     {}", config.network);
+        println!("Base image:  {}", config.base_image);
+        println!(
+            "{} container(s), {} route(s)",
+            config.containers.len(),
+            config.routes.len()
+        );
+    }
+    Ok(())
+}
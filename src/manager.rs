@@ -0,0 +1,2044 @@
+//! Proxy container lifecycle: build files, image builds, start/stop/reload,
+//! and last-good recovery snapshots.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::Instrument;
+
+use crate::caddy::CaddyfileGenerator;
+use crate::config::{Config, ConfigManager, ProxyBackend};
+use crate::docker::{DockerApi, CONFIG_HASH_LABEL};
+use crate::nginx::NginxConfigGenerator;
+use crate::probe::{HttpClient, ReqwestClient};
+use sha2::{Digest, Sha256};
+
+/// Subdirectory of the build dir holding the last successfully deployed
+/// build files, used by `recover`.
+const LAST_GOOD_DIR: &str = "last-good";
+
+/// Sidecar metadata stored next to the last-good build files.
+#[derive(Debug, Serialize, Deserialize)]
+struct LastGoodMeta {
+    host_ports: Vec<u16>,
+    proxy_name: String,
+    network: String,
+    #[serde(default)]
+    proxy_env: Vec<(String, String)>,
+    #[serde(default)]
+    config_hash: String,
+    /// Proxy config file in the snapshot ("nginx.conf" or "Caddyfile");
+    /// snapshots from before Caddy support predate the field.
+    #[serde(default = "default_snapshot_conf")]
+    conf_name: String,
+}
+
+fn default_snapshot_conf() -> String {
+    "nginx.conf".to_string()
+}
+
+/// Labels applied to the proxy container: the configured discovery labels
+/// plus the config hash.
+fn proxy_labels(config: &Config, hash: String) -> Vec<(String, String)> {
+    let mut labels: Vec<(String, String)> = config
+        .proxy_labels
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    labels.sort();
+    labels.push((CONFIG_HASH_LABEL.to_string(), hash));
+    labels
+}
+
+/// Hash identifying a config's deployed shape, stamped on the proxy
+/// container as a label so later invocations can tell whether the running
+/// proxy matches their config.
+pub(crate) fn config_hash(config: &Config) -> String {
+    let serialized = serde_json::to_string(config).unwrap_or_default();
+    format!("{:x}", Sha256::digest(serialized.as_bytes()))
+}
+
+/// How [`ProxyManager::write_build_files`] treats build files edited by
+/// hand since the last generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BuildOverwrite {
+    /// Refuse to overwrite an edited file (the default).
+    #[default]
+    Refuse,
+    /// Regenerate everything, discarding local edits (`--overwrite-build`).
+    Force,
+    /// Build with the edited files as-is (`--keep-local`).
+    KeepLocal,
+}
+
+/// Digests of the build files we last generated, stored alongside them so
+/// hand edits can be detected before they are clobbered.
+const BUILD_MANIFEST_FILE: &str = ".manifest.json";
+
+fn load_build_manifest(build_dir: &Path) -> std::collections::HashMap<String, String> {
+    std::fs::read_to_string(build_dir.join(BUILD_MANIFEST_FILE))
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save_build_manifest(
+    build_dir: &Path,
+    manifest: &std::collections::HashMap<String, String>,
+) -> Result<()> {
+    let path = build_dir.join(BUILD_MANIFEST_FILE);
+    std::fs::write(&path, serde_json::to_string_pretty(manifest)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+fn content_digest(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Outcome of [`ProxyManager::simulate_failover`]: one message per probed
+/// route plus whether every one of them served the 503 fallback.
+#[derive(Debug, Clone)]
+pub struct FailoverReport {
+    pub routes_tested: usize,
+    pub all_returned_503: bool,
+    pub messages: Vec<String>,
+}
+
+/// How often [`ProxyManager::wait_for_healthy`] re-checks the proxy.
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Drives the proxy container: generates build files, builds the image and
+/// manages the container through a [`DockerApi`].
+#[derive(Clone)]
+pub struct ProxyManager {
+    docker: Arc<dyn DockerApi>,
+    config: Arc<ConfigManager>,
+    build_overwrite: BuildOverwrite,
+}
+
+impl ProxyManager {
+    pub fn new(docker: Arc<dyn DockerApi>, config: Arc<ConfigManager>) -> Self {
+        Self {
+            docker,
+            config,
+            build_overwrite: BuildOverwrite::default(),
+        }
+    }
+
+    /// Choose how hand-edited build files are handled on the next
+    /// [`ProxyManager::write_build_files`].
+    pub fn set_build_overwrite(&mut self, policy: BuildOverwrite) {
+        self.build_overwrite = policy;
+    }
+
+    /// Write the proxy config (`nginx.conf` or `Caddyfile`, per
+    /// `proxy_backend`) and `Dockerfile` into the build directory and
+    /// return their paths. Static route directories are copied into the
+    /// build context here, after checking they exist and fit the size limit.
+    pub fn write_build_files(&self, config: &Config) -> Result<(PathBuf, PathBuf)> {
+        let build_dir = self.config.store().build_dir();
+        std::fs::create_dir_all(&build_dir)
+            .with_context(|| format!("failed to create {}", build_dir.display()))?;
+        let (conf_name, conf_body, dockerfile_body) = match config.proxy_backend {
+            ProxyBackend::Nginx => (
+                "nginx.conf",
+                NginxConfigGenerator::generate(config),
+                NginxConfigGenerator::generate_dockerfile(config),
+            ),
+            ProxyBackend::Caddy => (
+                "Caddyfile",
+                CaddyfileGenerator::generate(config),
+                CaddyfileGenerator::generate_dockerfile(config),
+            ),
+        };
+        let mut manifest = load_build_manifest(&build_dir);
+        for (name, body) in [(conf_name, &conf_body), ("Dockerfile", &dockerfile_body)] {
+            let path = build_dir.join(name);
+            // A file differing from what we last wrote was edited by hand;
+            // files without a manifest entry (pre-manifest build dirs,
+            // fresh checkouts) are overwritten as before.
+            let edited = match (std::fs::read_to_string(&path).ok(), manifest.get(name)) {
+                (Some(on_disk), Some(last)) => content_digest(&on_disk) != *last,
+                _ => false,
+            };
+            if edited {
+                match self.build_overwrite {
+                    BuildOverwrite::Refuse => bail!(
+                        "{} was edited since the last generation; diff it against \
+                         `proxy-manager generate` output, then rerun with --overwrite-build \
+                         to regenerate or --keep-local to build with your edits",
+                        path.display()
+                    ),
+                    // The manifest keeps the last generated digest so the
+                    // guard still fires on future runs.
+                    BuildOverwrite::KeepLocal => continue,
+                    BuildOverwrite::Force => {}
+                }
+            }
+            std::fs::write(&path, body)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            manifest.insert(name.to_string(), content_digest(body));
+        }
+        save_build_manifest(&build_dir, &manifest)?;
+        let conf_path = build_dir.join(conf_name);
+        let dockerfile_path = build_dir.join("Dockerfile");
+        self.stage_static_dirs(config, &build_dir)?;
+        stage_htpasswd_files(config, &build_dir)?;
+        stage_ca_files(config, &build_dir)?;
+        Ok((conf_path, dockerfile_path))
+    }
+
+    /// Copy every static route's directory into the build context as
+    /// `static_<port>`, enforcing the configured size limit.
+    fn stage_static_dirs(&self, config: &Config, build_dir: &Path) -> Result<()> {
+        let limit = config.static_size_limit_mb * 1024 * 1024;
+        for route in config.routes.iter().filter(|r| !r.unbound) {
+            let Some(dir) = &route.static_dir else {
+                continue;
+            };
+            if !dir.is_dir() {
+                bail!(
+                    "static route {}: '{}' is not a directory",
+                    route.primary_port(),
+                    dir.display()
+                );
+            }
+            let size = dir_size(dir)?;
+            if size > limit {
+                bail!(
+                    "static route {}: '{}' is {} bytes, over the {} MB limit \
+                     (raise static_size_limit_mb in the config to allow it)",
+                    route.primary_port(),
+                    dir.display(),
+                    size,
+                    config.static_size_limit_mb
+                );
+            }
+            let staged = build_dir.join(format!("static_{}", route.primary_port()));
+            if staged.exists() {
+                std::fs::remove_dir_all(&staged)
+                    .with_context(|| format!("failed to clear {}", staged.display()))?;
+            }
+            copy_dir(dir, &staged)?;
+        }
+        Ok(())
+    }
+
+    /// Tar the build directory and build the proxy image from it.
+    pub async fn build_proxy_image(&self, config: &Config) -> Result<()> {
+        let build_dir = self.config.store().build_dir();
+        let tarball = tar_build_context(&build_context_entries(config, &build_dir)?)?;
+        self.docker.build_image(&config.proxy_name, tarball).await
+    }
+
+    /// Full start: validate, ensure networks, write build files, build the
+    /// image, run the container and connect it to every required network.
+    /// Returns human-readable progress lines.
+    pub async fn start_proxy(&self, config: &Config) -> Result<Vec<String>> {
+        let config = &config.interpolated()?;
+        config.validate()?;
+        let mut output = Vec::new();
+
+        for route in config.routes.iter().filter(|r| !r.unbound) {
+            if route.backend_scheme == crate::config::BackendScheme::Https
+                && !route.verify_backend_tls
+            {
+                output.push(format!(
+                    "Warning: backend TLS verification is DISABLED for route {}; \
+                     the upstream certificate will not be checked",
+                    route.primary_port()
+                ));
+            }
+        }
+
+        for network in config.all_networks() {
+            // External networks are joined, never created: silently
+            // recreating one would lose its DNS aliases and addressing.
+            if config.external_networks.contains(&network) {
+                let exists = self
+                    .docker
+                    .list_networks()
+                    .await?
+                    .iter()
+                    .any(|n| n.name == network);
+                if !exists {
+                    bail!(
+                        "external network '{network}' does not exist; create it first or \
+                         remove it from external_networks"
+                    );
+                }
+                continue;
+            }
+            let span = tracing::info_span!("network_ensure", network = %network);
+            if self
+                .docker
+                .ensure_network(&network, None)
+                .instrument(span)
+                .await?
+            {
+                output.push(format!("Created network '{network}'"));
+            }
+        }
+
+        // Static IP pins must be sane before anything gets built or run.
+        let mut pins: Vec<(&String, &std::net::IpAddr)> = config.proxy_static_ips.iter().collect();
+        pins.sort();
+        for (network, ip) in &pins {
+            if !config.all_networks().contains(network)
+                && !config.external_networks.contains(network)
+                && !config.connect_all_networks
+            {
+                bail!("proxy_static_ips pins unknown network '{network}'");
+            }
+            let Some(subnet) = self.docker.network_subnet(network).await? else {
+                bail!("network '{network}' has no subnet to validate static IP {ip} against");
+            };
+            if !ip_in_subnet(**ip, &subnet)? {
+                bail!("static IP {ip} is outside network '{network}' subnet {subnet}");
+            }
+        }
+
+        self.write_build_files(config)?;
+        output.push("Generated nginx.conf and Dockerfile".to_string());
+
+        let span = tracing::info_span!("image_build", duration_ms = tracing::field::Empty);
+        let started = std::time::Instant::now();
+        self.build_proxy_image(config)
+            .instrument(span.clone())
+            .await?;
+        span.record("duration_ms", started.elapsed().as_millis() as u64);
+        output.push(format!("Built image '{}:latest'", config.proxy_name));
+
+        let mut host_ports = config.host_ports();
+        if let Some(port) = config.management_port {
+            if !host_ports.contains(&port) {
+                host_ports.push(port);
+            }
+        }
+        let span = tracing::info_span!("container_run", duration_ms = tracing::field::Empty);
+        let started = std::time::Instant::now();
+        self.docker
+            .run_container_with_ports(
+                &config.proxy_name,
+                &format!("{}:latest", config.proxy_name),
+                &config.network,
+                &host_ports,
+                &config.proxy_env,
+                &proxy_labels(config, config_hash(config)),
+            )
+            .instrument(span.clone())
+            .await?;
+        span.record("duration_ms", started.elapsed().as_millis() as u64);
+        output.push(format!(
+            "Started proxy '{}' on port(s) {}",
+            config.proxy_name,
+            format_ports(&host_ports)
+        ));
+
+        for network in config.all_networks() {
+            let static_ip = config.proxy_static_ips.get(&network).copied();
+            if network == config.network {
+                // `run` already joined the default network with a dynamic
+                // address; reattach when a pin asks for a fixed one.
+                if let Some(ip) = static_ip {
+                    self.docker
+                        .disconnect_container_from_network(&config.proxy_name, &network)
+                        .await?;
+                    self.docker
+                        .connect_container_to_network(&config.proxy_name, &network, Some(ip))
+                        .await?;
+                    output.push(format!("Pinned proxy to {ip} on network '{network}'"));
+                }
+                continue;
+            }
+            self.docker
+                .connect_container_to_network(&config.proxy_name, &network, static_ip)
+                .await?;
+            match static_ip {
+                Some(ip) => output.push(format!("Connected proxy to network '{network}' at {ip}")),
+                None => output.push(format!("Connected proxy to network '{network}'")),
+            }
+        }
+
+        // Backend DNS aliases: reattach each container that wants them so
+        // Docker's resolver serves the alias on that network.
+        for container in &config.containers {
+            if container.dns_aliases.is_empty() {
+                continue;
+            }
+            let network = container.network.as_deref().unwrap_or(&config.network);
+            self.docker
+                .disconnect_container_from_network(&container.name, network)
+                .await?;
+            self.docker
+                .connect_network_with_alias(&container.name, network, &container.dns_aliases)
+                .await?;
+            output.push(format!(
+                "Attached '{}' to '{network}' with alias(es) {}",
+                container.name,
+                container.dns_aliases.join(", ")
+            ));
+        }
+
+        if config.connect_all_networks {
+            let joined = config.all_networks();
+            let mut extra = 0;
+            for network in self.docker.list_networks().await? {
+                if network.driver != "bridge"
+                    || network.name == "bridge"
+                    || joined.contains(&network.name)
+                {
+                    continue;
+                }
+                let static_ip = config.proxy_static_ips.get(&network.name).copied();
+                self.docker
+                    .connect_container_to_network(&config.proxy_name, &network.name, static_ip)
+                    .await?;
+                output.push(format!("Connected proxy to network '{}'", network.name));
+                extra += 1;
+            }
+            if extra > 0 {
+                output.push(format!(
+                    "Warning: connect_all_networks joined {extra} extra network(s); \
+                     the proxy can now reach every container on them"
+                ));
+            }
+        }
+
+        if config.connect_backends {
+            output.extend(self.connect_backend_containers(config).await?);
+        }
+
+        // Only after everything succeeded: remember this deployment so
+        // `recover` can restore it if a later reload breaks halfway.
+        self.snapshot_last_good(config)?;
+
+        Ok(output)
+    }
+
+    /// Simulate a backend outage for every route targeting `identifier`:
+    /// pause the container (what a crashed backend looks like to the
+    /// proxy), probe each routed host port and report whether the 503
+    /// fallback page answered, then resume the container.
+    pub async fn simulate_failover(&self, identifier: &str) -> Result<FailoverReport> {
+        let client = ReqwestClient::new()?;
+        self.simulate_failover_with(&client, identifier).await
+    }
+
+    async fn simulate_failover_with(
+        &self,
+        client: &dyn HttpClient,
+        identifier: &str,
+    ) -> Result<FailoverReport> {
+        let config = self.config.get().clone().interpolated()?;
+        let Some(container) = config.find_container(identifier) else {
+            bail!("no configured container matches '{identifier}'");
+        };
+        let name = container.name.clone();
+        let ports = config.routes_targeting(&name);
+        if ports.is_empty() {
+            bail!("no routes target '{name}'; nothing to fail over");
+        }
+        self.docker.pause_container(&name).await?;
+        let mut messages = Vec::new();
+        let mut all_returned_503 = true;
+        for port in &ports {
+            match client.get(&format!("http://127.0.0.1:{port}/")).await {
+                Ok(response) => {
+                    let fallback = response.body.contains(crate::nginx::FALLBACK_MESSAGE)
+                        || config
+                            .maintenance_message
+                            .as_deref()
+                            .is_some_and(|m| response.body.contains(m));
+                    if response.status != 503 {
+                        all_returned_503 = false;
+                    }
+                    messages.push(format!(
+                        "Route {port}: HTTP {}{}",
+                        response.status,
+                        if fallback { " (fallback page)" } else { "" }
+                    ));
+                }
+                Err(e) => {
+                    all_returned_503 = false;
+                    messages.push(format!("Route {port}: request failed: {e:#}"));
+                }
+            }
+        }
+        self.docker.unpause_container(&name).await?;
+        Ok(FailoverReport {
+            routes_tested: ports.len(),
+            all_returned_503,
+            messages,
+        })
+    }
+
+    /// Block until the freshly started proxy answers. With
+    /// `management_port` set this polls the stub_status page until it
+    /// returns 200; otherwise it falls back to the container status, which
+    /// only proves the process is up.
+    pub async fn wait_for_healthy(&self, timeout: Duration) -> Result<()> {
+        let config = self.config.get().clone().interpolated()?;
+        match config.management_port {
+            Some(port) => {
+                let client = ReqwestClient::new()?;
+                self.wait_for_status_page(&client, port, timeout).await
+            }
+            None => self.wait_for_running(&config.proxy_name, timeout).await,
+        }
+    }
+
+    async fn wait_for_status_page(
+        &self,
+        client: &dyn HttpClient,
+        port: u16,
+        timeout: Duration,
+    ) -> Result<()> {
+        let url = format!("http://localhost:{port}/nginx_status");
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Ok(response) = client.get(&url).await {
+                if response.status == 200 {
+                    return Ok(());
+                }
+            }
+            if Instant::now() >= deadline {
+                bail!("proxy did not answer on {url} within {:?}", timeout);
+            }
+            tokio::time::sleep(HEALTH_POLL_INTERVAL).await;
+        }
+    }
+
+    async fn wait_for_running(&self, proxy_name: &str, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let status = self.docker.get_container_status(proxy_name).await?;
+            if status.as_deref() == Some("running") {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                bail!(
+                    "proxy '{proxy_name}' did not reach 'running' within {:?}",
+                    timeout
+                );
+            }
+            tokio::time::sleep(HEALTH_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Deploy an in-memory config: stop whatever proxy is running, then
+    /// build and start it from `config`, never reading or writing the
+    /// on-disk config file. Embedders can point the [`ConfigManager`] at
+    /// any scratch directory; only the build context lives there.
+    pub async fn apply(&self, config: &Config) -> Result<()> {
+        let interpolated = config.interpolated()?;
+        self.docker
+            .stop_and_remove_container(&interpolated.proxy_name)
+            .await?;
+        self.start_proxy(config).await?;
+        Ok(())
+    }
+
+    /// Connect each bound route's target that shares no network with the
+    /// proxy to the proxy's default network, recording every connection so
+    /// `stop --disconnect-on-stop` removes exactly those and never touches
+    /// networks the user attached manually.
+    async fn connect_backend_containers(&self, config: &Config) -> Result<Vec<String>> {
+        let mut output = Vec::new();
+        let existing = self.docker.list_containers(true).await?;
+        let proxy_networks = config.all_networks();
+        let path = self.config.store().connected_backends_file();
+        let mut recorded = load_connected_backends(&path);
+        let mut seen: Vec<&str> = Vec::new();
+        for route in config
+            .routes
+            .iter()
+            .filter(|r| !r.unbound && !r.is_static())
+        {
+            let Some(container) = config.find_container(&route.target) else {
+                continue;
+            };
+            if seen.contains(&container.name.as_str()) {
+                continue;
+            }
+            seen.push(&container.name);
+            let Some(info) = existing.iter().find(|c| c.name == container.name) else {
+                output.push(format!(
+                    "Warning: container '{}' does not exist; cannot connect it to '{}'",
+                    container.name, config.network
+                ));
+                continue;
+            };
+            if info.networks.iter().any(|n| proxy_networks.contains(n)) {
+                continue;
+            }
+            self.docker
+                .connect_container_to_network(&container.name, &config.network, None)
+                .await?;
+            output.push(format!(
+                "Connected backend '{}' to network '{}'",
+                container.name, config.network
+            ));
+            let record = (container.name.clone(), config.network.clone());
+            if !recorded.contains(&record) {
+                recorded.push(record);
+            }
+        }
+        save_connected_backends(&path, &recorded)?;
+        Ok(output)
+    }
+
+    /// Undo the network connections recorded by `connect_backends` and
+    /// clear the record; manually attached networks stay untouched.
+    pub async fn disconnect_recorded_backends(&self) -> Result<Vec<String>> {
+        let path = self.config.store().connected_backends_file();
+        let recorded = load_connected_backends(&path);
+        if recorded.is_empty() {
+            return Ok(vec!["No recorded backend connections to remove".to_string()]);
+        }
+        let mut output = Vec::new();
+        for (container, network) in &recorded {
+            self.docker
+                .disconnect_container_from_network(container, network)
+                .await?;
+            output.push(format!(
+                "Disconnected backend '{container}' from network '{network}'"
+            ));
+        }
+        save_connected_backends(&path, &[])?;
+        Ok(output)
+    }
+
+    /// Stop and remove the proxy container.
+    pub async fn stop_proxy(&self, config: &Config) -> Result<Vec<String>> {
+        let config = &config.interpolated()?;
+        let span = tracing::info_span!("container_stop", duration_ms = tracing::field::Empty);
+        let started = std::time::Instant::now();
+        self.docker
+            .stop_and_remove_container(&config.proxy_name)
+            .instrument(span.clone())
+            .await?;
+        span.record("duration_ms", started.elapsed().as_millis() as u64);
+        Ok(vec![format!("Stopped proxy '{}'", config.proxy_name)])
+    }
+
+    /// Stop then start the proxy. When the start phase fails after a
+    /// successful stop the error is tagged so callers can offer (or
+    /// automatically run, with `auto_recover`) recovery from the last-good
+    /// snapshot.
+    pub async fn reload(&self, config: &Config, auto_recover: bool) -> Result<Vec<String>> {
+        let mut output = self.stop_proxy(config).await?;
+        match self.start_proxy(config).await {
+            Ok(lines) => {
+                output.extend(lines);
+                Ok(output)
+            }
+            Err(start_err) => {
+                if auto_recover && self.has_last_good() {
+                    output.push(format!(
+                        "Start failed after stop ({start_err:#}); recovering from last-good snapshot"
+                    ));
+                    output.extend(self.recover().await?);
+                    Ok(output)
+                } else if self.has_last_good() {
+                    Err(start_err.context(
+                        "proxy was stopped but could not be restarted; \
+                         run 'proxy-manager recover' to restore the last working deployment",
+                    ))
+                } else {
+                    Err(start_err)
+                }
+            }
+        }
+    }
+
+    /// The nginx.conf the running proxy actually uses, read out of the
+    /// container. When the proxy is not running, falls back to what the
+    /// generator would produce now, prefixed with a warning so the output
+    /// cannot be mistaken for live state.
+    pub async fn capture_config(&self) -> Result<String> {
+        let config = self.config.get().clone().interpolated()?;
+        if self.docker.container_running(&config.proxy_name).await? {
+            let bytes = self
+                .docker
+                .copy_file_from_container(&config.proxy_name, "/etc/nginx/nginx.conf")
+                .await?;
+            return String::from_utf8(bytes).context("live nginx.conf is not valid UTF-8");
+        }
+        Ok(format!(
+            "# proxy '{}' is not running; showing the generated config instead\n{}",
+            config.proxy_name,
+            NginxConfigGenerator::generate(&config)
+        ))
+    }
+
+    /// Whether a last-good snapshot exists on disk.
+    pub fn has_last_good(&self) -> bool {
+        self.last_good_dir().join("meta.json").exists()
+    }
+
+    fn last_good_dir(&self) -> PathBuf {
+        self.config.store().build_dir().join(LAST_GOOD_DIR)
+    }
+
+    /// Persist the currently deployed build files and port set. Called only
+    /// after a fully successful `start_proxy`.
+    fn snapshot_last_good(&self, config: &Config) -> Result<()> {
+        let build_dir = self.config.store().build_dir();
+        let dir = self.last_good_dir();
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+        let conf_name = match config.proxy_backend {
+            ProxyBackend::Nginx => "nginx.conf",
+            ProxyBackend::Caddy => "Caddyfile",
+        };
+        std::fs::copy(build_dir.join(conf_name), dir.join(conf_name))?;
+        std::fs::copy(build_dir.join("Dockerfile"), dir.join("Dockerfile"))?;
+        let meta = LastGoodMeta {
+            host_ports: config.host_ports(),
+            proxy_name: config.proxy_name.clone(),
+            network: config.network.clone(),
+            proxy_env: config.proxy_env.clone(),
+            config_hash: config_hash(config),
+            conf_name: conf_name.to_string(),
+        };
+        std::fs::write(dir.join("meta.json"), serde_json::to_string_pretty(&meta)?)?;
+        Ok(())
+    }
+
+    /// Rebuild and start the proxy from the last-good snapshot, ignoring the
+    /// current (possibly broken) configuration.
+    pub async fn recover(&self) -> Result<Vec<String>> {
+        let dir = self.last_good_dir();
+        if !self.has_last_good() {
+            bail!(
+                "no last-good snapshot found in {}; start the proxy successfully once first",
+                dir.display()
+            );
+        }
+        let meta: LastGoodMeta =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("meta.json"))?)
+                .context("failed to parse last-good metadata")?;
+        let tarball = tar_build_context(&[
+            (
+                meta.conf_name.clone(),
+                std::fs::read(dir.join(&meta.conf_name))?,
+            ),
+            (
+                "Dockerfile".to_string(),
+                std::fs::read(dir.join("Dockerfile"))?,
+            ),
+        ])?;
+
+        let mut output = Vec::new();
+        self.docker
+            .stop_and_remove_container(&meta.proxy_name)
+            .await?;
+        self.docker.build_image(&meta.proxy_name, tarball).await?;
+        output.push(format!(
+            "Rebuilt image '{}:latest' from last-good snapshot",
+            meta.proxy_name
+        ));
+        self.docker
+            .run_container_with_ports(
+                &meta.proxy_name,
+                &format!("{}:latest", meta.proxy_name),
+                &meta.network,
+                &meta.host_ports,
+                &meta.proxy_env,
+                &[(CONFIG_HASH_LABEL.to_string(), meta.config_hash.clone())],
+            )
+            .await?;
+        output.push(format!(
+            "Recovered proxy '{}' on port(s) {}",
+            meta.proxy_name,
+            format_ports(&meta.host_ports)
+        ));
+        Ok(output)
+    }
+}
+
+/// Build an in-memory tar archive usable as a Docker build context.
+fn tar_build_context(files: &[(String, Vec<u8>)]) -> Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, data) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_path(name)?;
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, data.as_slice())?;
+    }
+    builder.into_inner().context("failed to build tar context")
+}
+
+/// Files sent to the Docker daemon as the build context: the build files
+/// plus the staged directory of every static route.
+fn build_context_entries(config: &Config, build_dir: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+    let conf_name = match config.proxy_backend {
+        ProxyBackend::Nginx => "nginx.conf",
+        ProxyBackend::Caddy => "Caddyfile",
+    };
+    let mut entries = vec![
+        (
+            conf_name.to_string(),
+            std::fs::read(build_dir.join(conf_name))?,
+        ),
+        (
+            "Dockerfile".to_string(),
+            std::fs::read(build_dir.join("Dockerfile"))?,
+        ),
+    ];
+    for route in config.routes.iter().filter(|r| !r.unbound) {
+        if !route.is_static() {
+            continue;
+        }
+        let prefix = format!("static_{}", route.primary_port());
+        collect_files(&build_dir.join(&prefix), &prefix, &mut entries)?;
+    }
+    for route in config
+        .routes
+        .iter()
+        .filter(|r| r.basic_auth.is_some() && !r.unbound)
+    {
+        let name = format!("htpasswd_{}", route.primary_port());
+        entries.push((name.clone(), std::fs::read(build_dir.join(&name))?));
+    }
+    for route in config
+        .routes
+        .iter()
+        .filter(|r| r.backend_ca_file.is_some() && !r.unbound)
+    {
+        let name = format!("ca_{}.crt", route.primary_port());
+        entries.push((name.clone(), std::fs::read(build_dir.join(&name))?));
+    }
+    for route in config
+        .routes
+        .iter()
+        .filter(|r| r.mtls_ca_cert.is_some() && !r.unbound)
+    {
+        let name = format!("ca_client_{}.crt", route.primary_port());
+        entries.push((name.clone(), std::fs::read(build_dir.join(&name))?));
+    }
+    Ok(entries)
+}
+
+/// Write one htpasswd file per protected route into the build context.
+fn stage_htpasswd_files(config: &Config, build_dir: &Path) -> Result<()> {
+    for route in config.routes.iter().filter(|r| !r.unbound) {
+        let Some(auth) = &route.basic_auth else {
+            continue;
+        };
+        let path = build_dir.join(format!("htpasswd_{}", route.primary_port()));
+        let line = crate::auth::htpasswd_line(&auth.username, &auth.password_hash);
+        std::fs::write(&path, format!("{line}\n"))
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Copy each route's backend and client CA bundles into the build
+/// context as `ca_<port>.crt` / `ca_client_<port>.crt`; validation
+/// already checked the sources exist.
+fn stage_ca_files(config: &Config, build_dir: &Path) -> Result<()> {
+    for route in config.routes.iter().filter(|r| !r.unbound) {
+        if let Some(ca) = &route.backend_ca_file {
+            let path = build_dir.join(format!("ca_{}.crt", route.primary_port()));
+            std::fs::copy(ca, &path).with_context(|| {
+                format!("failed to copy {} to {}", ca.display(), path.display())
+            })?;
+        }
+        if let Some(ca) = &route.mtls_ca_cert {
+            let path = build_dir.join(format!("ca_client_{}.crt", route.primary_port()));
+            std::fs::copy(ca, &path).with_context(|| {
+                format!("failed to copy {} to {}", ca.display(), path.display())
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Read the recorded backend connections; a missing or unreadable file
+/// is an empty record.
+fn load_connected_backends(path: &Path) -> Vec<(String, String)> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+/// Persist the recorded backend connections, removing the file when
+/// nothing is recorded.
+fn save_connected_backends(path: &Path, records: &[(String, String)]) -> Result<()> {
+    if records.is_empty() {
+        if path.exists() {
+            std::fs::remove_file(path)
+                .with_context(|| format!("failed to remove {}", path.display()))?;
+        }
+        return Ok(());
+    }
+    let raw = serde_json::to_string_pretty(records)?;
+    std::fs::write(path, raw).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Recursively read `dir`, appending `(archive path, contents)` pairs.
+fn collect_files(dir: &Path, prefix: &str, out: &mut Vec<(String, Vec<u8>)>) -> Result<()> {
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?
+    {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let archive_path = format!("{prefix}/{name}");
+        if entry.file_type()?.is_dir() {
+            collect_files(&entry.path(), &archive_path, out)?;
+        } else {
+            out.push((archive_path, std::fs::read(entry.path())?));
+        }
+    }
+    Ok(())
+}
+
+/// Total size in bytes of the files under `dir`.
+fn dir_size(dir: &Path) -> Result<u64> {
+    let mut total = 0;
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?
+    {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            total += dir_size(&entry.path())?;
+        } else {
+            total += entry.metadata()?.len();
+        }
+    }
+    Ok(total)
+}
+
+/// Recursively copy `src` into `dst` (created fresh).
+fn copy_dir(src: &Path, dst: &Path) -> Result<()> {
+    std::fs::create_dir_all(dst).with_context(|| format!("failed to create {}", dst.display()))?;
+    for entry in
+        std::fs::read_dir(src).with_context(|| format!("failed to read {}", src.display()))?
+    {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)
+                .with_context(|| format!("failed to copy to {}", target.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether `ip` falls inside the CIDR subnet `cidr` (e.g. "172.18.0.0/16").
+fn ip_in_subnet(ip: std::net::IpAddr, cidr: &str) -> Result<bool> {
+    use std::net::IpAddr;
+    let (base, prefix) = cidr
+        .split_once('/')
+        .with_context(|| format!("subnet '{cidr}' is not in CIDR form"))?;
+    let prefix: u32 = prefix
+        .parse()
+        .with_context(|| format!("subnet '{cidr}' has an invalid prefix length"))?;
+    let base: IpAddr = base
+        .parse()
+        .with_context(|| format!("subnet '{cidr}' has an invalid base address"))?;
+    Ok(match (ip, base) {
+        (IpAddr::V4(ip), IpAddr::V4(base)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix.min(32)).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(base) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(base)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix.min(128)).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(base) & mask
+        }
+        _ => false,
+    })
+}
+
+fn format_ports(ports: &[u16]) -> String {
+    if ports.is_empty() {
+        "none".to_string()
+    } else {
+        ports
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config::Container;
+    use crate::docker::{ContainerInfo, ImageInfo, NetworkInfo};
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// In-memory DockerApi recording calls; individual operations can be
+    /// made to fail for error-path tests.
+    #[derive(Default)]
+    pub(crate) struct FakeDocker {
+        pub calls: Mutex<Vec<String>>,
+        pub fail_build: Mutex<bool>,
+        pub fail_run: Mutex<bool>,
+        pub containers: Mutex<Vec<ContainerInfo>>,
+        pub images: Mutex<Vec<ImageInfo>>,
+        /// Image id reported for any inspected container.
+        pub in_use_image: Mutex<Option<String>>,
+        /// Age reported for any running container.
+        pub age: Mutex<Option<std::time::Duration>>,
+        /// Whether `daemon_info` reports a rootless daemon.
+        pub rootless: Mutex<bool>,
+        /// Networks returned by `list_networks`.
+        pub networks: Mutex<Vec<NetworkInfo>>,
+        /// Attached containers returned by `inspect_network`, keyed by
+        /// network name.
+        pub network_containers: Mutex<Vec<(String, crate::docker::NetworkContainerInfo)>>,
+        /// Subnets returned by `network_subnet`, keyed by network name.
+        pub subnets: Mutex<Vec<(String, String)>>,
+        /// File contents served by `copy_file_from_container`, keyed by
+        /// container-path pairs.
+        pub files: Mutex<Vec<(String, String, Vec<u8>)>>,
+        /// Host ports reported by `container_published_ports`, keyed by
+        /// container name.
+        pub published_ports: Mutex<Vec<(String, u16)>>,
+        /// Labels reported by `container_labels`, keyed by container name;
+        /// `run_container_with_ports` records its labels here too.
+        pub labels: Mutex<Vec<(String, String, String)>>,
+        /// Responses for `exec_in_container`, matched by the command's
+        /// last argument; unmatched commands report exit code 0.
+        pub exec_results: Mutex<Vec<(String, i64, String)>>,
+    }
+
+    impl FakeDocker {
+        pub fn record(&self, call: impl Into<String>) {
+            self.calls.lock().unwrap().push(call.into());
+        }
+
+        pub fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl DockerApi for FakeDocker {
+        async fn list_containers(&self, _all: bool) -> Result<Vec<ContainerInfo>> {
+            self.record("list_containers");
+            Ok(self.containers.lock().unwrap().clone())
+        }
+
+        async fn get_container_status(&self, name: &str) -> Result<Option<String>> {
+            self.record(format!("get_container_status {name}"));
+            Ok(self
+                .containers
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|c| c.name == name)
+                .map(|c| c.status.clone()))
+        }
+
+        async fn ensure_network(
+            &self,
+            name: &str,
+            options: Option<&crate::docker::NetworkOptions>,
+        ) -> Result<bool> {
+            match options {
+                Some(o) => self.record(format!("ensure_network {name} driver={}", o.driver)),
+                None => self.record(format!("ensure_network {name}")),
+            }
+            Ok(false)
+        }
+
+        async fn list_networks(&self) -> Result<Vec<NetworkInfo>> {
+            self.record("list_networks");
+            Ok(self.networks.lock().unwrap().clone())
+        }
+
+        async fn inspect_network(
+            &self,
+            name: &str,
+        ) -> Result<Vec<crate::docker::NetworkContainerInfo>> {
+            self.record(format!("inspect_network {name}"));
+            Ok(self
+                .network_containers
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(net, _)| net == name)
+                .map(|(_, info)| info.clone())
+                .collect())
+        }
+
+        async fn remove_network(&self, name: &str) -> Result<()> {
+            self.record(format!("remove_network {name}"));
+            self.networks.lock().unwrap().retain(|n| n.name != name);
+            Ok(())
+        }
+
+        async fn connect_container_to_network(
+            &self,
+            container: &str,
+            network: &str,
+            static_ip: Option<std::net::IpAddr>,
+        ) -> Result<()> {
+            match static_ip {
+                Some(ip) => self.record(format!("connect {container} {network} ip={ip}")),
+                None => self.record(format!("connect {container} {network}")),
+            }
+            Ok(())
+        }
+
+        async fn connect_network_with_alias(
+            &self,
+            container: &str,
+            network: &str,
+            aliases: &[String],
+        ) -> Result<()> {
+            self.record(format!(
+                "connect_alias {container} {network} aliases={}",
+                aliases.join(",")
+            ));
+            Ok(())
+        }
+
+        async fn disconnect_container_from_network(
+            &self,
+            container: &str,
+            network: &str,
+        ) -> Result<()> {
+            self.record(format!("disconnect {container} {network}"));
+            Ok(())
+        }
+
+        async fn network_subnet(&self, name: &str) -> Result<Option<String>> {
+            self.record(format!("network_subnet {name}"));
+            Ok(self
+                .subnets
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, s)| s.clone()))
+        }
+
+        async fn build_image(&self, tag: &str, _build_context: Vec<u8>) -> Result<()> {
+            self.record(format!("build_image {tag}"));
+            if *self.fail_build.lock().unwrap() {
+                bail!("simulated build failure");
+            }
+            Ok(())
+        }
+
+        async fn run_container_with_ports(
+            &self,
+            name: &str,
+            image: &str,
+            network: &str,
+            host_ports: &[u16],
+            env: &[(String, String)],
+            labels: &[(String, String)],
+        ) -> Result<()> {
+            if env.is_empty() {
+                self.record(format!("run {name} ports={host_ports:?}"));
+            } else {
+                self.record(format!("run {name} ports={host_ports:?} env={env:?}"));
+            }
+            if *self.fail_run.lock().unwrap() {
+                bail!("simulated run failure");
+            }
+            let mut stored = self.labels.lock().unwrap();
+            stored.retain(|(n, _, _)| n != name);
+            stored.push((
+                name.to_string(),
+                crate::docker::MANAGED_BY_LABEL.to_string(),
+                crate::docker::MANAGED_BY_VALUE.to_string(),
+            ));
+            for (key, value) in labels {
+                stored.push((name.to_string(), key.clone(), value.clone()));
+            }
+            drop(stored);
+            let mut containers = self.containers.lock().unwrap();
+            containers.retain(|c| c.name != name);
+            containers.push(ContainerInfo {
+                name: name.to_string(),
+                image: image.to_string(),
+                status: "running".to_string(),
+                networks: vec![network.to_string()],
+                ports: Vec::new(),
+            });
+            Ok(())
+        }
+
+        async fn stop_and_remove_container(&self, name: &str) -> Result<()> {
+            self.record(format!("stop_and_remove {name}"));
+            Ok(())
+        }
+
+        async fn list_dangling_images(&self) -> Result<Vec<ImageInfo>> {
+            self.record("list_dangling_images");
+            Ok(self.images.lock().unwrap().clone())
+        }
+
+        async fn remove_image(&self, id: &str) -> Result<()> {
+            self.record(format!("remove_image {id}"));
+            self.images.lock().unwrap().retain(|i| i.id != id);
+            Ok(())
+        }
+
+        async fn container_image_id(&self, name: &str) -> Result<Option<String>> {
+            self.record(format!("container_image_id {name}"));
+            Ok(self.in_use_image.lock().unwrap().clone())
+        }
+
+        async fn container_age(&self, name: &str) -> Result<Option<std::time::Duration>> {
+            self.record(format!("container_age {name}"));
+            Ok(*self.age.lock().unwrap())
+        }
+
+        async fn daemon_info(&self) -> Result<crate::docker::DaemonInfo> {
+            self.record("daemon_info");
+            Ok(crate::docker::DaemonInfo {
+                rootless: *self.rootless.lock().unwrap(),
+            })
+        }
+
+        async fn get_logs(
+            &self,
+            name: &str,
+            _tail: Option<u32>,
+            _since: Option<i64>,
+        ) -> Result<Vec<String>> {
+            self.record(format!("get_logs {name}"));
+            Ok(Vec::new())
+        }
+
+        async fn pause_container(&self, name: &str) -> Result<()> {
+            self.record(format!("pause {name}"));
+            if let Some(c) = self
+                .containers
+                .lock()
+                .unwrap()
+                .iter_mut()
+                .find(|c| c.name == name)
+            {
+                c.status = "paused".to_string();
+            }
+            Ok(())
+        }
+
+        async fn unpause_container(&self, name: &str) -> Result<()> {
+            self.record(format!("unpause {name}"));
+            if let Some(c) = self
+                .containers
+                .lock()
+                .unwrap()
+                .iter_mut()
+                .find(|c| c.name == name)
+            {
+                c.status = "running".to_string();
+            }
+            Ok(())
+        }
+
+        async fn restart_container(&self, name: &str, timeout_secs: u32) -> Result<()> {
+            self.record(format!("restart_container {name} t={timeout_secs}"));
+            Ok(())
+        }
+
+        async fn container_labels(
+            &self,
+            name: &str,
+        ) -> Result<Option<std::collections::HashMap<String, String>>> {
+            self.record(format!("container_labels {name}"));
+            let stored = self.labels.lock().unwrap();
+            let map: std::collections::HashMap<String, String> = stored
+                .iter()
+                .filter(|(n, _, _)| n == name)
+                .map(|(_, k, v)| (k.clone(), v.clone()))
+                .collect();
+            if map.is_empty()
+                && !self
+                    .containers
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|c| c.name == name)
+            {
+                return Ok(None);
+            }
+            Ok(Some(map))
+        }
+
+        async fn container_published_ports(&self, name: &str) -> Result<Vec<u16>> {
+            self.record(format!("container_published_ports {name}"));
+            Ok(self
+                .published_ports
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(n, _)| n == name)
+                .map(|(_, p)| *p)
+                .collect())
+        }
+
+        async fn exec_in_container(&self, name: &str, cmd: &[String]) -> Result<(i64, String)> {
+            self.record(format!("exec {name} {}", cmd.join(" ")));
+            let key = cmd.last().cloned().unwrap_or_default();
+            Ok(self
+                .exec_results
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(k, _, _)| *k == key)
+                .map(|(_, code, out)| (*code, out.clone()))
+                .unwrap_or((0, String::new())))
+        }
+
+        async fn copy_file_from_container(&self, name: &str, path: &str) -> Result<Vec<u8>> {
+            self.record(format!("copy_file_from_container {name} {path}"));
+            self.files
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(n, p, _)| n == name && p == path)
+                .map(|(_, _, contents)| contents.clone())
+                .ok_or_else(|| anyhow::anyhow!("'{path}' not found in container '{name}'"))
+        }
+    }
+
+    pub(crate) fn test_config() -> Config {
+        let mut config = Config::default();
+        config.upsert_container(Container {
+            name: "app1".into(),
+            label: None,
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.set_route(8000, "app1", 8080);
+        config
+    }
+
+    #[tokio::test]
+    async fn backend_ca_files_land_in_the_build_context() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, dir) = manager_with(docker);
+        let ca = dir.path().join("ca.pem");
+        std::fs::write(&ca, "CERT").unwrap();
+        let mut config = test_config();
+        config.routes[0].backend_scheme = crate::config::BackendScheme::Https;
+        config.routes[0].backend_ca_file = Some(ca);
+        manager.write_build_files(&config).unwrap();
+        let entries = build_context_entries(&config, &manager.config.store().build_dir()).unwrap();
+        let entry = entries
+            .iter()
+            .find(|(name, _)| name == "ca_8000.crt")
+            .unwrap();
+        assert_eq!(entry.1, b"CERT");
+    }
+
+    #[test]
+    fn hand_edited_build_files_are_guarded() {
+        let docker = Arc::new(FakeDocker::default());
+        let (mut manager, _dir) = manager_with(docker);
+        let config = test_config();
+        let (conf_path, _) = manager.write_build_files(&config).unwrap();
+        // Untouched files regenerate silently.
+        manager.write_build_files(&config).unwrap();
+
+        std::fs::write(&conf_path, "# my experiment\n").unwrap();
+        let err = manager.write_build_files(&config).unwrap_err();
+        assert!(err.to_string().contains("--overwrite-build"));
+
+        // --keep-local builds with the edit and leaves the guard armed.
+        manager.set_build_overwrite(BuildOverwrite::KeepLocal);
+        manager.write_build_files(&config).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&conf_path).unwrap(),
+            "# my experiment\n"
+        );
+        let build_dir = manager.config.store().build_dir();
+        let entries = build_context_entries(&config, &build_dir).unwrap();
+        let conf = entries
+            .iter()
+            .find(|(name, _)| name == "nginx.conf")
+            .unwrap();
+        assert_eq!(conf.1, b"# my experiment\n");
+        manager.set_build_overwrite(BuildOverwrite::Refuse);
+        assert!(manager.write_build_files(&config).is_err());
+
+        // --overwrite-build regenerates and refreshes the manifest, so the
+        // next default run is clean again.
+        manager.set_build_overwrite(BuildOverwrite::Force);
+        manager.write_build_files(&config).unwrap();
+        let regenerated = std::fs::read_to_string(&conf_path).unwrap();
+        assert!(regenerated.contains("worker_processes"));
+        let manifest = load_build_manifest(&build_dir);
+        assert_eq!(manifest["nginx.conf"], content_digest(&regenerated));
+        manager.set_build_overwrite(BuildOverwrite::Refuse);
+        manager.write_build_files(&config).unwrap();
+    }
+
+    #[tokio::test]
+    async fn caddy_backend_builds_from_the_caddyfile() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker);
+        let mut config = test_config();
+        config.proxy_backend = ProxyBackend::Caddy;
+        manager.write_build_files(&config).unwrap();
+        let build_dir = manager.config.store().build_dir();
+        assert!(build_dir.join("Caddyfile").exists());
+        assert!(!build_dir.join("nginx.conf").exists());
+        // The build context picks up the Caddyfile, not nginx.conf.
+        manager.build_proxy_image(&config).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn caddy_start_snapshots_and_recovers_from_the_caddyfile() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, dir) = manager_with(docker.clone());
+        let mut config = test_config();
+        config.proxy_backend = ProxyBackend::Caddy;
+
+        manager.start_proxy(&config).await.unwrap();
+        let snapshot = dir.path().join("build").join(LAST_GOOD_DIR);
+        assert!(snapshot.join("Caddyfile").is_file());
+        assert!(!snapshot.join("nginx.conf").exists());
+
+        docker.calls.lock().unwrap().clear();
+        let output = manager.recover().await.unwrap();
+        assert!(output.iter().any(|l| l.contains("Recovered proxy")));
+        assert!(docker.calls().iter().any(|c| c.starts_with("build_image")));
+    }
+
+    fn manager_with(docker: Arc<FakeDocker>) -> (ProxyManager, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Arc::new(ConfigManager::new(dir.path().to_path_buf()).unwrap());
+        (ProxyManager::new(docker, config), dir)
+    }
+
+    /// MakeWriter capturing formatter output so tests can assert on it.
+    #[derive(Clone, Default)]
+    struct CaptureWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CaptureWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for CaptureWriter {
+        type Writer = CaptureWriter;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn start_emits_spans_with_durations() {
+        let writer = CaptureWriter::default();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_max_level(tracing::Level::INFO)
+            .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+            .with_writer(writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker);
+        manager.start_proxy(&test_config()).await.unwrap();
+
+        let logs = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        for span in ["network_ensure", "image_build", "container_run"] {
+            assert!(logs.contains(span), "missing span '{span}' in:\n{logs}");
+        }
+        assert!(
+            logs.contains("duration_ms"),
+            "no duration field in:\n{logs}"
+        );
+    }
+
+    #[tokio::test]
+    async fn start_applies_configured_proxy_labels() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker.clone());
+        let mut config = test_config();
+        config
+            .proxy_labels
+            .insert("profile".to_string(), "dev".to_string());
+        manager.start_proxy(&config).await.unwrap();
+
+        let labels = docker.labels.lock().unwrap();
+        let of = |key: &str| {
+            labels
+                .iter()
+                .find(|(name, k, _)| name == &test_config().proxy_name && k == key)
+                .map(|(_, _, v)| v.clone())
+        };
+        // The default managed-by label survives alongside custom ones.
+        assert_eq!(of("managed-by").as_deref(), Some("proxy-manager"));
+        assert_eq!(of("profile").as_deref(), Some("dev"));
+        assert!(of(crate::docker::CONFIG_HASH_LABEL).is_some());
+    }
+
+    #[tokio::test]
+    async fn build_context_contains_the_htpasswd_file() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker);
+        let mut config = test_config();
+        config.routes[0].basic_auth = Some(crate::config::BasicAuth {
+            username: "alice".into(),
+            password_hash: "$apr1$salt$hash".into(),
+        });
+        manager.write_build_files(&config).unwrap();
+        let build_dir = manager.config.store().build_dir();
+        let entries = build_context_entries(&config, &build_dir).unwrap();
+        let htpasswd = entries
+            .iter()
+            .find(|(name, _)| name == "htpasswd_8000")
+            .expect("htpasswd entry missing");
+        assert_eq!(htpasswd.1, b"alice:$apr1$salt$hash\n");
+    }
+
+    #[tokio::test]
+    async fn apply_deploys_without_touching_the_config_file() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, dir) = manager_with(docker.clone());
+        manager.apply(&test_config()).await.unwrap();
+
+        let calls = docker.calls.lock().unwrap().clone();
+        assert!(calls.iter().any(|c| c.starts_with("stop_and_remove")));
+        assert!(calls.iter().any(|c| c.starts_with("run ")));
+        // Build files are generated, the config file is not.
+        assert!(dir.path().join("build/nginx.conf").exists());
+        assert!(!dir.path().join("config.json").exists());
+    }
+
+    #[tokio::test]
+    async fn capture_config_reads_the_live_file_when_running() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker.clone());
+        let proxy = Config::default().proxy_name;
+        docker.containers.lock().unwrap().push(ContainerInfo {
+            name: proxy.clone(),
+            image: format!("{proxy}:latest"),
+            status: "running".to_string(),
+            networks: Vec::new(),
+            ports: Vec::new(),
+        });
+        docker.files.lock().unwrap().push((
+            proxy,
+            "/etc/nginx/nginx.conf".to_string(),
+            b"# live config\n".to_vec(),
+        ));
+        assert_eq!(manager.capture_config().await.unwrap(), "# live config\n");
+    }
+
+    #[tokio::test]
+    async fn capture_config_falls_back_to_generated_with_a_warning() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker);
+        let captured = manager.capture_config().await.unwrap();
+        assert!(captured.starts_with("# proxy "));
+        assert!(captured.contains("not running"));
+        assert!(captured.contains("http {"));
+    }
+
+    #[tokio::test]
+    async fn snapshot_written_only_on_success() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker.clone());
+        let config = test_config();
+
+        *docker.fail_build.lock().unwrap() = true;
+        assert!(manager.start_proxy(&config).await.is_err());
+        assert!(!manager.has_last_good());
+
+        *docker.fail_build.lock().unwrap() = false;
+        manager.start_proxy(&config).await.unwrap();
+        assert!(manager.has_last_good());
+    }
+
+    #[tokio::test]
+    async fn recover_uses_snapshot_port_set() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker.clone());
+        let mut config = test_config();
+        manager.start_proxy(&config).await.unwrap();
+
+        // Config changes after the snapshot; recover must ignore it.
+        config.set_route(9999, "app1", 8080);
+
+        manager.recover().await.unwrap();
+        let calls = docker.calls();
+        let last_run = calls.iter().rev().find(|c| c.starts_with("run ")).unwrap();
+        assert_eq!(last_run, "run proxy-manager ports=[8000]");
+    }
+
+    #[tokio::test]
+    async fn recover_without_snapshot_errors() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker);
+        let err = manager.recover().await.unwrap_err();
+        assert!(err.to_string().contains("no last-good snapshot"));
+    }
+
+    #[tokio::test]
+    async fn reload_without_auto_recover_points_at_recover_command() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker.clone());
+        let config = test_config();
+        manager.start_proxy(&config).await.unwrap();
+
+        // Break the config so the start phase fails after the stop phase
+        // already succeeded.
+        let mut broken = config.clone();
+        broken.set_route(8001, "missing", 80);
+        let err = manager.reload(&broken, false).await.unwrap_err();
+        assert!(format!("{err:#}").contains("proxy-manager recover"));
+    }
+
+    #[tokio::test]
+    async fn auto_recover_restores_snapshot_when_start_fails_after_stop() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker.clone());
+        let config = test_config();
+        manager.start_proxy(&config).await.unwrap();
+
+        let mut broken = config.clone();
+        broken.set_route(8001, "missing", 80);
+        let output = manager.reload(&broken, true).await.unwrap();
+        assert!(output.iter().any(|l| l.contains("recovering")));
+        assert!(output.iter().any(|l| l.contains("Recovered proxy")));
+        // Recovery ran with the snapshot's port set, not the broken config's.
+        let calls = docker.calls();
+        let last_run = calls.iter().rev().find(|c| c.starts_with("run ")).unwrap();
+        assert_eq!(last_run, "run proxy-manager ports=[8000]");
+    }
+
+    #[tokio::test]
+    async fn external_networks_are_joined_but_never_created() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker.clone());
+        let mut config = test_config();
+        config.external_networks = vec!["proxy-net".to_string()];
+
+        // Missing external network fails fast before any build.
+        let err = manager.start_proxy(&config).await.unwrap_err();
+        assert!(err.to_string().contains("external network 'proxy-net'"));
+        assert!(!docker.calls().iter().any(|c| c.starts_with("build_image")));
+
+        docker.networks.lock().unwrap().push(NetworkInfo {
+            name: "proxy-net".into(),
+            driver: "bridge".into(),
+            container_count: Some(0),
+            containers: None,
+            managed: false,
+        });
+        manager.start_proxy(&config).await.unwrap();
+        assert!(!docker
+            .calls()
+            .iter()
+            .any(|c| c.starts_with("ensure_network")));
+    }
+
+    fn static_site() -> tempfile::TempDir {
+        let site = tempfile::tempdir().unwrap();
+        std::fs::write(site.path().join("index.html"), "<h1>hi</h1>").unwrap();
+        std::fs::create_dir(site.path().join("css")).unwrap();
+        std::fs::write(site.path().join("css/site.css"), "body{}").unwrap();
+        site
+    }
+
+    #[test]
+    fn static_route_is_staged_into_the_build_context() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, dir) = manager_with(docker);
+        let site = static_site();
+        let mut config = Config::default();
+        config.set_static_route(8008, site.path().to_path_buf());
+        manager.write_build_files(&config).unwrap();
+
+        let build_dir = dir.path().join("build");
+        assert!(build_dir.join("static_8008/index.html").is_file());
+        let entries = build_context_entries(&config, &build_dir).unwrap();
+        let names: Vec<&str> = entries.iter().map(|(n, _)| n.as_str()).collect();
+        assert!(names.contains(&"static_8008/index.html"));
+        assert!(names.contains(&"static_8008/css/site.css"));
+    }
+
+    #[test]
+    fn static_route_over_the_size_limit_fails_the_build() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker);
+        let site = static_site();
+        let mut config = Config {
+            static_size_limit_mb: 0,
+            ..Config::default()
+        };
+        config.set_static_route(8008, site.path().to_path_buf());
+        let err = manager.write_build_files(&config).unwrap_err();
+        assert!(err.to_string().contains("static_size_limit_mb"));
+    }
+
+    #[test]
+    fn static_route_requires_an_existing_directory() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker);
+        let mut config = Config::default();
+        config.set_static_route(8008, PathBuf::from("/nonexistent/site"));
+        let err = manager.write_build_files(&config).unwrap_err();
+        assert!(err.to_string().contains("not a directory"));
+    }
+
+    #[tokio::test]
+    async fn dns_aliases_reattach_the_backend_with_the_full_list() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker.clone());
+        let mut config = test_config();
+        config.containers[0].dns_aliases = vec!["billing".into(), "billing-v2".into()];
+        let output = manager.start_proxy(&config).await.unwrap();
+        let calls = docker.calls();
+        let disconnect = calls
+            .iter()
+            .position(|c| c == "disconnect app1 proxy-net")
+            .unwrap();
+        let connect = calls
+            .iter()
+            .position(|c| c == "connect_alias app1 proxy-net aliases=billing,billing-v2")
+            .unwrap();
+        assert!(disconnect < connect);
+        assert!(output
+            .iter()
+            .any(|l| l.contains("alias(es) billing, billing-v2")));
+    }
+
+    #[tokio::test]
+    async fn connect_all_networks_joins_only_user_bridges() {
+        let docker = Arc::new(FakeDocker::default());
+        for (name, driver) in [
+            ("proxy-net", "bridge"),
+            ("bridge", "bridge"),
+            ("host", "host"),
+            ("none", "null"),
+            ("team-a", "bridge"),
+            ("team-b", "bridge"),
+        ] {
+            docker.networks.lock().unwrap().push(NetworkInfo {
+                name: name.into(),
+                driver: driver.into(),
+                container_count: None,
+                containers: None,
+                managed: false,
+            });
+        }
+        let (manager, _dir) = manager_with(docker.clone());
+        let mut config = test_config();
+        config.connect_all_networks = true;
+        let output = manager.start_proxy(&config).await.unwrap();
+        let calls = docker.calls();
+        assert!(calls.iter().any(|c| c == "connect proxy-manager team-a"));
+        assert!(calls.iter().any(|c| c == "connect proxy-manager team-b"));
+        // The defaults and non-bridge drivers stay untouched.
+        for skipped in ["bridge", "host", "none", "proxy-net"] {
+            assert!(!calls
+                .iter()
+                .any(|c| *c == format!("connect proxy-manager {skipped}")));
+        }
+        assert!(output
+            .iter()
+            .any(|l| l.contains("Warning: connect_all_networks joined 2")));
+    }
+
+    #[tokio::test]
+    async fn connect_backends_attaches_only_disjoint_targets_and_records_them() {
+        let docker = Arc::new(FakeDocker::default());
+        for (name, networks) in [
+            ("app1", vec!["proxy-net".to_string()]),
+            ("app2", vec!["team-a".to_string()]),
+        ] {
+            docker.containers.lock().unwrap().push(ContainerInfo {
+                name: name.into(),
+                image: String::new(),
+                status: "running".into(),
+                networks,
+                ports: vec![],
+            });
+        }
+        let (manager, dir) = manager_with(docker.clone());
+        let mut config = test_config();
+        for name in ["app2", "ghost"] {
+            config.upsert_container(Container {
+                name: name.into(),
+                label: None,
+                port: 8080,
+                network: None,
+                static_root: None,
+                response_rewrites: Vec::new(),
+                allowed_methods: None,
+                tls_backend: false,
+                tls_backend_insecure: false,
+                auth_request_url: None,
+                description: None,
+                dns_aliases: Vec::new(),
+                aliases: Vec::new(),
+            });
+        }
+        config.set_route(8001, "app2", 8080);
+        config.set_route(8002, "ghost", 8080);
+        config.connect_backends = true;
+
+        let output = manager.start_proxy(&config).await.unwrap();
+        let calls = docker.calls();
+        // app1 already shares proxy-net; only the disjoint app2 connects.
+        assert!(!calls.iter().any(|c| c == "connect app1 proxy-net"));
+        assert!(calls.iter().any(|c| c == "connect app2 proxy-net"));
+        assert!(output
+            .iter()
+            .any(|l| l.contains("container 'ghost' does not exist")));
+
+        let raw = std::fs::read_to_string(dir.path().join("connected_backends.json")).unwrap();
+        let records: Vec<(String, String)> = serde_json::from_str(&raw).unwrap();
+        assert_eq!(records, vec![("app2".to_string(), "proxy-net".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn disconnect_on_stop_removes_exactly_the_recorded_connections() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, dir) = manager_with(docker.clone());
+        let path = dir.path().join("connected_backends.json");
+        std::fs::write(&path, r#"[["app2", "proxy-net"]]"#).unwrap();
+
+        let output = manager.disconnect_recorded_backends().await.unwrap();
+        let disconnects: Vec<_> = docker
+            .calls()
+            .iter()
+            .filter(|c| c.starts_with("disconnect"))
+            .cloned()
+            .collect();
+        assert_eq!(disconnects, vec!["disconnect app2 proxy-net"]);
+        assert!(output[0].contains("Disconnected backend 'app2'"));
+        assert!(!path.exists(), "record must be cleared after disconnect");
+
+        // A second run finds nothing and touches no networks.
+        docker.calls.lock().unwrap().clear();
+        let output = manager.disconnect_recorded_backends().await.unwrap();
+        assert_eq!(output, vec!["No recorded backend connections to remove"]);
+        assert!(docker.calls().is_empty());
+    }
+
+    #[test]
+    fn ip_in_subnet_handles_edges() {
+        let ip = "172.30.0.5".parse().unwrap();
+        assert!(ip_in_subnet(ip, "172.30.0.0/24").unwrap());
+        assert!(!ip_in_subnet(ip, "172.31.0.0/24").unwrap());
+        assert!(ip_in_subnet(ip, "0.0.0.0/0").unwrap());
+        assert!(ip_in_subnet("fd00::5".parse().unwrap(), "fd00::/64").unwrap());
+        assert!(ip_in_subnet("1.2.3.4".parse().unwrap(), "not-a-subnet").is_err());
+    }
+
+    #[tokio::test]
+    async fn static_ip_is_sent_only_for_pinned_networks() {
+        let docker = Arc::new(FakeDocker::default());
+        docker
+            .subnets
+            .lock()
+            .unwrap()
+            .push(("net2".into(), "172.30.0.0/24".into()));
+        let (manager, _dir) = manager_with(docker.clone());
+        let mut config = test_config();
+        config.containers[0].network = Some("net2".into());
+        config
+            .proxy_static_ips
+            .insert("net2".into(), "172.30.0.5".parse().unwrap());
+        manager.start_proxy(&config).await.unwrap();
+        let calls = docker.calls();
+        assert!(calls
+            .iter()
+            .any(|c| c == "connect proxy-manager net2 ip=172.30.0.5"));
+        // The unpinned default network carries no address.
+        assert!(!calls.iter().any(|c| c.contains("proxy-net ip=")));
+    }
+
+    #[tokio::test]
+    async fn pin_on_the_default_network_reattaches_the_proxy() {
+        let docker = Arc::new(FakeDocker::default());
+        docker
+            .subnets
+            .lock()
+            .unwrap()
+            .push(("proxy-net".into(), "172.18.0.0/16".into()));
+        let (manager, _dir) = manager_with(docker.clone());
+        let mut config = test_config();
+        config
+            .proxy_static_ips
+            .insert("proxy-net".into(), "172.18.0.9".parse().unwrap());
+        let output = manager.start_proxy(&config).await.unwrap();
+        let calls = docker.calls();
+        let disconnect = calls
+            .iter()
+            .position(|c| c == "disconnect proxy-manager proxy-net")
+            .unwrap();
+        let connect = calls
+            .iter()
+            .position(|c| c == "connect proxy-manager proxy-net ip=172.18.0.9")
+            .unwrap();
+        assert!(disconnect < connect);
+        assert!(output
+            .iter()
+            .any(|l| l.contains("Pinned proxy to 172.18.0.9")));
+    }
+
+    #[tokio::test]
+    async fn out_of_subnet_pin_fails_before_anything_is_built() {
+        let docker = Arc::new(FakeDocker::default());
+        docker
+            .subnets
+            .lock()
+            .unwrap()
+            .push(("proxy-net".into(), "172.18.0.0/16".into()));
+        let (manager, _dir) = manager_with(docker.clone());
+        let mut config = test_config();
+        config
+            .proxy_static_ips
+            .insert("proxy-net".into(), "10.9.9.9".parse().unwrap());
+        let err = manager.start_proxy(&config).await.unwrap_err();
+        assert!(err.to_string().contains("outside network 'proxy-net'"));
+        assert!(!docker.calls().iter().any(|c| c.starts_with("run ")));
+        assert!(!docker.calls().iter().any(|c| c.starts_with("build_image")));
+    }
+
+    /// HttpClient serving the fallback page for every request.
+    struct FallbackPage;
+
+    #[async_trait]
+    impl HttpClient for FallbackPage {
+        async fn get(&self, _url: &str) -> Result<crate::probe::ProbeResponse> {
+            Ok(crate::probe::ProbeResponse {
+                status: 503,
+                headers: Vec::new(),
+                body: crate::nginx::FALLBACK_MESSAGE.to_string(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn failover_simulation_pauses_probes_and_resumes() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker.clone());
+        manager.config.replace(test_config()).unwrap();
+        docker.containers.lock().unwrap().push(ContainerInfo {
+            name: "app1".into(),
+            image: "app1:latest".into(),
+            status: "running".into(),
+            networks: Vec::new(),
+            ports: Vec::new(),
+        });
+
+        let report = manager
+            .simulate_failover_with(&FallbackPage, "app1")
+            .await
+            .unwrap();
+        assert_eq!(report.routes_tested, 1);
+        assert!(report.all_returned_503);
+        assert_eq!(
+            report.messages,
+            vec!["Route 8000: HTTP 503 (fallback page)"]
+        );
+        // The container is paused before the probe and resumed after.
+        let calls = docker.calls();
+        assert!(calls.contains(&"pause app1".to_string()));
+        assert!(calls.contains(&"unpause app1".to_string()));
+        assert_eq!(
+            docker.containers.lock().unwrap()[0].status,
+            "running",
+            "container must be resumed"
+        );
+
+        // A backend answering normally fails the check.
+        let report = manager
+            .simulate_failover_with(&FixedStatus(200), "app1")
+            .await
+            .unwrap();
+        assert!(!report.all_returned_503);
+
+        let err = manager
+            .simulate_failover_with(&FallbackPage, "ghost")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no configured container"));
+    }
+
+    /// HttpClient answering every request with one fixed status.
+    struct FixedStatus(u16);
+
+    #[async_trait]
+    impl HttpClient for FixedStatus {
+        async fn get(&self, _url: &str) -> Result<crate::probe::ProbeResponse> {
+            Ok(crate::probe::ProbeResponse {
+                status: self.0,
+                headers: Vec::new(),
+                body: String::new(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn wait_for_healthy_falls_back_to_container_status() {
+        let docker = Arc::new(FakeDocker::default());
+        let (manager, _dir) = manager_with(docker.clone());
+        let proxy_name = manager.config.get().proxy_name.clone();
+        docker.containers.lock().unwrap().push(ContainerInfo {
+            name: proxy_name,
+            image: "proxy-manager:latest".into(),
+            status: "running".into(),
+            networks: Vec::new(),
+            ports: Vec::new(),
+        });
+        manager
+            .wait_for_healthy(Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        // Without the container the fallback times out instead of hanging.
+        let (manager, _dir) = manager_with(Arc::new(FakeDocker::default()));
+        let err = manager.wait_for_healthy(Duration::ZERO).await.unwrap_err();
+        assert!(err.to_string().contains("did not reach 'running'"));
+    }
+
+    #[tokio::test]
+    async fn wait_for_status_page_wants_a_200() {
+        let (manager, _dir) = manager_with(Arc::new(FakeDocker::default()));
+        manager
+            .wait_for_status_page(&FixedStatus(200), 8099, Duration::ZERO)
+            .await
+            .unwrap();
+        let err = manager
+            .wait_for_status_page(&FixedStatus(503), 8099, Duration::ZERO)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("nginx_status"));
+    }
+}
@@ -0,0 +1,910 @@
+//! nginx configuration generation.
+//!
+//! A single generator turns the [`Config`] into the `nginx.conf` and
+//! `Dockerfile` written into the build directory. Every call site must go
+//! through this module so the template cannot drift.
+
+use crate::config::{BackendScheme, Config, LbMethod, MtlsVerify, Route};
+
+/// Message served by the fallback page when a backend is unreachable.
+pub const FALLBACK_MESSAGE: &str = "Service temporarily unavailable";
+
+/// Loopback port of the always-emitted internal stub_status server; never
+/// published, reachable only through `docker exec` for idle detection.
+pub const INTERNAL_STATUS_PORT: u16 = 18080;
+
+/// Generates nginx configuration files from the tool configuration.
+pub struct NginxConfigGenerator;
+
+/// First nginx version understanding the standalone `http2 on;` directive
+/// (the `listen ... http2` parameter is deprecated from here on).
+const HTTP2_DIRECTIVE_SINCE: (u32, u32, u32) = (1, 25, 1);
+
+/// Whether `base_image` pins an nginx new enough for `http2 on;`. Images
+/// without an explicit version (e.g. `nginx:alpine`) cannot be judged, so
+/// we fall back to the `listen ... http2` form that every version accepts.
+fn supports_http2_directive(base_image: &str) -> bool {
+    let tag = match base_image.rsplit_once(':') {
+        Some((_, tag)) => tag,
+        None => return false,
+    };
+    let digits: Vec<u32> = tag
+        .split(|c: char| !c.is_ascii_digit())
+        .take_while(|part| !part.is_empty())
+        .filter_map(|part| part.parse().ok())
+        .collect();
+    match digits.as_slice() {
+        [major, minor, patch, ..] => (*major, *minor, *patch) >= HTTP2_DIRECTIVE_SINCE,
+        [major, minor] => (*major, *minor, 0) >= HTTP2_DIRECTIVE_SINCE,
+        _ => false,
+    }
+}
+
+/// The nginx directive for a load-balancing method; round-robin is the
+/// default and needs none.
+fn lb_directive(method: Option<LbMethod>) -> Option<&'static str> {
+    match method {
+        Some(LbMethod::LeastConn) => Some("least_conn;"),
+        Some(LbMethod::IpHash) => Some("ip_hash;"),
+        Some(LbMethod::Random) => Some("random;"),
+        Some(LbMethod::RoundRobin) | None => None,
+    }
+}
+
+/// nginx-safe upstream block name for a backend address.
+fn upstream_name(target: &str, port: u16) -> String {
+    let safe: String = target
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("upstream_{safe}_{port}")
+}
+
+/// Emit the client-certificate directives for an mTLS route.
+fn push_mtls_lines(out: &mut String, route: &Route) {
+    if route.mtls_ca_cert.is_some() {
+        let port = route.primary_port();
+        out.push_str(&format!(
+            "        ssl_client_certificate /etc/nginx/ca/client_{port}.crt;\n"
+        ));
+        out.push_str(&format!(
+            "        ssl_verify_client {};\n",
+            route.mtls_verify.unwrap_or(MtlsVerify::On).directive()
+        ));
+    }
+}
+
+/// Emit the basic-auth directives for a protected route.
+fn push_auth_lines(out: &mut String, route: &Route) {
+    if route.basic_auth.is_some() {
+        out.push_str("        auth_basic \"Restricted\";\n");
+        out.push_str(&format!(
+            "        auth_basic_user_file /etc/nginx/htpasswd_{};\n",
+            route.primary_port()
+        ));
+    }
+}
+
+/// Emit the listen lines for a route plus the TLS/HTTP2 directives they
+/// imply, shared by every server-block flavour.
+fn push_listen_lines(out: &mut String, route: &Route, http2_directive: bool) {
+    let mut suffix = String::new();
+    if route.tls {
+        suffix.push_str(" ssl");
+    }
+    if route.http2_enabled() && !http2_directive {
+        suffix.push_str(" http2");
+    }
+    for port in &route.host_ports {
+        out.push_str(&format!("        listen {port}{suffix};\n"));
+    }
+    if route.tls {
+        let port = route.primary_port();
+        out.push_str(&format!(
+            "        ssl_certificate /etc/nginx/certs/{port}.crt;\n"
+        ));
+        out.push_str(&format!(
+            "        ssl_certificate_key /etc/nginx/certs/{port}.key;\n"
+        ));
+    }
+    if route.http2_enabled() && http2_directive {
+        out.push_str("        http2 on;\n");
+    }
+}
+
+impl NginxConfigGenerator {
+    /// Render the complete `nginx.conf` with one server block per route.
+    pub fn generate(config: &Config) -> String {
+        let _span = tracing::debug_span!("nginx_generate").entered();
+        let mut out = String::new();
+        out.push_str("worker_processes auto;\n\n");
+        out.push_str("events {\n    worker_connections 1024;\n}\n\n");
+        out.push_str("http {\n");
+        let resolver = config.resolver.as_deref().unwrap_or("127.0.0.11");
+        if resolver != "off" {
+            out.push_str(&format!(
+                "    resolver {resolver} valid={}s;\n",
+                config.resolver_valid_secs.unwrap_or(30)
+            ));
+        }
+        out.push_str("    access_log /dev/stdout;\n");
+        out.push_str("    error_log /dev/stderr warn;\n");
+        let http2_directive = supports_http2_directive(&config.base_image);
+
+        // Canary routes split traffic per client address at the http level.
+        for route in config
+            .routes
+            .iter()
+            .filter(|r| !r.unbound && !r.maintenance)
+        {
+            if let Some(canary) = &route.canary {
+                let stable = format!(
+                    "{}:{}",
+                    config.backend_host(&route.target),
+                    route.internal_port
+                );
+                let experiment = format!(
+                    "{}:{}",
+                    config.backend_host(&canary.target),
+                    canary.internal_port
+                );
+                out.push('\n');
+                out.push_str(&format!(
+                    "    split_clients \"${{remote_addr}}${{remote_port}}\" $canary_{} {{\n",
+                    route.primary_port()
+                ));
+                out.push_str(&format!("        {}% {experiment};\n", canary.percent));
+                out.push_str(&format!("        * {stable};\n"));
+                out.push_str("    }\n");
+            }
+        }
+
+        // Connection caps need their zones declared at the http level; one
+        // zone per route keeps the names unique.
+        for route in config
+            .routes
+            .iter()
+            .filter(|r| !r.unbound && !r.maintenance)
+        {
+            if route.max_conn.is_some() {
+                out.push_str(&format!(
+                    "    limit_conn_zone $binary_remote_addr zone=conn_{}:10m;\n",
+                    route.primary_port()
+                ));
+            }
+        }
+
+        // Internal status server: loopback-only, never published; `restart
+        // --when-idle` reads active connection counts through `docker exec`.
+        out.push('\n');
+        out.push_str("    server {\n");
+        out.push_str(&format!(
+            "        listen 127.0.0.1:{INTERNAL_STATUS_PORT};\n"
+        ));
+        out.push_str("        location /nginx_status {\n");
+        out.push_str("            stub_status;\n");
+        out.push_str("            access_log off;\n");
+        out.push_str("        }\n");
+        out.push_str("    }\n");
+
+        // Management server: lets startup health checks (and operators)
+        // see nginx answering without touching a routed port.
+        if let Some(port) = config.management_port {
+            out.push('\n');
+            out.push_str("    server {\n");
+            out.push_str(&format!("        listen {port};\n"));
+            out.push_str("        location /nginx_status {\n");
+            out.push_str("            stub_status;\n");
+            out.push_str("            access_log off;\n");
+            out.push_str("        }\n");
+            out.push_str("    }\n");
+        }
+
+        // With compact routes, identical backends share one upstream block
+        // instead of repeating the address in every server block.
+        if config.compact_routes {
+            let mut backends: Vec<(String, u16, Option<LbMethod>)> = Vec::new();
+            for route in config
+                .routes
+                .iter()
+                .filter(|r| !r.unbound && !r.maintenance && !r.is_static() && r.canary.is_none())
+            {
+                let target = config.backend_host(&route.target);
+                match backends
+                    .iter_mut()
+                    .find(|(t, p, _)| *t == target && *p == route.internal_port)
+                {
+                    // The first route asking for a method wins for the
+                    // shared group (ip_hash is rejected by validation).
+                    Some((_, _, lb)) => {
+                        if lb.is_none() {
+                            *lb = route.lb_method;
+                        }
+                    }
+                    None => backends.push((target, route.internal_port, route.lb_method)),
+                }
+            }
+            for (target, port, lb) in backends {
+                out.push('\n');
+                out.push_str(&format!(
+                    "    upstream {} {{\n",
+                    upstream_name(&target, port)
+                ));
+                if let Some(directive) = lb_directive(lb) {
+                    out.push_str(&format!("        {directive}\n"));
+                }
+                out.push_str(&format!("        server {target}:{port};\n"));
+                out.push_str("    }\n");
+            }
+        } else {
+            // Routes with an explicit load-balancing method still need an
+            // upstream group; nginx only accepts the directives there.
+            let mut seen = Vec::new();
+            for route in config
+                .routes
+                .iter()
+                .filter(|r| !r.unbound && !r.maintenance && !r.is_static() && r.canary.is_none())
+            {
+                let Some(directive) = lb_directive(route.lb_method) else {
+                    continue;
+                };
+                let target = config.backend_host(&route.target);
+                let name = upstream_name(&target, route.internal_port);
+                if seen.contains(&name) {
+                    continue;
+                }
+                out.push('\n');
+                out.push_str(&format!("    upstream {name} {{\n"));
+                out.push_str(&format!("        {directive}\n"));
+                out.push_str(&format!(
+                    "        server {target}:{};\n",
+                    route.internal_port
+                ));
+                out.push_str("    }\n");
+                seen.push(name);
+            }
+        }
+
+        for route in config.routes.iter().filter(|r| !r.unbound) {
+            // Maintenance routes answer everything with the 503 page; the
+            // proxy block is omitted entirely so nothing leaks through.
+            if route.maintenance {
+                let message = config
+                    .maintenance_message
+                    .as_deref()
+                    .unwrap_or(FALLBACK_MESSAGE);
+                out.push('\n');
+                out.push_str("    server {\n");
+                push_listen_lines(&mut out, route, http2_directive);
+                out.push('\n');
+                out.push_str("        location / {\n");
+                out.push_str(&format!("            return 503 '{message}';\n"));
+                out.push_str("            add_header Content-Type text/plain always;\n");
+                out.push_str("        }\n");
+                out.push_str("    }\n");
+                continue;
+            }
+            // Static routes serve files straight out of the image; there is
+            // no backend to proxy to or fall back from.
+            if route.is_static() {
+                out.push('\n');
+                out.push_str("    server {\n");
+                push_listen_lines(&mut out, route, http2_directive);
+                push_auth_lines(&mut out, route);
+                out.push_str(&format!(
+                    "        root /srv/static_{};\n",
+                    route.primary_port()
+                ));
+                out.push('\n');
+                out.push_str("        location / {\n");
+                out.push_str("            try_files $uri $uri/ =404;\n");
+                out.push_str("        }\n");
+                out.push_str("    }\n");
+                continue;
+            }
+            let container = config.find_container(&route.target);
+            let (target, static_root, rewrites, methods) = match container {
+                Some(container) => (
+                    config.backend_host(&route.target),
+                    container.static_root.clone(),
+                    container.response_rewrites.clone(),
+                    container.allowed_methods.clone(),
+                ),
+                // Validation should catch this; emit the raw target so the
+                // generated file still points at something inspectable.
+                None => (route.target.clone(), None, Vec::new(), None),
+            };
+            let tls_backend = container.map(|c| c.tls_backend).unwrap_or(false);
+            let tls_insecure = container.map(|c| c.tls_backend_insecure).unwrap_or(false);
+            let auth_request_url = container.and_then(|c| c.auth_request_url.clone());
+            let backend_https = tls_backend || route.backend_scheme == BackendScheme::Https;
+            let scheme = if backend_https { "https" } else { "http" };
+            out.push('\n');
+            out.push_str("    server {\n");
+            push_listen_lines(&mut out, route, http2_directive);
+            push_mtls_lines(&mut out, route);
+            push_auth_lines(&mut out, route);
+            if auth_request_url.is_some() {
+                out.push_str("        auth_request /_auth_request;\n");
+                out.push_str("        auth_request_set $auth_status $upstream_status;\n");
+            }
+            if let Some(host) = &route.host {
+                out.push_str(&format!("        server_name {host};\n"));
+            }
+            if let Some(root) = &static_root {
+                out.push_str(&format!("        root {root};\n"));
+            }
+            if !rewrites.is_empty() {
+                // Requires ngx_http_sub_module compiled into the image
+                // (present in the stock nginx builds).
+                out.push('\n');
+                out.push_str("        # Response rewriting needs ngx_http_sub_module.\n");
+                out.push_str("        sub_filter_once off;\n");
+                out.push_str(
+                    "        sub_filter_types text/html text/css application/javascript;\n",
+                );
+                for (from, to) in &rewrites {
+                    out.push_str(&format!("        sub_filter '{from}' '{to}';\n"));
+                }
+            }
+            out.push('\n');
+            // With a static root, files are served directly and only misses
+            // fall through to the proxied backend in the named location.
+            let proxy_location = if static_root.is_some() {
+                out.push_str("        location / {\n");
+                out.push_str("            try_files $uri $uri/ @proxy;\n");
+                out.push_str("        }\n");
+                out.push('\n');
+                "@proxy"
+            } else {
+                "/"
+            };
+            out.push_str(&format!("        location {proxy_location} {{\n"));
+            if let Some(max_conn) = route.max_conn {
+                out.push_str(&format!(
+                    "            limit_conn conn_{} {max_conn};\n",
+                    route.primary_port()
+                ));
+            }
+            if let Some(methods) = &methods {
+                out.push_str(&format!(
+                    "            limit_except {} {{ deny all; }}\n",
+                    methods.join(" ")
+                ));
+            }
+            // Resolve at request time via the variable so nginx starts even
+            // when the backend is down. The indirection only exists for the
+            // resolver, so with it off we proxy to the fixed name directly.
+            if route.canary.is_some() {
+                out.push_str(&format!(
+                    "            set $backend_addr $canary_{};\n",
+                    route.primary_port()
+                ));
+                out.push_str(&format!(
+                    "            proxy_pass {scheme}://$backend_addr;\n"
+                ));
+            } else if config.compact_routes || lb_directive(route.lb_method).is_some() {
+                // Either way an upstream group carrying this backend was
+                // emitted above; proxy to it by name.
+                out.push_str(&format!(
+                    "            proxy_pass {scheme}://{};\n",
+                    upstream_name(&target, route.internal_port)
+                ));
+            } else if resolver == "off" {
+                out.push_str(&format!(
+                    "            proxy_pass {scheme}://{}:{};\n",
+                    target, route.internal_port
+                ));
+            } else {
+                out.push_str(&format!(
+                    "            set $backend_addr {}:{};\n",
+                    target, route.internal_port
+                ));
+                out.push_str(&format!(
+                    "            proxy_pass {scheme}://$backend_addr;\n"
+                ));
+            }
+            if backend_https {
+                out.push_str("            proxy_ssl_server_name on;\n");
+                if route.backend_scheme == BackendScheme::Https {
+                    if route.verify_backend_tls {
+                        out.push_str("            proxy_ssl_verify on;\n");
+                        if route.backend_ca_file.is_some() {
+                            out.push_str(&format!(
+                                "            proxy_ssl_trusted_certificate /etc/nginx/ca/{}.crt;\n",
+                                route.primary_port()
+                            ));
+                        }
+                    } else {
+                        out.push_str("            proxy_ssl_verify off;\n");
+                    }
+                } else if tls_insecure {
+                    out.push_str("            proxy_ssl_verify off;\n");
+                }
+            }
+            out.push_str("            proxy_set_header Host $host;\n");
+            out.push_str("            proxy_set_header X-Real-IP $remote_addr;\n");
+            out.push_str(
+                "            proxy_set_header X-Forwarded-For $proxy_add_x_forwarded_for;\n",
+            );
+            out.push_str("            proxy_set_header X-Forwarded-Proto $scheme;\n");
+            out.push_str("        }\n");
+            out.push('\n');
+            if let Some(url) = &auth_request_url {
+                // The subrequest location is internal-only and proxies the
+                // original request headers to the auth service.
+                out.push_str("        location = /_auth_request {\n");
+                out.push_str("            internal;\n");
+                out.push_str(&format!("            proxy_pass {url};\n"));
+                out.push_str("            proxy_pass_request_body off;\n");
+                out.push_str("            proxy_set_header Content-Length \"\";\n");
+                out.push_str("            proxy_set_header X-Original-URI $request_uri;\n");
+                out.push_str("        }\n");
+                out.push('\n');
+            }
+            out.push_str("        error_page 502 503 504 = @fallback;\n");
+            out.push_str("        location @fallback {\n");
+            out.push_str(&format!("            return 503 '{FALLBACK_MESSAGE}';\n"));
+            out.push_str("            add_header Content-Type text/plain always;\n");
+            out.push_str("        }\n");
+            out.push_str("    }\n");
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Render the Dockerfile for the proxy image.
+    pub fn generate_dockerfile(config: &Config) -> String {
+        let mut out = format!(
+            "FROM {}\nCOPY nginx.conf /etc/nginx/nginx.conf\n",
+            config.base_image
+        );
+        for route in config.routes.iter().filter(|r| r.is_static() && !r.unbound) {
+            let port = route.primary_port();
+            out.push_str(&format!("COPY static_{port}/ /srv/static_{port}/\n"));
+        }
+        for route in config
+            .routes
+            .iter()
+            .filter(|r| r.basic_auth.is_some() && !r.unbound)
+        {
+            let port = route.primary_port();
+            out.push_str(&format!(
+                "COPY htpasswd_{port} /etc/nginx/htpasswd_{port}\n"
+            ));
+        }
+        for route in config
+            .routes
+            .iter()
+            .filter(|r| r.backend_ca_file.is_some() && !r.unbound)
+        {
+            let port = route.primary_port();
+            out.push_str(&format!("COPY ca_{port}.crt /etc/nginx/ca/{port}.crt\n"));
+        }
+        for route in config
+            .routes
+            .iter()
+            .filter(|r| r.mtls_ca_cert.is_some() && !r.unbound)
+        {
+            let port = route.primary_port();
+            out.push_str(&format!(
+                "COPY ca_client_{port}.crt /etc/nginx/ca/client_{port}.crt\n"
+            ));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Container;
+
+    fn config_with_route() -> Config {
+        let mut config = Config::default();
+        config.upsert_container(Container {
+            name: "app1".into(),
+            label: None,
+            port: 8080,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.set_route(8000, "app1", 8080);
+        config
+    }
+
+    #[test]
+    fn empty_config_has_only_the_internal_status_server() {
+        let conf = NginxConfigGenerator::generate(&Config::default());
+        assert_eq!(conf.matches("server {").count(), 1);
+        assert!(conf.contains(&format!("listen 127.0.0.1:{INTERNAL_STATUS_PORT};")));
+        assert!(conf.contains("http {"));
+    }
+
+    #[test]
+    fn management_port_emits_a_stub_status_server() {
+        let mut config = config_with_route();
+        config.management_port = Some(8099);
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("listen 8099;"));
+        assert_eq!(conf.matches("stub_status;").count(), 2);
+        // Without a management port only the internal loopback server
+        // carries stub_status.
+        let conf = NginxConfigGenerator::generate(&config_with_route());
+        assert_eq!(conf.matches("stub_status;").count(), 1);
+        assert!(!conf.contains("listen 8099;"));
+    }
+
+    #[test]
+    fn route_emits_listen_and_proxy_pass() {
+        let conf = NginxConfigGenerator::generate(&config_with_route());
+        assert!(conf.contains("listen 8000;"));
+        assert!(conf.contains("set $backend_addr app1:8080;"));
+        assert!(conf.contains("proxy_pass http://$backend_addr;"));
+    }
+
+    #[test]
+    fn multi_port_route_emits_one_listen_line_per_port() {
+        let mut config = config_with_route();
+        config.routes[0].host_ports = vec![80, 443];
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("listen 80;"));
+        assert!(conf.contains("listen 443;"));
+        // One route server plus the internal status server.
+        assert_eq!(conf.matches("server {").count(), 2);
+    }
+
+    #[test]
+    fn static_root_moves_proxying_into_a_named_location() {
+        let mut config = config_with_route();
+        config.find_container_mut("app1").unwrap().static_root =
+            Some("/srv/static/app1".to_string());
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("root /srv/static/app1;"));
+        assert!(conf.contains("try_files $uri $uri/ @proxy;"));
+        assert!(conf.contains("location @proxy {"));
+        assert!(!conf.contains("location / {\n            set $backend_addr"));
+    }
+
+    #[test]
+    fn canary_route_emits_split_clients() {
+        let mut config = config_with_route();
+        config.upsert_container(Container {
+            name: "app2".into(),
+            label: None,
+            port: 3000,
+            network: None,
+            static_root: None,
+            response_rewrites: Vec::new(),
+            allowed_methods: None,
+            tls_backend: false,
+            tls_backend_insecure: false,
+            auth_request_url: None,
+            description: None,
+            dns_aliases: Vec::new(),
+            aliases: Vec::new(),
+        });
+        config.routes[0].canary = Some(crate::config::Canary {
+            target: "app2".into(),
+            internal_port: 3000,
+            percent: 10,
+        });
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("split_clients \"${remote_addr}${remote_port}\" $canary_8000 {"));
+        assert!(conf.contains("10% app2:3000;"));
+        assert!(conf.contains("* app1:8080;"));
+        assert!(conf.contains("set $backend_addr $canary_8000;"));
+    }
+
+    #[test]
+    fn static_route_serves_files_with_no_backend() {
+        let mut config = Config::default();
+        config.set_static_route(8008, "/tmp/site".into());
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("listen 8008;"));
+        assert!(conf.contains("root /srv/static_8008;"));
+        assert!(conf.contains("try_files $uri $uri/ =404;"));
+        assert!(!conf.contains("proxy_pass"));
+        let dockerfile = NginxConfigGenerator::generate_dockerfile(&config);
+        assert!(dockerfile.contains("COPY static_8008/ /srv/static_8008/"));
+    }
+
+    #[test]
+    fn response_rewrites_emit_sub_filters() {
+        let mut config = config_with_route();
+        config.find_container_mut("app1").unwrap().response_rewrites = vec![
+            ("http://app1.internal".into(), "/".into()),
+            ("ws://app1.internal".into(), "/ws".into()),
+        ];
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("sub_filter_once off;"));
+        assert!(conf.contains("sub_filter_types text/html text/css application/javascript;"));
+        assert!(conf.contains("sub_filter 'http://app1.internal' '/';"));
+        assert!(conf.contains("sub_filter 'ws://app1.internal' '/ws';"));
+        assert!(conf.contains("ngx_http_sub_module"));
+    }
+
+    #[test]
+    fn maintenance_route_returns_503_without_proxying() {
+        let mut config = config_with_route();
+        config.routes[0].maintenance = true;
+        config.maintenance_message = Some("Back soon".to_string());
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("return 503 'Back soon';"));
+        assert!(!conf.contains("proxy_pass"));
+        assert!(!conf.contains("split_clients"));
+    }
+
+    #[test]
+    fn tls_route_listens_with_ssl_and_cert_paths() {
+        let mut config = config_with_route();
+        config.routes[0].tls = true;
+        let conf = NginxConfigGenerator::generate(&config);
+        // Default base image has no version tag, so the listen form is used.
+        assert!(conf.contains("listen 8000 ssl http2;"));
+        assert!(conf.contains("ssl_certificate /etc/nginx/certs/8000.crt;"));
+        assert!(conf.contains("ssl_certificate_key /etc/nginx/certs/8000.key;"));
+        assert!(!conf.contains("http2 on;"));
+    }
+
+    #[test]
+    fn modern_base_image_uses_the_http2_directive() {
+        let mut config = config_with_route();
+        config.base_image = "nginx:1.27.2-alpine".to_string();
+        config.routes[0].tls = true;
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("listen 8000 ssl;"));
+        assert!(conf.contains("http2 on;"));
+    }
+
+    #[test]
+    fn http2_can_be_disabled_and_never_applies_without_tls() {
+        let mut config = config_with_route();
+        config.routes[0].tls = true;
+        config.routes[0].http2 = Some(false);
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("listen 8000 ssl;"));
+        assert!(!conf.contains("http2"));
+
+        config.routes[0].tls = false;
+        config.routes[0].http2 = Some(true);
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("listen 8000;"));
+        assert!(!conf.contains("http2"));
+        assert!(!conf.contains("ssl"));
+    }
+
+    #[test]
+    fn http2_directive_detection_parses_image_tags() {
+        assert!(supports_http2_directive("nginx:1.25.1"));
+        assert!(supports_http2_directive("nginx:1.27.2-alpine"));
+        assert!(supports_http2_directive("nginx:1.26"));
+        assert!(!supports_http2_directive("nginx:1.24.0"));
+        assert!(!supports_http2_directive("nginx:alpine"));
+        assert!(!supports_http2_directive("nginx"));
+    }
+
+    #[test]
+    fn auth_request_emits_the_subrequest_plumbing() {
+        let mut config = config_with_route();
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(!conf.contains("auth_request"));
+
+        config.find_container_mut("app1").unwrap().auth_request_url =
+            Some("http://auth-service:9000/verify".to_string());
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("        auth_request /_auth_request;"));
+        assert!(conf.contains("        auth_request_set $auth_status $upstream_status;"));
+        assert!(conf.contains("        location = /_auth_request {"));
+        assert!(conf.contains("            internal;"));
+        assert!(conf.contains("            proxy_pass http://auth-service:9000/verify;"));
+    }
+
+    #[test]
+    fn max_conn_emits_one_zone_and_a_location_limit() {
+        let mut config = config_with_route();
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(!conf.contains("limit_conn"));
+
+        let route = config.routes.first_mut().unwrap();
+        route.max_conn = Some(10);
+        // Extra host ports must not duplicate the zone declaration.
+        route.host_ports.push(8001);
+        let conf = NginxConfigGenerator::generate(&config);
+        assert_eq!(
+            conf.matches("limit_conn_zone $binary_remote_addr zone=conn_8000:10m;")
+                .count(),
+            1
+        );
+        assert_eq!(conf.matches("limit_conn_zone").count(), 1);
+        assert!(conf.contains("            limit_conn conn_8000 10;"));
+    }
+
+    #[test]
+    fn tls_backend_flips_the_scheme_and_enables_sni() {
+        let mut config = config_with_route();
+        config.find_container_mut("app1").unwrap().tls_backend = true;
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("proxy_pass https://$backend_addr;"));
+        assert!(conf.contains("proxy_ssl_server_name on;"));
+        assert!(!conf.contains("proxy_ssl_verify off;"));
+
+        config
+            .find_container_mut("app1")
+            .unwrap()
+            .tls_backend_insecure = true;
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("proxy_ssl_verify off;"));
+    }
+
+    #[test]
+    fn qualified_names_reach_secondary_networks() {
+        let mut config = config_with_route();
+        config.qualify_network_names = true;
+        config.find_container_mut("app1").unwrap().network = Some("backend-net".into());
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("set $backend_addr app1.backend-net:8080;"));
+
+        // Containers on the default network keep the short name.
+        config.find_container_mut("app1").unwrap().network = None;
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("set $backend_addr app1:8080;"));
+    }
+
+    #[test]
+    fn lb_methods_get_an_upstream_group() {
+        let mut config = config_with_route();
+        config.routes[0].lb_method = Some(LbMethod::LeastConn);
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("upstream upstream_app1_8080 {"));
+        assert!(conf.contains("        least_conn;"));
+        assert!(conf.contains("proxy_pass http://upstream_app1_8080;"));
+
+        config.routes[0].lb_method = Some(LbMethod::IpHash);
+        assert!(NginxConfigGenerator::generate(&config).contains("        ip_hash;"));
+        config.routes[0].lb_method = Some(LbMethod::Random);
+        assert!(NginxConfigGenerator::generate(&config).contains("        random;"));
+
+        // Round-robin, explicit or unset, emits nothing special.
+        config.routes[0].lb_method = Some(LbMethod::RoundRobin);
+        assert!(!NginxConfigGenerator::generate(&config).contains("upstream "));
+        config.routes[0].lb_method = None;
+        assert!(!NginxConfigGenerator::generate(&config).contains("upstream "));
+    }
+
+    #[test]
+    fn compact_upstreams_carry_the_lb_directive() {
+        let mut config = config_with_route();
+        config.compact_routes = true;
+        config.routes[0].lb_method = Some(LbMethod::LeastConn);
+        let conf = NginxConfigGenerator::generate(&config);
+        assert_eq!(conf.matches("upstream ").count(), 1);
+        assert!(conf.contains("        least_conn;"));
+    }
+
+    #[test]
+    fn https_backend_scheme_controls_verification() {
+        let mut config = config_with_route();
+        config.routes[0].backend_scheme = BackendScheme::Https;
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("proxy_pass https://$backend_addr;"));
+        assert!(conf.contains("proxy_ssl_server_name on;"));
+        assert!(conf.contains("proxy_ssl_verify on;"));
+        assert!(!conf.contains("proxy_ssl_trusted_certificate"));
+
+        config.routes[0].verify_backend_tls = false;
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("proxy_ssl_verify off;"));
+        assert!(!conf.contains("proxy_ssl_verify on;"));
+
+        config.routes[0].verify_backend_tls = true;
+        config.routes[0].backend_ca_file = Some(std::path::PathBuf::from("/tmp/ca.crt"));
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("proxy_ssl_trusted_certificate /etc/nginx/ca/8000.crt;"));
+        let dockerfile = NginxConfigGenerator::generate_dockerfile(&config);
+        assert!(dockerfile.contains("COPY ca_8000.crt /etc/nginx/ca/8000.crt"));
+    }
+
+    #[test]
+    fn mtls_routes_demand_client_certificates() {
+        let mut config = config_with_route();
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(!conf.contains("ssl_verify_client"));
+
+        config.routes[0].tls = true;
+        config.routes[0].mtls_ca_cert = Some(std::path::PathBuf::from("/tmp/clients.crt"));
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("ssl_client_certificate /etc/nginx/ca/client_8000.crt;"));
+        assert!(conf.contains("ssl_verify_client on;"));
+
+        config.routes[0].mtls_verify = Some(MtlsVerify::Optional);
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("ssl_verify_client optional;"));
+        let dockerfile = NginxConfigGenerator::generate_dockerfile(&config);
+        assert!(dockerfile.contains("COPY ca_client_8000.crt /etc/nginx/ca/client_8000.crt"));
+    }
+
+    #[test]
+    fn plain_backends_keep_http_and_no_ssl_directives() {
+        let conf = NginxConfigGenerator::generate(&config_with_route());
+        assert!(conf.contains("proxy_pass http://$backend_addr;"));
+        assert!(!conf.contains("proxy_ssl"));
+    }
+
+    #[test]
+    fn compact_routes_share_one_upstream_per_backend() {
+        let mut config = config_with_route();
+        config.compact_routes = true;
+        config.set_route(8001, "app1", 8080);
+        let conf = NginxConfigGenerator::generate(&config);
+        assert_eq!(conf.matches("upstream upstream_app1_8080 {").count(), 1);
+        assert_eq!(
+            conf.matches("proxy_pass http://upstream_app1_8080;")
+                .count(),
+            2
+        );
+        assert!(conf.contains("server app1:8080;"));
+        assert!(!conf.contains("set $backend_addr"));
+        // Both ports still get their own server block.
+        assert!(conf.contains("listen 8000;"));
+        assert!(conf.contains("listen 8001;"));
+    }
+
+    #[test]
+    fn compact_routes_off_keeps_per_block_addresses() {
+        let mut config = config_with_route();
+        config.set_route(8001, "app1", 8080);
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(!conf.contains("upstream "));
+        assert_eq!(conf.matches("set $backend_addr app1:8080;").count(), 2);
+    }
+
+    #[test]
+    fn allowed_methods_emit_limit_except() {
+        let mut config = config_with_route();
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(!conf.contains("limit_except"));
+
+        config.find_container_mut("app1").unwrap().allowed_methods = Some(vec!["GET".to_string()]);
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("limit_except GET { deny all; }"));
+
+        config.find_container_mut("app1").unwrap().allowed_methods =
+            Some(vec!["GET".to_string(), "POST".to_string()]);
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("limit_except GET POST { deny all; }"));
+    }
+
+    #[test]
+    fn basic_auth_directives_appear_only_on_protected_routes() {
+        let mut config = config_with_route();
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(!conf.contains("auth_basic"));
+
+        config.routes[0].basic_auth = Some(crate::config::BasicAuth {
+            username: "alice".into(),
+            password_hash: "$apr1$x$y".into(),
+        });
+        let conf = NginxConfigGenerator::generate(&config);
+        assert!(conf.contains("auth_basic \"Restricted\";"));
+        assert!(conf.contains("auth_basic_user_file /etc/nginx/htpasswd_8000;"));
+        let dockerfile = NginxConfigGenerator::generate_dockerfile(&config);
+        assert!(dockerfile.contains("COPY htpasswd_8000 /etc/nginx/htpasswd_8000"));
+    }
+
+    #[test]
+    fn dockerfile_uses_base_image() {
+        let dockerfile = NginxConfigGenerator::generate_dockerfile(&Config::default());
+        assert!(dockerfile.starts_with("FROM nginx:alpine\n"));
+        assert!(dockerfile.contains("COPY nginx.conf"));
+    }
+}
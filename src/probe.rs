@@ -0,0 +1,279 @@
+//! Route probing: the `test <port>` command.
+//!
+//! Performs one HTTP request against a routed host port and reports what
+//! came back, detecting the proxy's own 503 fallback page so "backend
+//! down" does not masquerade as a backend response. The HTTP client sits
+//! behind a trait so redirect handling and reporting are unit-testable.
+
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+
+use crate::config::Config;
+use crate::nginx::FALLBACK_MESSAGE;
+
+/// Redirects followed before giving up.
+pub const MAX_REDIRECTS: usize = 3;
+
+/// Request timeout; probes target localhost, so anything slower than this
+/// is as good as down.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Body bytes included in the report.
+const BODY_PREVIEW_BYTES: usize = 500;
+
+/// One HTTP response as seen by a probe.
+#[derive(Debug, Clone)]
+pub struct ProbeResponse {
+    pub status: u16,
+    /// Header name/value pairs, names lower-cased.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl ProbeResponse {
+    /// First value of a header, by lower-cased name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Minimal HTTP client: one GET, redirects not followed.
+#[async_trait]
+pub trait HttpClient: Send + Sync {
+    async fn get(&self, url: &str) -> Result<ProbeResponse>;
+}
+
+/// Real client backed by reqwest.
+pub struct ReqwestClient {
+    client: reqwest::Client,
+}
+
+impl ReqwestClient {
+    pub fn new() -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(TIMEOUT)
+            .build()
+            .context("failed to build HTTP client")?;
+        Ok(Self { client })
+    }
+}
+
+#[async_trait]
+impl HttpClient for ReqwestClient {
+    async fn get(&self, url: &str) -> Result<ProbeResponse> {
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .with_context(|| format!("request to {url} failed"))?;
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(k, v)| {
+                (
+                    k.as_str().to_ascii_lowercase(),
+                    String::from_utf8_lossy(v.as_bytes()).into_owned(),
+                )
+            })
+            .collect();
+        let body = response.text().await.unwrap_or_default();
+        Ok(ProbeResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+/// GET `url`, following up to [`MAX_REDIRECTS`] redirects. Returns the
+/// final response and the number of redirects taken.
+pub async fn probe(client: &dyn HttpClient, url: &str) -> Result<(ProbeResponse, usize)> {
+    let mut url = url.to_string();
+    for hops in 0..=MAX_REDIRECTS {
+        let response = client.get(&url).await?;
+        if !(300..400).contains(&response.status) {
+            return Ok((response, hops));
+        }
+        let Some(location) = response.header("location") else {
+            return Ok((response, hops));
+        };
+        url = resolve_location(&url, location);
+    }
+    bail!("gave up after {MAX_REDIRECTS} redirects")
+}
+
+/// Resolve a Location header against the current URL: absolute locations
+/// win, anything else is taken as a path on the same origin.
+fn resolve_location(current: &str, location: &str) -> String {
+    if location.starts_with("http://") || location.starts_with("https://") {
+        return location.to_string();
+    }
+    let origin_end = current
+        .find("://")
+        .and_then(|scheme| current[scheme + 3..].find('/').map(|p| scheme + 3 + p))
+        .unwrap_or(current.len());
+    format!("{}{}", &current[..origin_end], location)
+}
+
+/// Render the human report for a probe, flagging the proxy's own fallback
+/// page when the backend behind `host_port` appears down.
+pub fn render_report(
+    config: &Config,
+    host_port: u16,
+    response: &ProbeResponse,
+    redirects: usize,
+    elapsed: Duration,
+) -> Vec<String> {
+    let mut lines = vec![format!("HTTP {}", response.status)];
+    for name in ["server", "content-type"] {
+        if let Some(value) = response.header(name) {
+            lines.push(format!("{name}: {value}"));
+        }
+    }
+    for (name, value) in &response.headers {
+        if name.starts_with("x-forwarded-") {
+            lines.push(format!("{name}: {value}"));
+        }
+    }
+    if redirects > 0 {
+        lines.push(format!("Redirects followed: {redirects}"));
+    }
+    lines.push(format!("Time: {} ms", elapsed.as_millis()));
+    if response.status == 503 && response.body.contains(FALLBACK_MESSAGE) {
+        let target = config
+            .find_route(host_port)
+            .map(|r| r.target.as_str())
+            .unwrap_or("unknown");
+        lines.push(format!(
+            "hit proxy fallback page — backend '{target}' appears down"
+        ));
+    }
+    let preview: String = response.body.chars().take(BODY_PREVIEW_BYTES).collect();
+    if !preview.is_empty() {
+        lines.push(String::new());
+        lines.push(preview);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Scripted client returning canned responses per URL.
+    #[derive(Default)]
+    struct FakeClient {
+        responses: Vec<(String, ProbeResponse)>,
+        requests: Mutex<Vec<String>>,
+    }
+
+    impl FakeClient {
+        fn with(mut self, url: &str, status: u16, headers: &[(&str, &str)], body: &str) -> Self {
+            self.responses.push((
+                url.to_string(),
+                ProbeResponse {
+                    status,
+                    headers: headers
+                        .iter()
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect(),
+                    body: body.to_string(),
+                },
+            ));
+            self
+        }
+    }
+
+    #[async_trait]
+    impl HttpClient for FakeClient {
+        async fn get(&self, url: &str) -> Result<ProbeResponse> {
+            self.requests.lock().unwrap().push(url.to_string());
+            self.responses
+                .iter()
+                .find(|(u, _)| u == url)
+                .map(|(_, r)| r.clone())
+                .ok_or_else(|| anyhow::anyhow!("no canned response for {url}"))
+        }
+    }
+
+    #[tokio::test]
+    async fn probe_follows_relative_and_absolute_redirects() {
+        let client = FakeClient::default()
+            .with("http://127.0.0.1:8000/", 302, &[("location", "/login")], "")
+            .with(
+                "http://127.0.0.1:8000/login",
+                301,
+                &[("location", "http://127.0.0.1:8000/auth")],
+                "",
+            )
+            .with("http://127.0.0.1:8000/auth", 200, &[], "welcome");
+        let (response, redirects) = probe(&client, "http://127.0.0.1:8000/").await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(redirects, 2);
+    }
+
+    #[tokio::test]
+    async fn probe_gives_up_after_the_redirect_cap() {
+        // Every hop redirects back to itself.
+        let client =
+            FakeClient::default().with("http://127.0.0.1:8000/", 302, &[("location", "/")], "");
+        let err = probe(&client, "http://127.0.0.1:8000/").await.unwrap_err();
+        assert!(err.to_string().contains("redirects"));
+        assert_eq!(client.requests.lock().unwrap().len(), MAX_REDIRECTS + 1);
+    }
+
+    #[test]
+    fn report_flags_the_fallback_page() {
+        let mut config = Config::default();
+        config.set_route(8000, "app1", 8080);
+        let response = ProbeResponse {
+            status: 503,
+            headers: vec![("content-type".into(), "text/plain".into())],
+            body: FALLBACK_MESSAGE.to_string(),
+        };
+        let lines = render_report(&config, 8000, &response, 0, Duration::from_millis(12));
+        assert!(lines[0] == "HTTP 503");
+        assert!(lines
+            .iter()
+            .any(|l| l.contains("backend 'app1' appears down")));
+        // A backend's own 503 is not mistaken for the fallback.
+        let response = ProbeResponse {
+            body: "backend says no".to_string(),
+            ..response
+        };
+        let lines = render_report(&config, 8000, &response, 0, Duration::from_millis(12));
+        assert!(!lines.iter().any(|l| l.contains("appears down")));
+    }
+
+    #[test]
+    fn report_echoes_forwarding_headers_and_truncates_the_body() {
+        let response = ProbeResponse {
+            status: 200,
+            headers: vec![
+                ("server".into(), "nginx".into()),
+                ("x-forwarded-for".into(), "172.18.0.1".into()),
+            ],
+            body: "x".repeat(2000),
+        };
+        let lines = render_report(
+            &Config::default(),
+            8000,
+            &response,
+            1,
+            Duration::from_millis(3),
+        );
+        assert!(lines.contains(&"server: nginx".to_string()));
+        assert!(lines.contains(&"x-forwarded-for: 172.18.0.1".to_string()));
+        assert!(lines.contains(&"Redirects followed: 1".to_string()));
+        assert_eq!(lines.last().unwrap().len(), 500);
+    }
+}
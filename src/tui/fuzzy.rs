@@ -0,0 +1,83 @@
+//! Tiny case-insensitive subsequence matcher for the container palette.
+//!
+//! Deliberately simpler than a full fzf-style scorer: candidates match when
+//! the query is a subsequence, and scoring prefers prefix and consecutive
+//! matches so exact-ish hits sort first.
+
+/// Score `candidate` against `query`; `None` when the query is not a
+/// subsequence of the candidate. Higher scores are better matches. An empty
+/// query matches everything with score 0.
+pub fn score(query: &str, candidate: &str) -> Option<u32> {
+    if query.is_empty() {
+        return Some(0);
+    }
+    let candidate_chars: Vec<char> = candidate.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0u32;
+    let mut pos = 0usize;
+    let mut previous_hit: Option<usize> = None;
+    for qc in query.chars().flat_map(char::to_lowercase) {
+        let hit = candidate_chars[pos..].iter().position(|c| *c == qc)? + pos;
+        score += match previous_hit {
+            // Consecutive matches beat scattered ones.
+            Some(prev) if hit == prev + 1 => 3,
+            _ => 1,
+        };
+        if hit == 0 {
+            // A match at the very start is the strongest signal.
+            score += 2;
+        }
+        previous_hit = Some(hit);
+        pos = hit + 1;
+    }
+    Some(score)
+}
+
+/// Indices of `candidates` matching `query`, best score first; ties keep the
+/// original order.
+pub fn rank(query: &str, candidates: &[String]) -> Vec<usize> {
+    let mut scored: Vec<(usize, u32)> = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(i, c)| score(query, c).map(|s| (i, s)))
+        .collect();
+    scored.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    scored.into_iter().map(|(i, _)| i).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn empty_query_matches_everything_in_order() {
+        let candidates = names(&["beta", "alpha"]);
+        assert_eq!(rank("", &candidates), vec![0, 1]);
+    }
+
+    #[test]
+    fn non_subsequence_does_not_match() {
+        assert_eq!(score("xyz", "app1"), None);
+        assert_eq!(score("appa", "app1"), None);
+        let candidates = names(&["app1", "app2"]);
+        assert!(rank("web", &candidates).is_empty());
+    }
+
+    #[test]
+    fn matching_is_case_insensitive() {
+        assert!(score("APP", "app1").is_some());
+        assert_eq!(score("app", "APP1"), score("APP", "app1"));
+    }
+
+    #[test]
+    fn prefix_and_consecutive_matches_rank_first() {
+        let candidates = names(&["magnate", "api-gateway", "gateway"]);
+        let ranked = rank("gate", &candidates);
+        // "gateway" starts with the query, "api-gateway" contains it
+        // consecutively, "magnate" only as a scattered subsequence.
+        assert_eq!(ranked, vec![2, 1, 0]);
+    }
+}
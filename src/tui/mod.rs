@@ -0,0 +1,1648 @@
+//! Terminal UI built on ratatui.
+//!
+//! The TUI is a thin presentation layer over [`App`]: it keeps a periodically
+//! refreshed snapshot of the config and Docker state and funnels every
+//! mutation through a confirmation [`Modal`].
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+    KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Row, Table, Tabs};
+use ratatui::{Frame, Terminal};
+use regex::Regex;
+
+use crate::app::App;
+use crate::config::Config;
+use crate::docker::NetworkInfo;
+
+mod fuzzy;
+
+/// Interval between background refreshes of Docker state.
+const TICK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Number of log lines kept in the Logs tab.
+const LOG_TAIL: u32 = 200;
+
+/// Number of operations kept in the Recent Commands history.
+const HISTORY_LIMIT: usize = 20;
+
+/// Two clicks on the same spot within this window count as a double-click.
+const DOUBLE_CLICK_WINDOW: Duration = Duration::from_millis(400);
+
+/// Smallest terminal the tab layout can render into; anything below this
+/// shows a plain resize hint instead of panicking in Layout/Table math.
+const MIN_WIDTH: u16 = 20;
+const MIN_HEIGHT: u16 = 6;
+
+/// The visible tabs, in display order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tab {
+    Status,
+    Containers,
+    Routes,
+    Networks,
+    Logs,
+}
+
+impl Tab {
+    const ALL: [Tab; 5] = [
+        Tab::Status,
+        Tab::Containers,
+        Tab::Routes,
+        Tab::Networks,
+        Tab::Logs,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            Tab::Status => "Status",
+            Tab::Containers => "Containers",
+            Tab::Routes => "Routes",
+            Tab::Networks => "Networks",
+            Tab::Logs => "Logs",
+        }
+    }
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|t| *t == self).unwrap_or(0)
+    }
+
+    fn next(self) -> Tab {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+}
+
+/// A pending action awaiting confirmation through a [`Modal::Confirm`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModalAction {
+    StartProxy,
+    StopProxy,
+    /// Reload already previewed and approved through a [`Modal::Diff`].
+    ConfirmedReload,
+    RemoveContainer(String),
+    StopRoute(u16),
+    CreateNetwork(String),
+}
+
+/// Popup state rendered above the active tab.
+#[derive(Debug, Clone)]
+pub enum Modal {
+    /// Yes/no confirmation executing `action` on `y`.
+    Confirm {
+        message: String,
+        action: ModalAction,
+    },
+    /// Informational popup dismissed with any key.
+    Message(String),
+    /// Fuzzy-searchable container palette for quick route switching. `port`
+    /// is the route being retargeted, or `None` when invoked globally (a
+    /// port prompt follows the pick).
+    Palette {
+        query: String,
+        selected: usize,
+        port: Option<u16>,
+    },
+    /// Port input shown after a global palette pick.
+    PortPrompt { target: String, input: String },
+    /// Unified diff of the deployed vs. proposed nginx config, shown before
+    /// a reload; `y` proceeds, anything else cancels.
+    Diff { old: String, new: String },
+    /// Full-width command palette over every TUI action.
+    Commands { query: String, selected: usize },
+    /// Containers attached to a network, pre-rendered on open.
+    NetworkDetail(String),
+    /// Recent Commands popup; `scroll` counts entries skipped from the top.
+    History { scroll: usize },
+}
+
+/// A palette entry: display name and the handler it triggers.
+type Command = (&'static str, fn(&mut TuiApp));
+
+/// Every action the command palette can trigger, matched by substring.
+/// Each entry behaves exactly like its key shortcut.
+const COMMANDS: &[Command] = &[
+    ("start proxy", |t| {
+        t.confirm("Start the proxy?", ModalAction::StartProxy)
+    }),
+    ("stop proxy", |t| {
+        t.confirm("Stop the proxy?", ModalAction::StopProxy)
+    }),
+    ("reload config", TuiApp::open_reload_diff),
+    ("switch route target", |t| t.open_palette(None)),
+    ("remove selected", TuiApp::delete_selected),
+    ("create selected network", TuiApp::create_selected_network),
+    ("cycle tag filter", TuiApp::cycle_tag_filter),
+    ("recent commands", |t| {
+        t.modal = Some(Modal::History { scroll: 0 })
+    }),
+    ("next tab", |t| t.tab = t.tab.next()),
+    ("quit", |t| t.should_quit = true),
+];
+
+/// Indices of [`COMMANDS`] whose name contains `query` (case-insensitive).
+fn command_matches(query: &str) -> Vec<usize> {
+    let query = query.to_lowercase();
+    COMMANDS
+        .iter()
+        .enumerate()
+        .filter(|(_, (name, _))| name.contains(&query))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Screen regions recorded during the last draw, so mouse events can be
+/// mapped back to the widget under the cursor.
+#[derive(Debug, Clone, Copy, Default)]
+struct HitAreas {
+    tabs: Rect,
+    body: Rect,
+}
+
+/// State of the running TUI.
+pub struct TuiApp {
+    app: App,
+    tab: Tab,
+    config: Config,
+    proxy_status: Option<String>,
+    proxy_age: Option<std::time::Duration>,
+    /// Docker status per configured container name, refreshed on tick.
+    container_statuses: HashMap<String, Option<String>>,
+    network_infos: Vec<NetworkInfo>,
+    logs: Vec<String>,
+    /// Active log filter; matching lines are highlighted, the rest dimmed.
+    log_filter: Option<Regex>,
+    /// Filter pattern being typed after `/`; captures keys until Enter.
+    log_filter_input: Option<String>,
+    /// Lines scrolled up from the tail of the log.
+    log_scroll: usize,
+    container_selected: usize,
+    route_selected: usize,
+    /// Active tag filter on the Routes tab, if any.
+    route_tag_filter: Option<String>,
+    network_selected: usize,
+    modal: Option<Modal>,
+    /// In-flight background switch/reload, polled by the main loop.
+    pending_reload: Option<tokio::task::JoinHandle<Result<Vec<String>>>>,
+    /// Description of the in-flight switch, recorded to the history when
+    /// it finishes.
+    pending_label: Option<String>,
+    /// Most recent operations, newest last; shown by the `H` popup.
+    history: VecDeque<HistoryEntry>,
+    /// Widget regions from the last frame, for mouse hit-testing.
+    hit_areas: HitAreas,
+    /// Last left-click, for double-click detection.
+    last_click: Option<(Instant, (u16, u16))>,
+    spinner: usize,
+    should_quit: bool,
+    /// Set from outside the event loop (the SIGTERM handler) to ask for a
+    /// clean shutdown on the next iteration.
+    quit_signal: Arc<AtomicBool>,
+    last_tick: Instant,
+}
+
+impl TuiApp {
+    pub fn new(app: App) -> Result<Self> {
+        let config = app.config_manager().get().clone();
+        Ok(Self {
+            app,
+            tab: Tab::Status,
+            config,
+            proxy_status: None,
+            proxy_age: None,
+            container_statuses: HashMap::new(),
+            network_infos: Vec::new(),
+            logs: Vec::new(),
+            log_filter: None,
+            log_filter_input: None,
+            log_scroll: 0,
+            container_selected: 0,
+            route_selected: 0,
+            route_tag_filter: None,
+            network_selected: 0,
+            modal: None,
+            pending_reload: None,
+            pending_label: None,
+            history: VecDeque::new(),
+            hit_areas: HitAreas::default(),
+            last_click: None,
+            spinner: 0,
+            should_quit: false,
+            quit_signal: Arc::new(AtomicBool::new(false)),
+            last_tick: Instant::now() - TICK_INTERVAL,
+        })
+    }
+
+    /// Main event loop; draws, polls input and refreshes Docker state.
+    pub async fn run(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    ) -> Result<()> {
+        // Callers may set up the terminal themselves without going through
+        // [`run_tui`]; make sure a panic still restores it either way.
+        install_panic_hook(crash_log_path(self.app.store().config_dir()));
+        while !self.should_quit && !self.quit_signal.load(Ordering::Relaxed) {
+            if self.last_tick.elapsed() >= TICK_INTERVAL {
+                self.on_tick().await;
+                self.last_tick = Instant::now();
+            }
+            self.poll_pending_reload().await;
+            terminal.draw(|frame| self.draw(frame))?;
+            if event::poll(Duration::from_millis(100))? {
+                match event::read()? {
+                    Event::Key(key) if key.kind == KeyEventKind::Press => {
+                        self.on_key(key).await?;
+                    }
+                    Event::Mouse(mouse) => self.on_mouse(mouse),
+                    _ => {}
+                }
+            }
+        }
+        // Quitting must not abort a reload halfway through its docker
+        // calls; let it run to completion before tearing down.
+        if let Some(handle) = self.pending_reload.take() {
+            let _ = handle.await;
+        }
+        Ok(())
+    }
+
+    /// Flag that stops the event loop when set; hand it to a signal
+    /// handler so SIGTERM quits as cleanly as pressing 'q'.
+    pub fn quit_signal(&self) -> Arc<AtomicBool> {
+        self.quit_signal.clone()
+    }
+
+    /// Harvest a finished background switch, surfacing errors in a modal.
+    async fn poll_pending_reload(&mut self) {
+        self.spinner = self.spinner.wrapping_add(1);
+        let finished = self
+            .pending_reload
+            .as_ref()
+            .is_some_and(tokio::task::JoinHandle::is_finished);
+        if !finished {
+            return;
+        }
+        let handle = self.pending_reload.take().expect("checked above");
+        let outcome = handle.await;
+        let success = matches!(outcome, Ok(Ok(_)));
+        match outcome {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => self.modal = Some(Modal::Message(format!("Error: {e:#}"))),
+            Err(e) => self.modal = Some(Modal::Message(format!("Error: switch task failed: {e}"))),
+        }
+        if let Some(description) = self.pending_label.take() {
+            push_history(
+                &mut self.history,
+                HistoryEntry {
+                    timestamp: Instant::now(),
+                    description,
+                    success,
+                },
+            );
+        }
+        // Pick up the new route state immediately.
+        self.last_tick = Instant::now() - TICK_INTERVAL;
+    }
+
+    /// Periodic refresh of config and Docker state.
+    async fn on_tick(&mut self) {
+        // Pick up external config edits, then mirror the shared state.
+        let _ = self.app.config_manager().reload();
+        self.config = self.app.config_manager().get().clone();
+        self.proxy_status = self
+            .app
+            .docker()
+            .get_container_status(&self.config.proxy_name)
+            .await
+            .ok()
+            .flatten();
+        self.proxy_age = self
+            .app
+            .docker()
+            .container_age(&self.config.proxy_name)
+            .await
+            .ok()
+            .flatten();
+        let mut statuses = HashMap::new();
+        for container in &self.config.containers {
+            let status = self
+                .app
+                .docker()
+                .get_container_status(&container.name)
+                .await
+                .ok()
+                .flatten();
+            statuses.insert(container.name.clone(), status);
+        }
+        self.container_statuses = statuses;
+        if let Ok(networks) = self.app.docker().list_networks().await {
+            self.network_infos = networks;
+        }
+        if self.tab == Tab::Logs {
+            if let Ok(lines) = self
+                .app
+                .docker()
+                .get_logs(&self.config.proxy_name, Some(LOG_TAIL), None)
+                .await
+            {
+                self.logs = lines;
+            }
+        }
+    }
+
+    async fn on_key(&mut self, key: KeyEvent) -> Result<()> {
+        let code = key.code;
+        // A visible modal captures all input.
+        if let Some(modal) = self.modal.clone() {
+            match modal {
+                Modal::Confirm { action, .. } => match code {
+                    KeyCode::Char('y') | KeyCode::Enter => {
+                        self.modal = None;
+                        self.execute_action(action).await;
+                    }
+                    _ => self.modal = None,
+                },
+                Modal::Message(_) | Modal::NetworkDetail(_) => self.modal = None,
+                Modal::History { mut scroll } => {
+                    match code {
+                        KeyCode::Up | KeyCode::Char('k') => scroll = scroll.saturating_sub(1),
+                        KeyCode::Down | KeyCode::Char('j') => {
+                            scroll = (scroll + 1).min(self.history.len().saturating_sub(1));
+                        }
+                        _ => {
+                            self.modal = None;
+                            return Ok(());
+                        }
+                    }
+                    self.modal = Some(Modal::History { scroll });
+                    return Ok(());
+                }
+                Modal::Diff { .. } => match code {
+                    KeyCode::Char('y') | KeyCode::Enter => {
+                        self.modal = None;
+                        self.execute_action(ModalAction::ConfirmedReload).await;
+                    }
+                    _ => self.modal = None,
+                },
+                Modal::Palette {
+                    mut query,
+                    mut selected,
+                    port,
+                } => {
+                    match code {
+                        KeyCode::Esc => {
+                            self.modal = None;
+                            return Ok(());
+                        }
+                        KeyCode::Enter => {
+                            let matches = self.palette_matches(&query);
+                            self.modal = None;
+                            if let Some(target) = matches.get(selected).cloned() {
+                                match port {
+                                    Some(p) => self.start_background_switch(p, target),
+                                    None => {
+                                        self.modal = Some(Modal::PortPrompt {
+                                            target,
+                                            input: String::new(),
+                                        })
+                                    }
+                                }
+                            }
+                            return Ok(());
+                        }
+                        KeyCode::Up => selected = selected.saturating_sub(1),
+                        KeyCode::Down => selected += 1,
+                        KeyCode::Backspace => {
+                            query.pop();
+                            selected = 0;
+                        }
+                        KeyCode::Char(c) => {
+                            query.push(c);
+                            selected = 0;
+                        }
+                        _ => {}
+                    }
+                    let len = self.palette_matches(&query).len();
+                    selected = if len == 0 { 0 } else { selected.min(len - 1) };
+                    self.modal = Some(Modal::Palette {
+                        query,
+                        selected,
+                        port,
+                    });
+                    return Ok(());
+                }
+                Modal::PortPrompt { target, mut input } => {
+                    match code {
+                        KeyCode::Esc => self.modal = None,
+                        KeyCode::Enter => match input.parse::<u16>() {
+                            Ok(p) => {
+                                self.modal = None;
+                                self.start_background_switch(p, target);
+                            }
+                            Err(_) => {
+                                self.modal = Some(Modal::Message(format!("Invalid port '{input}'")))
+                            }
+                        },
+                        KeyCode::Backspace => {
+                            input.pop();
+                            self.modal = Some(Modal::PortPrompt { target, input });
+                        }
+                        KeyCode::Char(c) if c.is_ascii_digit() => {
+                            input.push(c);
+                            self.modal = Some(Modal::PortPrompt { target, input });
+                        }
+                        _ => self.modal = Some(Modal::PortPrompt { target, input }),
+                    }
+                    return Ok(());
+                }
+                Modal::Commands {
+                    mut query,
+                    mut selected,
+                } => {
+                    match code {
+                        KeyCode::Esc => {
+                            self.modal = None;
+                            return Ok(());
+                        }
+                        KeyCode::Enter => {
+                            let matches = command_matches(&query);
+                            self.modal = None;
+                            if let Some(&index) = matches.get(selected) {
+                                COMMANDS[index].1(self);
+                            }
+                            return Ok(());
+                        }
+                        KeyCode::Up => selected = selected.saturating_sub(1),
+                        KeyCode::Down => selected += 1,
+                        KeyCode::Backspace => {
+                            query.pop();
+                            selected = 0;
+                        }
+                        KeyCode::Char(c) => {
+                            query.push(c);
+                            selected = 0;
+                        }
+                        _ => {}
+                    }
+                    let len = command_matches(&query).len();
+                    selected = if len == 0 { 0 } else { selected.min(len - 1) };
+                    self.modal = Some(Modal::Commands { query, selected });
+                    return Ok(());
+                }
+            }
+            return Ok(());
+        }
+
+        // Typing a log filter captures every key until Enter or Esc.
+        if let Some(mut input) = self.log_filter_input.take() {
+            match code {
+                KeyCode::Enter => {
+                    if input.is_empty() {
+                        self.log_filter = None;
+                    } else {
+                        match Regex::new(&input) {
+                            Ok(filter) => self.log_filter = Some(filter),
+                            Err(e) => {
+                                self.modal =
+                                    Some(Modal::Message(format!("Invalid filter regex: {e}")));
+                            }
+                        }
+                    }
+                    self.log_scroll = 0;
+                }
+                KeyCode::Esc => {}
+                KeyCode::Backspace => {
+                    input.pop();
+                    self.log_filter_input = Some(input);
+                }
+                KeyCode::Char(c) => {
+                    input.push(c);
+                    self.log_filter_input = Some(input);
+                }
+                _ => self.log_filter_input = Some(input),
+            }
+            return Ok(());
+        }
+
+        if self.app.is_read_only() && is_mutating_key(&key) {
+            self.modal = Some(Modal::Message(
+                "Read-only mode: mutations are disabled".to_string(),
+            ));
+            return Ok(());
+        }
+        match code {
+            KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.modal = Some(Modal::Commands {
+                    query: String::new(),
+                    selected: 0,
+                });
+                return Ok(());
+            }
+            KeyCode::Char('q') | KeyCode::Esc => self.should_quit = true,
+            KeyCode::Tab => self.tab = self.tab.next(),
+            KeyCode::Char('1') => self.tab = Tab::Status,
+            KeyCode::Char('2') => self.tab = Tab::Containers,
+            KeyCode::Char('3') => self.tab = Tab::Routes,
+            KeyCode::Char('4') => self.tab = Tab::Networks,
+            KeyCode::Char('5') => self.tab = Tab::Logs,
+            KeyCode::Char('/') if self.tab == Tab::Logs => {
+                self.log_filter_input = Some(String::new());
+            }
+            KeyCode::Char('l')
+                if self.tab == Tab::Logs && key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                self.log_filter = None;
+                self.log_scroll = 0;
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Char('s') if self.tab == Tab::Routes => {
+                if let Some(route) = self.visible_routes().get(self.route_selected) {
+                    let port = route.primary_port();
+                    self.open_palette(Some(port));
+                }
+            }
+            KeyCode::Char('s') => {
+                self.confirm("Start the proxy?", ModalAction::StartProxy);
+            }
+            KeyCode::Char('x') => {
+                self.confirm("Stop the proxy?", ModalAction::StopProxy);
+            }
+            KeyCode::Char('r') => self.open_reload_diff(),
+            KeyCode::Enter if self.tab == Tab::Networks => self.open_network_detail().await,
+            KeyCode::Char('d') => self.delete_selected(),
+            KeyCode::Char('t') if self.tab == Tab::Routes => self.cycle_tag_filter(),
+            KeyCode::Char('H') => self.modal = Some(Modal::History { scroll: 0 }),
+            KeyCode::Char('n') if self.tab == Tab::Containers => self.create_selected_network(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Map a mouse event to the widget under it: clicking a tab title
+    /// switches tabs, clicking a table row selects it (a double-click on a
+    /// route opens the retarget palette) and the wheel moves the
+    /// selection, which on the Logs tab scrolls the log.
+    fn on_mouse(&mut self, mouse: MouseEvent) {
+        // A visible modal keeps capturing the keyboard; mouse input would
+        // act on widgets it covers, so drop it.
+        if self.modal.is_some() {
+            return;
+        }
+        match mouse.kind {
+            MouseEventKind::ScrollUp => self.move_selection(-1),
+            MouseEventKind::ScrollDown => self.move_selection(1),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let position = (mouse.column, mouse.row);
+                if let Some(tab) = tab_at(position, self.hit_areas.tabs) {
+                    self.tab = tab;
+                    return;
+                }
+                let Some(index) = row_at(position, self.hit_areas.body) else {
+                    return;
+                };
+                let double = self
+                    .last_click
+                    .take()
+                    .is_some_and(|(at, p)| p == position && at.elapsed() < DOUBLE_CLICK_WINDOW);
+                self.select_row(index);
+                if double && self.tab == Tab::Routes && !self.app.is_read_only() {
+                    if let Some(route) = self.visible_routes().get(self.route_selected) {
+                        let port = route.primary_port();
+                        self.open_palette(Some(port));
+                    }
+                } else {
+                    self.last_click = Some((Instant::now(), position));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Select the clicked row on the active tab, ignoring clicks past the
+    /// end of the table.
+    fn select_row(&mut self, index: usize) {
+        match self.tab {
+            Tab::Containers if index < self.config.containers.len() => {
+                self.container_selected = index;
+            }
+            Tab::Routes if index < self.visible_routes().len() => self.route_selected = index,
+            Tab::Networks if index < self.network_infos.len() => self.network_selected = index,
+            _ => {}
+        }
+    }
+
+    /// Confirm removal of the selected container or route, depending on
+    /// the active tab.
+    fn delete_selected(&mut self) {
+        match self.tab {
+            Tab::Containers => {
+                if let Some(container) = self.config.containers.get(self.container_selected) {
+                    let name = container.name.clone();
+                    let ports = self.config.routes_targeting(&name);
+                    let message = if ports.is_empty() {
+                        format!("Remove container '{name}'?")
+                    } else {
+                        let ports = ports
+                            .iter()
+                            .map(|p| p.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        format!("Remove container '{name}'? Routes {ports} point at it and will be dropped.")
+                    };
+                    self.confirm(&message, ModalAction::RemoveContainer(name.clone()));
+                }
+            }
+            Tab::Routes => {
+                if let Some(route) = self.visible_routes().get(self.route_selected) {
+                    let port = route.primary_port();
+                    self.confirm(
+                        &format!("Remove route on port {port}?"),
+                        ModalAction::StopRoute(port),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Inspect the selected network and show its attached containers.
+    async fn open_network_detail(&mut self) {
+        let Some(network) = self.network_infos.get(self.network_selected) else {
+            return;
+        };
+        let name = network.name.clone();
+        let body = match self.app.docker().inspect_network(&name).await {
+            Ok(containers) if containers.is_empty() => {
+                format!("{name}\n\nNo containers attached")
+            }
+            Ok(containers) => {
+                let mut lines = vec![name.clone(), String::new()];
+                for c in containers {
+                    // Configured containers stand out from bystanders.
+                    let known = if self.config.find_container(&c.name).is_some() {
+                        " *"
+                    } else {
+                        ""
+                    };
+                    lines.push(format!(
+                        "{}{known}  {}  {}",
+                        c.name, c.ipv4_address, c.mac_address
+                    ));
+                }
+                lines.push(String::new());
+                lines.push("* in config".to_string());
+                lines.join("\n")
+            }
+            Err(e) => format!("Error: {e:#}"),
+        };
+        self.modal = Some(Modal::NetworkDetail(body));
+    }
+
+    /// Confirm creation of the selected container's missing network.
+    fn create_selected_network(&mut self) {
+        if let Some(net) = self.selected_missing_network() {
+            self.confirm(
+                &format!("Create network '{net}'?"),
+                ModalAction::CreateNetwork(net.clone()),
+            );
+        }
+    }
+
+    /// The selected container's configured network when it does not exist
+    /// in Docker yet (based on the last `network_infos` refresh).
+    fn selected_missing_network(&self) -> Option<String> {
+        let container = self.config.containers.get(self.container_selected)?;
+        let net = container
+            .network
+            .clone()
+            .unwrap_or_else(|| self.config.network.clone());
+        if self.network_infos.iter().any(|n| n.name == net) {
+            None
+        } else {
+            Some(net)
+        }
+    }
+
+    /// Routes visible under the current tag filter.
+    fn visible_routes(&self) -> Vec<&crate::config::Route> {
+        self.config
+            .routes
+            .iter()
+            .filter(|r| {
+                self.route_tag_filter
+                    .as_deref()
+                    .is_none_or(|t| r.has_tag(t))
+            })
+            .collect()
+    }
+
+    /// Step the Routes tag filter through: no filter -> each known tag ->
+    /// no filter.
+    fn cycle_tag_filter(&mut self) {
+        let mut tags: Vec<String> = self
+            .config
+            .routes
+            .iter()
+            .flat_map(|r| r.tags.iter().cloned())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        self.route_tag_filter = match &self.route_tag_filter {
+            None => tags.first().cloned(),
+            Some(current) => tags
+                .iter()
+                .position(|t| t == current)
+                .and_then(|i| tags.get(i + 1))
+                .cloned(),
+        };
+        self.route_selected = 0;
+    }
+
+    /// Open the container palette; `port` is the route to retarget, `None`
+    /// prompts for one after the pick.
+    fn open_palette(&mut self, port: Option<u16>) {
+        self.modal = Some(Modal::Palette {
+            query: String::new(),
+            selected: 0,
+            port,
+        });
+    }
+
+    /// Container names matching `query`, best match first (name and label
+    /// both participate in matching).
+    fn palette_matches(&self, query: &str) -> Vec<String> {
+        let texts: Vec<String> = self
+            .config
+            .containers
+            .iter()
+            .map(|c| match &c.label {
+                Some(label) => format!("{} {label}", c.name),
+                None => c.name.clone(),
+            })
+            .collect();
+        fuzzy::rank(query, &texts)
+            .into_iter()
+            .map(|i| self.config.containers[i].name.clone())
+            .collect()
+    }
+
+    /// Kick off a switch (and the reload it implies) without blocking the
+    /// UI; progress shows as a spinner and errors surface via a modal.
+    fn start_background_switch(&mut self, port: u16, target: String) {
+        self.pending_label = Some(format!("switch port {port} to '{target}'"));
+        let app = self.app.clone();
+        self.pending_reload = Some(tokio::spawn(async move {
+            app.switch(port, &target, crate::app::SwitchOptions::default())
+                .await
+        }));
+    }
+
+    /// Open the reload preview: a diff of the nginx config last written to
+    /// the build directory against what the current config would generate.
+    fn open_reload_diff(&mut self) {
+        let new = match self.config.clone().interpolated() {
+            Ok(config) => crate::nginx::NginxConfigGenerator::generate(&config),
+            Err(e) => {
+                self.modal = Some(Modal::Message(format!("Error: {e:#}")));
+                return;
+            }
+        };
+        let old = std::fs::read_to_string(self.app.store().build_dir().join("nginx.conf"))
+            .unwrap_or_default();
+        self.modal = Some(Modal::Diff { old, new });
+    }
+
+    fn confirm(&mut self, message: &str, action: ModalAction) {
+        self.modal = Some(Modal::Confirm {
+            message: message.to_string(),
+            action,
+        });
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let (selected, len) = match self.tab {
+            Tab::Containers => (&mut self.container_selected, self.config.containers.len()),
+            Tab::Routes => {
+                let len = self.visible_routes().len();
+                (&mut self.route_selected, len)
+            }
+            Tab::Networks => (&mut self.network_selected, self.network_infos.len()),
+            Tab::Logs => {
+                // Up scrolls back through history, down returns to the tail.
+                self.log_scroll = self
+                    .log_scroll
+                    .saturating_add_signed(-delta)
+                    .min(self.logs.len());
+                return;
+            }
+            _ => return,
+        };
+        if len == 0 {
+            *selected = 0;
+            return;
+        }
+        let new = selected.saturating_add_signed(delta).min(len - 1);
+        *selected = new;
+    }
+
+    /// Run a confirmed action and surface the result in a message modal.
+    async fn execute_action(&mut self, action: ModalAction) {
+        let description = action_description(&action);
+        let result = match action {
+            ModalAction::StartProxy => self.app.start(false, true).await,
+            ModalAction::StopProxy => self.app.stop().await,
+            ModalAction::ConfirmedReload => self.app.reload(false).await,
+            // The confirmation popup already listed the affected routes.
+            ModalAction::RemoveContainer(name) => {
+                self.app.remove_container(&name, true, None).await
+            }
+            ModalAction::StopRoute(port) => self.app.stop_port(port, false).await,
+            ModalAction::CreateNetwork(net) => self
+                .app
+                .docker()
+                .ensure_network(&net, None)
+                .await
+                .map(|created| {
+                    if created {
+                        vec![format!("Created network '{net}'")]
+                    } else {
+                        vec![format!("Network '{net}' already exists")]
+                    }
+                }),
+        };
+        push_history(
+            &mut self.history,
+            HistoryEntry {
+                timestamp: Instant::now(),
+                description,
+                success: result.is_ok(),
+            },
+        );
+        self.modal = Some(match result {
+            Ok(lines) => Modal::Message(lines.join("\n")),
+            Err(e) => Modal::Message(format!("Error: {e:#}")),
+        });
+        // Force a refresh on the next loop iteration.
+        self.last_tick = Instant::now() - TICK_INTERVAL;
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        if area_too_small(frame.area()) {
+            frame.render_widget(Paragraph::new("Terminal too small"), frame.area());
+            return;
+        }
+        let chunks = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .split(frame.area());
+        self.hit_areas = HitAreas {
+            tabs: chunks[0],
+            body: chunks[1],
+        };
+
+        let titles: Vec<Line> = Tab::ALL.iter().map(|t| Line::from(t.title())).collect();
+        let tabs = Tabs::new(titles)
+            .select(self.tab.index())
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(if self.app.is_read_only() {
+                        "proxy-manager [READ-ONLY]"
+                    } else {
+                        "proxy-manager"
+                    }),
+            )
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            );
+        frame.render_widget(tabs, chunks[0]);
+
+        match self.tab {
+            Tab::Status => self.render_status(frame, chunks[1]),
+            Tab::Containers => self.render_containers(frame, chunks[1]),
+            Tab::Routes => self.render_routes(frame, chunks[1]),
+            Tab::Networks => self.render_networks(frame, chunks[1]),
+            Tab::Logs => self.render_logs(frame, chunks[1]),
+        }
+
+        let mut help_text = if self.app.is_read_only() {
+            String::from(
+                "q quit | Tab/1-5 tabs | j/k select | t tag filter | READ-ONLY: mutations disabled | \u{25cf} green=running yellow=transition red=down",
+            )
+        } else {
+            String::from(
+                "q quit | Tab/1-5 tabs | j/k select | s start/switch | x stop | r reload | d delete | t tag filter | H history | Ctrl+P palette | \u{25cf} green=running yellow=transition red=down",
+            )
+        };
+        if self.pending_reload.is_some() {
+            let frame_char = ['|', '/', '-', '\\'][self.spinner % 4];
+            help_text = format!("{frame_char} reloading...  {help_text}");
+        }
+        let help = Paragraph::new(help_text).style(Style::default().fg(Color::DarkGray));
+        frame.render_widget(help, chunks[2]);
+
+        if let Some(modal) = &self.modal {
+            self.draw_modal(frame, modal);
+        }
+    }
+
+    fn render_status(&self, frame: &mut Frame, area: Rect) {
+        let status = self.proxy_status.as_deref().unwrap_or("not running");
+        let style = if status == "running" {
+            Style::default().fg(Color::Green)
+        } else {
+            Style::default().fg(Color::Red)
+        };
+        let mut status_spans = vec![Span::raw("Status: "), Span::styled(status, style)];
+        if let Some(age) = self.proxy_age {
+            status_spans.push(Span::raw(format!("  (Running for {})", format_age(age))));
+        }
+        let lines = vec![
+            Line::from(format!("Proxy: {}", self.config.proxy_name)),
+            Line::from(status_spans),
+            Line::from(format!("Network: {}", self.config.network)),
+            Line::from(self.config.count_stats().summary()),
+        ];
+        let widget =
+            Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Status"));
+        frame.render_widget(widget, area);
+    }
+
+    fn render_containers(&self, frame: &mut Frame, area: Rect) {
+        let rows: Vec<Row> = self
+            .config
+            .containers
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let status = self.container_statuses.get(&c.name).cloned().flatten();
+                let row = Row::new(vec![
+                    ratatui::text::Text::styled(
+                        "\u{25cf}",
+                        Style::default().fg(status_color(status.as_deref())),
+                    ),
+                    c.name.clone().into(),
+                    c.label.clone().unwrap_or_default().into(),
+                    c.port.to_string().into(),
+                    c.network
+                        .clone()
+                        .unwrap_or_else(|| self.config.network.clone())
+                        .into(),
+                    ratatui::text::Text::styled(
+                        c.description.clone().unwrap_or_default(),
+                        Style::default().add_modifier(Modifier::DIM),
+                    ),
+                ]);
+                if i == self.container_selected {
+                    row.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    row
+                }
+            })
+            .collect();
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(1),
+                Constraint::Min(16),
+                Constraint::Min(10),
+                Constraint::Length(6),
+                Constraint::Min(12),
+                Constraint::Min(0),
+            ],
+        )
+        .header(Row::new(vec!["", "Name", "Label", "Port", "Network", "Note"]).style(bold()))
+        .block(Block::default().borders(Borders::ALL).title("Containers"));
+        frame.render_widget(table, area);
+    }
+
+    fn render_routes(&self, frame: &mut Frame, area: Rect) {
+        let rows: Vec<Row> = self
+            .visible_routes()
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                let (target, internal) = if r.is_static() {
+                    ("[static]".to_string(), String::new())
+                } else {
+                    (r.target.clone(), r.internal_port.to_string())
+                };
+                let row = Row::new(vec![
+                    ratatui::widgets::Cell::from(r.ports_display()),
+                    target.into(),
+                    internal.into(),
+                    r.tags.join(", ").into(),
+                    ratatui::widgets::Cell::from(ratatui::text::Text::styled(
+                        r.description.clone().unwrap_or_default(),
+                        Style::default().add_modifier(Modifier::DIM),
+                    )),
+                ]);
+                if i == self.route_selected {
+                    row.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    row
+                }
+            })
+            .collect();
+        let title = match &self.route_tag_filter {
+            Some(tag) => format!("Routes (tag: {tag})"),
+            None => "Routes".to_string(),
+        };
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(10),
+                Constraint::Min(16),
+                Constraint::Length(13),
+                Constraint::Min(10),
+                Constraint::Min(0),
+            ],
+        )
+        .header(
+            Row::new(vec!["Host port", "Target", "Internal port", "Tags", "Note"]).style(bold()),
+        )
+        .block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(table, area);
+    }
+
+    fn render_networks(&self, frame: &mut Frame, area: Rect) {
+        let rows: Vec<Row> = self
+            .network_infos
+            .iter()
+            .enumerate()
+            .map(|(i, n)| {
+                let row = Row::new(vec![
+                    n.name.clone(),
+                    n.driver.clone(),
+                    n.container_count
+                        .map(|c| c.to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                ]);
+                if i == self.network_selected {
+                    row.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    row
+                }
+            })
+            .collect();
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Min(16),
+                Constraint::Length(10),
+                Constraint::Length(10),
+            ],
+        )
+        .header(Row::new(vec!["Name", "Driver", "Containers"]).style(bold()))
+        .block(Block::default().borders(Borders::ALL).title("Networks"));
+        frame.render_widget(table, area);
+    }
+
+    fn render_logs(&self, frame: &mut Frame, area: Rect) {
+        let visible = area.height.saturating_sub(2) as usize;
+        let end = self.logs.len() - self.log_scroll.min(self.logs.len());
+        let start = end.saturating_sub(visible);
+        let items: Vec<ListItem> = self.logs[start..end]
+            .iter()
+            .map(|l| ListItem::new(l.clone()).style(log_line_style(self.log_filter.as_ref(), l)))
+            .collect();
+        let title = match (&self.log_filter_input, &self.log_filter) {
+            (Some(input), _) => {
+                format!("Logs ({})  Filter: {input}_", self.config.proxy_name)
+            }
+            (None, Some(filter)) => format!(
+                "Logs ({})  Filter: {}",
+                self.config.proxy_name,
+                filter.as_str()
+            ),
+            (None, None) => format!("Logs ({})", self.config.proxy_name),
+        };
+        let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(list, area);
+    }
+
+    fn draw_modal(&self, frame: &mut Frame, modal: &Modal) {
+        let (title, body) = match modal {
+            Modal::Confirm { message, .. } => ("Confirm (y/n)", message.clone()),
+            Modal::Message(text) => ("Message (any key)", text.clone()),
+            Modal::Palette {
+                query,
+                selected,
+                port,
+            } => {
+                self.draw_palette(frame, query, *selected, *port);
+                return;
+            }
+            Modal::PortPrompt { target, input } => (
+                "Host port (Enter to confirm)",
+                format!("Switch to '{target}' on port: {input}_"),
+            ),
+            Modal::Diff { old, new } => {
+                self.draw_diff(frame, old, new);
+                return;
+            }
+            Modal::Commands { query, selected } => {
+                self.draw_commands(frame, query, *selected);
+                return;
+            }
+            Modal::NetworkDetail(body) => ("Network (any key)", body.clone()),
+            Modal::History { scroll } => {
+                self.draw_history(frame, *scroll);
+                return;
+            }
+        };
+        let area = centered_rect(60, 30, frame.area());
+        frame.render_widget(Clear, area);
+        let widget = Paragraph::new(body)
+            .wrap(ratatui::widgets::Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(widget, area);
+    }
+
+    fn draw_diff(&self, frame: &mut Frame, old: &str, new: &str) {
+        let area = centered_rect(80, 70, frame.area());
+        frame.render_widget(Clear, area);
+        let diff = similar::TextDiff::from_lines(old, new);
+        let mut lines: Vec<Line> = Vec::new();
+        for change in diff.iter_all_changes() {
+            let text = change.value().trim_end_matches('\n').to_string();
+            lines.push(match change.tag() {
+                similar::ChangeTag::Delete => {
+                    Line::styled(format!("-{text}"), Style::default().fg(Color::Red))
+                }
+                similar::ChangeTag::Insert => {
+                    Line::styled(format!("+{text}"), Style::default().fg(Color::Green))
+                }
+                similar::ChangeTag::Equal => Line::raw(format!(" {text}")),
+            });
+        }
+        if diff.ratio() == 1.0 {
+            lines = vec![Line::raw("No changes; reload rebuilds the same config.")];
+        }
+        let widget = Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Reload diff (y confirms, n cancels)"),
+        );
+        frame.render_widget(widget, area);
+    }
+
+    /// Full-width command palette: an input line over the filtered actions.
+    fn draw_commands(&self, frame: &mut Frame, query: &str, selected: usize) {
+        let full = frame.area();
+        let height = (command_matches(query).len() as u16 + 3).min(full.height);
+        let area = Rect::new(full.x, full.y, full.width, height);
+        frame.render_widget(Clear, area);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title("Command palette (Enter runs, Esc closes)");
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let chunks = Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).split(inner);
+        frame.render_widget(Paragraph::new(format!("> {query}_")), chunks[0]);
+
+        let items: Vec<ListItem> = command_matches(query)
+            .into_iter()
+            .enumerate()
+            .map(|(i, index)| {
+                let item = ListItem::new(COMMANDS[index].0);
+                if i == selected {
+                    item.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    item
+                }
+            })
+            .collect();
+        frame.render_widget(List::new(items), chunks[1]);
+    }
+
+    /// Recent Commands popup: newest first, green for success, red for
+    /// failure, each entry stamped with how long ago it ran.
+    fn draw_history(&self, frame: &mut Frame, scroll: usize) {
+        let area = centered_rect(60, 60, frame.area());
+        frame.render_widget(Clear, area);
+        let items: Vec<ListItem> = if self.history.is_empty() {
+            vec![ListItem::new("No operations yet")]
+        } else {
+            self.history
+                .iter()
+                .rev()
+                .skip(scroll)
+                .map(|entry| {
+                    let color = if entry.success {
+                        Color::Green
+                    } else {
+                        Color::Red
+                    };
+                    ListItem::new(format!(
+                        "{:>8}  {}",
+                        format_ago(entry.timestamp.elapsed()),
+                        entry.description
+                    ))
+                    .style(Style::default().fg(color))
+                })
+                .collect()
+        };
+        let list = List::new(items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Recent Commands (j/k scroll, any other key closes)"),
+        );
+        frame.render_widget(list, area);
+    }
+
+    fn draw_palette(&self, frame: &mut Frame, query: &str, selected: usize, port: Option<u16>) {
+        let title = match port {
+            Some(port) => format!("Switch port {port} to... (Enter picks, Esc closes)"),
+            None => "Switch... (Enter picks, Esc closes)".to_string(),
+        };
+        let area = centered_rect(60, 50, frame.area());
+        frame.render_widget(Clear, area);
+        let block = Block::default().borders(Borders::ALL).title(title);
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let chunks = Layout::vertical([Constraint::Length(1), Constraint::Min(1)]).split(inner);
+        frame.render_widget(Paragraph::new(format!("> {query}_")), chunks[0]);
+
+        let items: Vec<ListItem> = self
+            .palette_matches(query)
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let label = self
+                    .config
+                    .find_container(name)
+                    .and_then(|c| c.label.clone())
+                    .map(|l| format!("  ({l})"))
+                    .unwrap_or_default();
+                let item = ListItem::new(format!("{name}{label}"));
+                if i == selected {
+                    item.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    item
+                }
+            })
+            .collect();
+        frame.render_widget(List::new(items), chunks[1]);
+    }
+}
+
+/// The tab whose title sits under `position` in the tab bar, if any.
+/// Titles are rendered one space of padding either side, separated by a
+/// one-column divider, inside the block border.
+fn tab_at(position: (u16, u16), tabs_area: Rect) -> Option<Tab> {
+    let (column, row) = position;
+    // The single title line sits inside the top border.
+    if row != tabs_area.y + 1 {
+        return None;
+    }
+    let mut x = tabs_area.x + 1;
+    for tab in Tab::ALL {
+        let width = tab.title().len() as u16 + 2;
+        if column >= x && column < x + width {
+            return Some(tab);
+        }
+        x += width + 1;
+    }
+    None
+}
+
+/// The table row index under `position` in a bordered table with a header
+/// line, or `None` for the border, the header or anything outside.
+fn row_at(position: (u16, u16), area: Rect) -> Option<usize> {
+    let (column, row) = position;
+    if column <= area.x || column >= area.x + area.width.saturating_sub(1) {
+        return None;
+    }
+    // Top border and header precede the first data row; the bottom border
+    // is excluded.
+    let first = area.y + 2;
+    let last = area.y + area.height.saturating_sub(1);
+    if row < first || row >= last {
+        return None;
+    }
+    Some((row - first) as usize)
+}
+
+/// One operation performed through the TUI, kept for the `H` popup.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub timestamp: Instant,
+    pub description: String,
+    pub success: bool,
+}
+
+/// Append to the history, dropping the oldest entries beyond
+/// [`HISTORY_LIMIT`].
+fn push_history(history: &mut VecDeque<HistoryEntry>, entry: HistoryEntry) {
+    history.push_back(entry);
+    while history.len() > HISTORY_LIMIT {
+        history.pop_front();
+    }
+}
+
+/// Human description of a confirmed action for the history popup.
+fn action_description(action: &ModalAction) -> String {
+    match action {
+        ModalAction::StartProxy => "start proxy".to_string(),
+        ModalAction::StopProxy => "stop proxy".to_string(),
+        ModalAction::ConfirmedReload => "reload config".to_string(),
+        ModalAction::RemoveContainer(name) => format!("remove container '{name}'"),
+        ModalAction::StopRoute(port) => format!("remove route on port {port}"),
+        ModalAction::CreateNetwork(net) => format!("create network '{net}'"),
+    }
+}
+
+/// Render how long ago something happened ("5s ago", "2m ago", "3h ago").
+fn format_ago(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match secs {
+        0..=59 => format!("{secs}s ago"),
+        60..=3599 => format!("{}m ago", secs / 60),
+        _ => format!("{}h ago", secs / 3600),
+    }
+}
+
+/// Render an uptime as "Xh Ym" (or "Ym" under an hour).
+fn format_age(age: std::time::Duration) -> String {
+    let minutes = age.as_secs() / 60;
+    let (hours, minutes) = (minutes / 60, minutes % 60);
+    if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else {
+        format!("{minutes}m")
+    }
+}
+
+/// Whether a key would open a mutation (start/stop/reload/delete, network
+/// creation or the switch palette); these are refused in read-only mode.
+fn is_mutating_key(key: &KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Char('p') => key.modifiers.contains(KeyModifiers::CONTROL),
+        KeyCode::Char('s' | 'x' | 'r' | 'd' | 'n') => true,
+        _ => false,
+    }
+}
+
+/// Style for one log line under the active filter: matches stand out,
+/// everything else recedes, no filter leaves lines untouched.
+fn log_line_style(filter: Option<&Regex>, line: &str) -> Style {
+    match filter {
+        Some(filter) if filter.is_match(line) => Style::default().fg(Color::Yellow),
+        Some(_) => Style::default().add_modifier(Modifier::DIM),
+        None => Style::default(),
+    }
+}
+
+/// Indicator color for a Docker status string: green running, yellow in
+/// transition, red stopped or missing.
+fn status_color(status: Option<&str>) -> Color {
+    match status {
+        Some("running") => Color::Green,
+        Some("paused") | Some("restarting") | Some("created") => Color::Yellow,
+        _ => Color::Red,
+    }
+}
+
+fn bold() -> Style {
+    Style::default().add_modifier(Modifier::BOLD)
+}
+
+/// A rect centered in `area` sized by percentage of each dimension.
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::vertical([
+        Constraint::Percentage((100 - percent_y) / 2),
+        Constraint::Percentage(percent_y),
+        Constraint::Percentage((100 - percent_y) / 2),
+    ])
+    .split(area);
+    Layout::horizontal([
+        Constraint::Percentage((100 - percent_x) / 2),
+        Constraint::Percentage(percent_x),
+        Constraint::Percentage((100 - percent_x) / 2),
+    ])
+    .split(vertical[1])[1]
+}
+
+/// Whether `area` is too small for the tab layout to render safely.
+fn area_too_small(area: Rect) -> bool {
+    area.width < MIN_WIDTH || area.height < MIN_HEIGHT
+}
+
+/// Where TUI panics are logged, next to the config file.
+fn crash_log_path(config_dir: &Path) -> PathBuf {
+    config_dir.join("tui-crash.log")
+}
+
+/// Write a panic report (message plus backtrace) to `path`; errors are
+/// swallowed because this runs inside a panic hook.
+fn write_crash_log(path: &Path, message: &str) {
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let _ = std::fs::File::create(path).map(|mut f| {
+        let _ = writeln!(f, "TUI panic: {message}");
+        let _ = writeln!(f, "{}", std::backtrace::Backtrace::force_capture());
+    });
+}
+
+/// Chain a panic hook that restores the terminal (raw mode off, alternate
+/// screen and mouse capture left) and records the panic in a crash log
+/// before handing off to the previous hook.
+fn install_panic_hook(crash_log: PathBuf) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic payload".to_string());
+        let message = match info.location() {
+            Some(location) => format!("{message} (at {location})"),
+            None => message,
+        };
+        write_crash_log(&crash_log, &message);
+        previous(info);
+    }));
+}
+
+/// Undo the terminal setup: raw mode off, back to the main screen, mouse
+/// capture released and the cursor shown again. Shared by the normal
+/// teardown, the panic hook and the SIGTERM path, so errors are ignored.
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = crossterm::execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        crossterm::cursor::Show
+    );
+}
+
+/// Set up the terminal, run the TUI and restore the terminal on exit,
+/// panic or SIGTERM. `mouse` enables click/scroll handling; without it
+/// capture stays off so terminal-native text selection keeps working.
+pub async fn run_tui(app: App, mouse: bool) -> Result<()> {
+    install_panic_hook(crash_log_path(app.store().config_dir()));
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    crossterm::execute!(stdout, EnterAlternateScreen)?;
+    if mouse {
+        crossterm::execute!(stdout, EnableMouseCapture)?;
+    }
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    let mut tui = TuiApp::new(app)?;
+    // SIGTERM flips the quit flag so the loop winds down cleanly (pending
+    // reloads included) and the teardown below still runs.
+    let quit = tui.quit_signal();
+    tokio::spawn(async move {
+        if let Ok(mut sigterm) =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        {
+            sigterm.recv().await;
+            quit.store(true, Ordering::Relaxed);
+        }
+    });
+    let result = tui.run(&mut terminal).await;
+
+    restore_terminal();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiny_terminals_are_rejected_before_layout() {
+        assert!(area_too_small(Rect::new(0, 0, 0, 0)));
+        assert!(area_too_small(Rect::new(0, 0, 80, MIN_HEIGHT - 1)));
+        assert!(area_too_small(Rect::new(0, 0, MIN_WIDTH - 1, 24)));
+        assert!(!area_too_small(Rect::new(0, 0, MIN_WIDTH, MIN_HEIGHT)));
+        assert!(!area_too_small(Rect::new(0, 0, 80, 24)));
+    }
+
+    #[test]
+    fn log_filter_styles_matches_and_dims_the_rest() {
+        let filter = Regex::new("GET /api").unwrap();
+        assert_eq!(
+            log_line_style(Some(&filter), "10.0.0.1 GET /api/users 200"),
+            Style::default().fg(Color::Yellow)
+        );
+        assert_eq!(
+            log_line_style(Some(&filter), "10.0.0.1 GET /static/a.css 200"),
+            Style::default().add_modifier(Modifier::DIM)
+        );
+        assert_eq!(log_line_style(None, "anything"), Style::default());
+    }
+
+    #[test]
+    fn command_palette_filters_by_substring() {
+        assert_eq!(command_matches("").len(), COMMANDS.len());
+        let matches = command_matches("proxy");
+        assert_eq!(matches.len(), 2);
+        assert_eq!(COMMANDS[matches[0]].0, "start proxy");
+        assert_eq!(COMMANDS[matches[1]].0, "stop proxy");
+        // Case-insensitive, and unknown text matches nothing.
+        assert_eq!(command_matches("QUIT").len(), 1);
+        assert!(command_matches("frobnicate").is_empty());
+    }
+
+    #[test]
+    fn tab_hits_map_titles_and_ignore_everything_else() {
+        let area = Rect::new(0, 0, 80, 3);
+        // "Status" starts after the border with one space of padding.
+        assert_eq!(tab_at((1, 1), area), Some(Tab::Status));
+        assert_eq!(tab_at((8, 1), area), Some(Tab::Status));
+        // Past the divider, "Containers" begins.
+        assert_eq!(tab_at((10, 1), area), Some(Tab::Containers));
+        // The divider column itself hits nothing.
+        assert_eq!(tab_at((9, 1), area), None);
+        // Border rows and far-right columns miss.
+        assert_eq!(tab_at((1, 0), area), None);
+        assert_eq!(tab_at((1, 2), area), None);
+        assert_eq!(tab_at((79, 1), area), None);
+    }
+
+    #[test]
+    fn row_hits_skip_borders_and_the_header() {
+        let area = Rect::new(0, 3, 80, 10);
+        // Top border and header are not rows.
+        assert_eq!(row_at((5, 3), area), None);
+        assert_eq!(row_at((5, 4), area), None);
+        assert_eq!(row_at((5, 5), area), Some(0));
+        assert_eq!(row_at((5, 8), area), Some(3));
+        // Bottom border and anything below miss.
+        assert_eq!(row_at((5, 12), area), None);
+        assert_eq!(row_at((5, 40), area), None);
+        // Side borders and out-of-bounds columns miss.
+        assert_eq!(row_at((0, 5), area), None);
+        assert_eq!(row_at((79, 5), area), None);
+        assert_eq!(row_at((80, 5), area), None);
+    }
+
+    #[test]
+    fn history_keeps_only_the_most_recent_operations() {
+        let mut history = VecDeque::new();
+        for i in 0..HISTORY_LIMIT + 5 {
+            push_history(
+                &mut history,
+                HistoryEntry {
+                    timestamp: Instant::now(),
+                    description: format!("op {i}"),
+                    success: true,
+                },
+            );
+        }
+        assert_eq!(history.len(), HISTORY_LIMIT);
+        // The oldest entries fell off the front.
+        assert_eq!(history.front().unwrap().description, "op 5");
+        assert_eq!(
+            history.back().unwrap().description,
+            format!("op {}", HISTORY_LIMIT + 4)
+        );
+    }
+
+    #[test]
+    fn elapsed_times_render_coarsely() {
+        assert_eq!(format_ago(Duration::from_secs(5)), "5s ago");
+        assert_eq!(format_ago(Duration::from_secs(120)), "2m ago");
+        assert_eq!(format_ago(Duration::from_secs(7200)), "2h ago");
+    }
+
+    #[test]
+    fn crash_log_records_the_panic_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = crash_log_path(dir.path());
+        write_crash_log(&path, "index out of bounds (at src/tui/mod.rs:1:1)");
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert!(log.contains("TUI panic: index out of bounds"));
+    }
+}
@@ -0,0 +1,142 @@
+//! In-place self-update from a release URL.
+//!
+//! The release URL serves a small JSON document describing the latest
+//! version, the binary download URL and its SHA-256 checksum. The update
+//! downloads next to the current executable and atomically renames over it.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+/// Where release metadata is fetched from when `--url` is not given.
+/// Placeholder until an official release feed exists.
+pub const DEFAULT_RELEASE_URL: &str = "https://example.invalid/proxy-manager/latest.json";
+
+/// Version compiled into this binary.
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Release metadata served at the release URL.
+#[derive(Debug, Deserialize)]
+pub struct ReleaseMeta {
+    /// Latest released version, e.g. "0.2.0".
+    pub version: String,
+    /// Download URL of the binary for this platform.
+    pub url: String,
+    /// Lowercase hex SHA-256 of the binary.
+    pub sha256: String,
+}
+
+/// Check for (and unless `check_only`, install) a newer release.
+pub async fn self_update(url: Option<&str>, check_only: bool) -> Result<Vec<String>> {
+    let url = url.unwrap_or(DEFAULT_RELEASE_URL);
+    let client = reqwest::Client::new();
+    let meta: ReleaseMeta = client
+        .get(url)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .with_context(|| format!("failed to fetch release metadata from {url}"))?
+        .json()
+        .await
+        .context("release metadata is not valid JSON")?;
+
+    if !update_available(CURRENT_VERSION, &meta.version) {
+        return Ok(vec![format!("Already up to date (v{CURRENT_VERSION})")]);
+    }
+    if check_only {
+        return Ok(vec![format!(
+            "Update available: v{CURRENT_VERSION} -> v{}",
+            meta.version
+        )]);
+    }
+
+    let binary = client
+        .get(&meta.url)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .with_context(|| format!("failed to download {}", meta.url))?
+        .bytes()
+        .await?;
+
+    let digest = sha256_hex(&binary);
+    if !digest.eq_ignore_ascii_case(&meta.sha256) {
+        bail!(
+            "checksum mismatch for downloaded binary: expected {}, got {digest}",
+            meta.sha256
+        );
+    }
+
+    let exe = std::env::current_exe().context("cannot determine current executable path")?;
+    // Stage in the same directory so the final rename stays on one
+    // filesystem and is atomic.
+    let staged = exe.with_extension("update");
+    std::fs::write(&staged, &binary)
+        .with_context(|| format!("failed to write {}", staged.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))?;
+    }
+    std::fs::rename(&staged, &exe)
+        .with_context(|| format!("failed to replace {}", exe.display()))?;
+
+    Ok(vec![format!(
+        "Updated v{CURRENT_VERSION} -> v{} ({})",
+        meta.version,
+        exe.display()
+    )])
+}
+
+/// True when `latest` is newer than `current`. Versions compare numerically
+/// component by component; anything unparsable falls back to inequality so
+/// a changed feed still triggers an update.
+fn update_available(current: &str, latest: &str) -> bool {
+    match (parse_version(current), parse_version(latest)) {
+        (Some(current), Some(latest)) => latest > current,
+        _ => current != latest,
+    }
+}
+
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version
+        .trim_start_matches('v')
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+/// Lowercase hex SHA-256 of `bytes`.
+fn sha256_hex(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_available_compares_numerically() {
+        assert!(update_available("0.1.0", "0.2.0"));
+        assert!(update_available("0.9.0", "0.10.0"));
+        assert!(!update_available("0.2.0", "0.2.0"));
+        assert!(!update_available("0.2.0", "0.1.9"));
+        // A 'v' prefix in the feed is tolerated.
+        assert!(update_available("0.1.0", "v0.1.1"));
+    }
+
+    #[test]
+    fn unparsable_versions_fall_back_to_inequality() {
+        assert!(update_available("0.1.0", "nightly"));
+        assert!(!update_available("nightly", "nightly"));
+    }
+
+    #[test]
+    fn sha256_hex_matches_known_vector() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
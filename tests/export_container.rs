@@ -0,0 +1,25 @@
+//! Exports a real container's filesystem and checks the tar parses.
+//! Ignored by default: requires a Docker daemon with a running container
+//! named "proxy-manager".
+
+use proxy_manager::docker::DockerClient;
+
+#[tokio::test]
+#[ignore = "needs a Docker daemon with a 'proxy-manager' container"]
+async fn exported_container_is_a_valid_tar_archive() {
+    let client = DockerClient::with_runtime(None).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("export.tar");
+    client
+        .export_container_to_tar("proxy-manager", &output)
+        .await
+        .unwrap();
+
+    let mut archive = tar::Archive::new(std::fs::File::open(&output).unwrap());
+    let mut entries = 0;
+    for entry in archive.entries().unwrap() {
+        entry.unwrap();
+        entries += 1;
+    }
+    assert!(entries > 0, "export produced an empty archive");
+}
@@ -0,0 +1,136 @@
+//! Golden-file tests pinning the generated nginx config byte-for-byte.
+//!
+//! Any template change must show up as a reviewed diff against the fixtures
+//! in `tests/fixtures/`. Run with `UPDATE_SNAPSHOTS=1` to rewrite them after
+//! an intentional change.
+
+use std::path::PathBuf;
+
+use proxy_manager::config::{Config, Container};
+use proxy_manager::nginx::NginxConfigGenerator;
+
+fn container(name: &str, port: u16, network: Option<&str>) -> Container {
+    Container {
+        name: name.to_string(),
+        label: None,
+        port,
+        network: network.map(str::to_string),
+        static_root: None,
+        response_rewrites: Vec::new(),
+        allowed_methods: None,
+        tls_backend: false,
+        tls_backend_insecure: false,
+        auth_request_url: None,
+        description: None,
+        dns_aliases: Vec::new(),
+        aliases: Vec::new(),
+    }
+}
+
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("{name}.conf"));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("missing fixture {}: {e}", path.display()));
+    assert_eq!(
+        actual,
+        expected,
+        "generated config diverges from {}; rerun with UPDATE_SNAPSHOTS=1 if intended",
+        path.display()
+    );
+}
+
+#[test]
+fn empty_config() {
+    assert_snapshot("empty", &NginxConfigGenerator::generate(&Config::default()));
+}
+
+#[test]
+fn single_route() {
+    let mut config = Config::default();
+    config.upsert_container(container("app1", 8080, None));
+    config.set_route(8000, "app1", 8080);
+    assert_snapshot("single_route", &NginxConfigGenerator::generate(&config));
+}
+
+#[test]
+fn multi_route_multi_network() {
+    let mut config = Config::default();
+    config.upsert_container(container("frontend", 3000, None));
+    config.upsert_container(container("api", 8080, Some("backend")));
+    config.set_route(80, "frontend", 3000);
+    config.set_route(8080, "api", 8080);
+    assert_snapshot(
+        "multi_route_multi_network",
+        &NginxConfigGenerator::generate(&config),
+    );
+}
+
+#[test]
+fn route_with_missing_container() {
+    // Validation rejects this, but the generator must still emit something
+    // inspectable rather than panic.
+    let mut config = Config::default();
+    config.set_route(8000, "ghost", 8080);
+    assert_snapshot(
+        "missing_container",
+        &NginxConfigGenerator::generate(&config),
+    );
+}
+
+#[test]
+fn custom_resolver() {
+    let mut config = Config {
+        resolver: Some("10.0.0.2:5353".to_string()),
+        resolver_valid_secs: Some(10),
+        ..Config::default()
+    };
+    config.upsert_container(container("app1", 8080, None));
+    config.set_route(8000, "app1", 8080);
+    assert_snapshot("custom_resolver", &NginxConfigGenerator::generate(&config));
+}
+
+#[test]
+fn multiple_resolvers() {
+    let mut config = Config {
+        resolver: Some("127.0.0.11 8.8.8.8:53".to_string()),
+        ..Config::default()
+    };
+    config.upsert_container(container("app1", 8080, None));
+    config.set_route(8000, "app1", 8080);
+    assert_snapshot(
+        "multiple_resolvers",
+        &NginxConfigGenerator::generate(&config),
+    );
+}
+
+#[test]
+fn resolver_off_proxies_directly() {
+    let mut config = Config {
+        resolver: Some("off".to_string()),
+        ..Config::default()
+    };
+    config.upsert_container(container("app1", 8080, None));
+    config.set_route(8000, "app1", 8080);
+    let conf = NginxConfigGenerator::generate(&config);
+    assert!(!conf.contains("resolver"));
+    assert!(!conf.contains("set $backend_addr"));
+    assert!(conf.contains("proxy_pass http://app1:8080;"));
+    assert_snapshot("resolver_off", &conf);
+}
+
+#[test]
+fn default_port_container() {
+    // Route created without an explicit container port, taking the
+    // container's configured one.
+    let mut config = Config::default();
+    config.upsert_container(container("web", 80, None));
+    let port = config.find_container("web").unwrap().port;
+    config.set_route(8000, "web", port);
+    assert_snapshot("default_port", &NginxConfigGenerator::generate(&config));
+}
@@ -0,0 +1,12 @@
+//! End-to-end probe against a locally running proxy. Ignored by default:
+//! requires `proxy-manager start` with a route on port 8000 beforehand.
+
+use proxy_manager::probe::{probe, ReqwestClient};
+
+#[tokio::test]
+#[ignore = "needs a running proxy with a route on port 8000"]
+async fn probes_a_real_proxy() {
+    let client = ReqwestClient::new().unwrap();
+    let (response, _) = probe(&client, "http://127.0.0.1:8000/").await.unwrap();
+    assert!(response.status < 600);
+}